@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+/// Declares the actions once with their titles, app actions are run by eta and editor actions by
+/// the focused code editor.
+macro_rules! actions {
+    (
+        app { $($app:ident => $app_title:literal,)* }
+        editor { $($editor:ident => $editor_title:literal,)* }
+    ) => {
+        /// Every command of eta which can be bound to a key.
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+        #[serde(rename_all = "snake_case")]
+        pub enum Action {
+            $($app,)*
+            $($editor,)*
+        }
+
+        impl Action {
+            pub const ALL: &'static [Action] = &[$(Action::$app,)* $(Action::$editor,)*];
+
+            pub fn title(&self) -> &'static str {
+                match self {
+                    $(Action::$app => $app_title,)*
+                    $(Action::$editor => $editor_title,)*
+                }
+            }
+
+            /// Editor actions are executed by the focused code editor.
+            pub fn is_editor(&self) -> bool {
+                matches!(self, $(Action::$editor)|*)
+            }
+        }
+    };
+}
+
+actions! {
+    app {
+        // project
+        CommandPalette => "Command Palette",
+        QuickOpen => "Go to File...",
+        GoToLine => "Go to Line...",
+        GoToSymbol => "Go to Symbol...",
+        SaveFile => "Save File",
+        FormatDocument => "Format Document",
+        FormatSelection => "Format Selection",
+        ConvertIndentationToSpaces => "Convert Indentation to Spaces",
+        ConvertIndentationToTabs => "Convert Indentation to Tabs",
+        CloseFile => "Close File",
+        OpenProject => "Open Project",
+        OpenWorkspace => "Open Workspace...",
+        AddFolder => "Add Folder to Workspace...",
+        SaveWorkspace => "Save Workspace",
+        ClearCache => "Clear Cache",
+        ToggleTerminal => "Toggle Terminal",
+        ToggleVim => "Toggle Vim Mode",
+        ReloadKeymap => "Reload Keymap",
+        EditKeymap => "Edit Keymap",
+        EditSnippets => "Edit Snippets",
+        OpenSettings => "Settings...",
+        ZoomIn => "Zoom In",
+        ZoomOut => "Zoom Out",
+        ResetZoom => "Reset Zoom",
+        Quit => "Quit",
+        // file tree
+        NewFile => "New File...",
+        NewDirectory => "New Directory...",
+        RenameFile => "Rename File...",
+        RevealActiveFile => "Reveal Active File",
+        UndoFileOperation => "Undo File Operation",
+    }
+    editor {
+        Undo => "Undo",
+        Redo => "Redo",
+        Indent => "Indent",
+        Outdent => "Outdent",
+        TriggerCompletion => "Trigger Completion",
+        NewLine => "New Line",
+        DeleteCharBackward => "Delete Previous Character",
+        DeleteCharForward => "Delete Next Character",
+        DeleteWordBackward => "Delete Previous Word",
+        DeleteWordForward => "Delete Next Word",
+        DeleteToLineStart => "Delete To Line Start",
+        DeleteToLineEnd => "Delete To Line End",
+        CursorLeft => "Cursor Left",
+        CursorRight => "Cursor Right",
+        CursorUp => "Cursor Up",
+        CursorDown => "Cursor Down",
+        WordLeft => "Previous Word",
+        WordRight => "Next Word",
+        LineStart => "Line Start",
+        LineEnd => "Line End",
+        DocumentStart => "Document Start",
+        DocumentEnd => "Document End",
+    }
+}
@@ -0,0 +1,182 @@
+use std::path::{Path, PathBuf};
+
+pub const USAGE: &str = "\
+Usage: eta [OPTIONS] [PATH[:LINE[:COLUMN]]]...
+
+Folders are opened as the project, files are opened at the given position.
+
+Options:
+      --new-window  Start with an empty window that is not saved
+      --diff A B    Show the differences between the files A and B
+  -w, --wait        Wait until all opened files are closed, for $EDITOR
+  -h, --help        Print this help
+";
+
+/// What eta was started with.
+#[derive(Default, Debug, PartialEq)]
+pub struct Args {
+    pub folders: Vec<PathBuf>,
+    pub files: Vec<FilePosition>,
+    pub new_window: bool,
+    pub diff: Option<(PathBuf, PathBuf)>,
+    pub wait: bool,
+}
+
+impl Args {
+    /// The files `--wait` waits for.
+    pub fn wait_for(&self) -> Vec<PathBuf> {
+        if self.wait {
+            self.files.iter().map(|file| file.path.clone()).collect()
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// What the command line asks for.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Open(Args),
+    Help,
+}
+
+/// A file with an optional 1-based line and column.
+#[derive(Debug, PartialEq)]
+pub struct FilePosition {
+    pub path: PathBuf,
+    pub position: Option<(usize, usize)>,
+}
+
+/// Parses the arguments without the program name, relative paths are resolved against `cwd`.
+pub fn parse(args: impl IntoIterator<Item = String>, cwd: &Path) -> Result<Command, String> {
+    let mut parsed = Args::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--new-window" => parsed.new_window = true,
+            "-w" | "--wait" => parsed.wait = true,
+            "--diff" => {
+                let (Some(left), Some(right)) = (args.next(), args.next()) else {
+                    return Err("--diff expects two files".to_string());
+                };
+                parsed.diff = Some((absolute(cwd, &left), absolute(cwd, &right)));
+            }
+            flag if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(format!("Unknown option '{flag}'"))
+            }
+            _ => {
+                let (path, position) = split_position(&arg, cwd);
+                let path = absolute(cwd, path);
+                if path.is_dir() {
+                    parsed.folders.push(path);
+                } else {
+                    parsed.files.push(FilePosition { path, position });
+                }
+            }
+        }
+    }
+    if parsed.wait && parsed.files.is_empty() {
+        return Err("--wait expects a file".to_string());
+    }
+    Ok(Command::Open(parsed))
+}
+
+/// Splits `path[:line[:column]]`, paths that exist are taken as they are.
+fn split_position<'a>(arg: &'a str, cwd: &Path) -> (&'a str, Option<(usize, usize)>) {
+    if cwd.join(arg).exists() {
+        return (arg, None);
+    }
+    let mut path = arg;
+    let mut numbers = Vec::new();
+    while numbers.len() < 2 {
+        let Some((rest, number)) = path.rsplit_once(':') else {
+            break;
+        };
+        match number.parse::<usize>() {
+            Ok(number) if !rest.is_empty() => {
+                numbers.insert(0, number);
+                path = rest;
+            }
+            _ => break,
+        }
+    }
+    let position = numbers
+        .first()
+        .map(|line| (*line, numbers.get(1).copied().unwrap_or(1)));
+    (path, position)
+}
+
+fn absolute(cwd: &Path, path: &str) -> PathBuf {
+    let path = cwd.join(path);
+    path.canonicalize().unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn project(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("a:1"), "").unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    fn parse_args(args: &[&str], cwd: &Path) -> Result<Command, String> {
+        parse(args.iter().map(|arg| arg.to_string()), cwd)
+    }
+
+    #[test]
+    fn splits_positions() {
+        let cwd = project("eta-cli-positions");
+        assert_eq!(split_position("main.rs", &cwd), ("main.rs", None));
+        assert_eq!(
+            split_position("main.rs:42", &cwd),
+            ("main.rs", Some((42, 1)))
+        );
+        assert_eq!(
+            split_position("main.rs:42:7", &cwd),
+            ("main.rs", Some((42, 7)))
+        );
+        assert_eq!(split_position("a:b:3:4:5", &cwd), ("a:b:3", Some((4, 5))));
+        assert_eq!(split_position("main.rs:x", &cwd), ("main.rs:x", None));
+        assert_eq!(split_position(":3", &cwd), (":3", None));
+        // an existing file is not split
+        assert_eq!(split_position("a:1", &cwd), ("a:1", None));
+    }
+
+    #[test]
+    fn parses_arguments() {
+        let cwd = project("eta-cli-arguments");
+        let Ok(Command::Open(args)) =
+            parse_args(&[".", "src/main.rs:42:7", "x.rs:3", "-w", "a:1"], &cwd)
+        else {
+            panic!("expected files to open");
+        };
+        assert_eq!(args.folders, vec![cwd.clone()]);
+        assert_eq!(args.files.len(), 3);
+        assert_eq!(args.files[0].path, cwd.join("src/main.rs"));
+        assert_eq!(args.files[0].position, Some((42, 7)));
+        assert_eq!(args.files[1].position, Some((3, 1)));
+        assert_eq!(args.files[2].path, cwd.join("a:1"));
+        assert_eq!(args.files[2].position, None);
+        assert_eq!(args.wait_for().len(), 3);
+
+        let Ok(Command::Open(args)) = parse_args(&["--new-window", "--diff", "l", "r"], &cwd)
+        else {
+            panic!("expected a diff");
+        };
+        assert!(args.new_window);
+        assert_eq!(args.diff, Some((cwd.join("l"), cwd.join("r"))));
+        assert!(args.wait_for().is_empty());
+
+        assert_eq!(parse_args(&["x", "-h", "--bogus"], &cwd), Ok(Command::Help));
+        assert_eq!(parse_args(&[], &cwd), Ok(Command::Open(Args::default())));
+        assert!(parse_args(&["--wait", "."], &cwd).is_err());
+        assert!(parse_args(&["--diff", "l"], &cwd).is_err());
+        assert!(parse_args(&["--bogus"], &cwd).is_err());
+    }
+}
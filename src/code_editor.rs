@@ -1,23 +1,73 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, ops::Range, path::Path};
 
 use eframe::{
     egui::{
-        self,
-        os::OperatingSystem,
+        self, pos2,
         text::CCursor,
         text_edit::TextEditState,
-        text_selection::{CCursorRange, CursorRange},
-        vec2, CursorIcon, Event, EventFilter, Key, Modifiers, Response, Stroke, TextBuffer,
-        TextStyle, Ui,
+        text_selection::{
+            text_cursor_state::{ccursor_next_word, ccursor_previous_word},
+            visuals::paint_text_selection,
+            CCursorRange, CursorRange,
+        },
+        vec2, CursorIcon, Event, EventFilter, Key, Order, Painter, Pos2, Rect, Response,
+        ScrollArea, Stroke, TextBuffer, TextStyle, Ui,
+    },
+    epaint::{
+        text::{cursor::PCursor, TAB_SIZE},
+        Color32, Galley, Vec2,
     },
-    epaint::{text::cursor::PCursor, Color32, Galley, Vec2},
 };
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    actions::Action,
+    completion::{self, Completion, CompletionPopup},
+    editorconfig::EditorConfig,
+    indent::{self, Indent},
+    keymap::{Keymap, Resolution},
+    panels::command_palette::highlighted,
+    settings::EditorSettings,
+    snippets::{self, SnippetScope, SnippetSession},
+    vim::{Vim, VimResponse},
+};
+
 #[derive(Default, Clone, Serialize, Deserialize)]
 pub struct FileData {
     pub text: String,
     pub editor: ExtendedCodeEditor,
+    #[serde(skip)]
+    pub editorconfig: Option<EditorConfig>,
+    /// Indentation picked in the status bar, it wins over everything else.
+    #[serde(default)]
+    pub indent_override: Option<Indent>,
+    /// Indentation detected from the text when the file was shown first.
+    #[serde(skip)]
+    pub detected_indent: Option<Indent>,
+}
+
+impl FileData {
+    /// The `.editorconfig` properties of the file at `path`, resolved once.
+    pub fn editorconfig(&mut self, path: &Path) -> &EditorConfig {
+        self.editorconfig
+            .get_or_insert_with(|| EditorConfig::resolve(path))
+    }
+
+    /// Resolves the `.editorconfig` properties and detects the indentation, both once.
+    pub fn resolve(&mut self, path: &Path) {
+        self.editorconfig(path);
+        if self.detected_indent.is_none() {
+            self.detected_indent = Some(indent::detect(&self.text).unwrap_or_default());
+        }
+    }
+
+    /// What Tab inserts: the picked indentation, the one of `.editorconfig` or the detected one.
+    pub fn indent(&self) -> Indent {
+        self.indent_override
+            .or(self.editorconfig.as_ref().and_then(|config| config.indent))
+            .or(self.detected_indent)
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Default, Clone, Serialize, Deserialize)]
@@ -25,6 +75,14 @@ pub struct ExtendedCodeEditor {
     pub scroll_offset: f32,
     pub target_scroll_offset: f32,
     pub state: TextEditState,
+    /// 0-based line and column the cursor should jump to in the next frame.
+    #[serde(skip)]
+    pub jump_to: Option<(usize, usize)>,
+    /// The expanded snippet whose tab stops are visited.
+    #[serde(skip)]
+    pub snippet: Option<SnippetSession>,
+    #[serde(skip)]
+    pub completion: Option<CompletionPopup>,
 }
 
 impl Debug for ExtendedCodeEditor {
@@ -36,8 +94,18 @@ impl Debug for ExtendedCodeEditor {
     }
 }
 impl ExtendedCodeEditor {
-    pub fn _ui(ui: &mut Ui, data: &mut FileData) -> Response {
-        let FileData { text, editor } = data;
+    pub fn _ui(
+        ui: &mut Ui,
+        data: &mut FileData,
+        vim: &mut Vim,
+        keymap: &mut Keymap,
+        scope: &SnippetScope,
+        completion: &Completion,
+        settings: &EditorSettings,
+    ) -> Response {
+        let indent = data.indent();
+        let FileData { text, editor, .. } = data;
+        vim.indent = indent;
         let text: &mut dyn TextBuffer = text;
         let prev_text = text.as_str().to_string();
 
@@ -118,6 +186,28 @@ impl ExtendedCodeEditor {
 
         let mut cursor_range = editor.state.cursor.range(&galley).unwrap_or_default();
 
+        // jumps, e.g. from quick open, centered in the view
+        if let Some((line, column)) = editor.jump_to.take() {
+            let line = line.min(line_count - 1);
+            let column = column.min(
+                text.as_str()
+                    .split('\n')
+                    .nth(line)
+                    .unwrap_or_default()
+                    .chars()
+                    .count(),
+            );
+            let cursor = galley.from_pcursor(PCursor {
+                paragraph: line,
+                offset: column,
+                prefer_next_row: false,
+            });
+            cursor_range = CursorRange::one(cursor);
+            let y = galley.pos_from_cursor(&cursor).center().y;
+            editor.target_scroll_offset = (y - rect.height() / 2.0).max(0.0);
+            editor.scroll_offset = editor.target_scroll_offset;
+        }
+
         // once before key input
         let mut undoer = editor.state.undoer();
         undoer.feed_state(
@@ -126,6 +216,36 @@ impl ExtendedCodeEditor {
         );
         editor.state.set_undoer(undoer);
 
+        // actions from outside, e.g. the command palette
+        let mut completion_requested = false;
+        let completion_open = editor.completion.is_some();
+        for action in keymap.take_queued() {
+            if action == Action::TriggerCompletion {
+                completion_requested = true;
+                continue;
+            }
+            if let Some(new_ccursor_range) = Self::run_action(
+                action,
+                &mut cursor_range,
+                text,
+                &galley,
+                &mut editor.state,
+                indent,
+            ) {
+                galley = painter.layout(
+                    text.as_str().to_string(),
+                    font.clone(),
+                    ui.visuals().text_color(),
+                    f32::INFINITY,
+                );
+                cursor_range = CursorRange {
+                    primary: galley.from_ccursor(new_ccursor_range.primary),
+                    secondary: galley.from_ccursor(new_ccursor_range.secondary),
+                };
+                response.mark_changed();
+            }
+        }
+
         // getting keys
         if response.has_focus() {
             // filter
@@ -133,156 +253,140 @@ impl ExtendedCodeEditor {
                 tab: true,
                 horizontal_arrows: true,
                 vertical_arrows: true,
-                ..Default::default()
+                escape: vim.enabled || completion_open,
             };
             ui.memory_mut(|mem| mem.set_focus_lock_filter(id, event_filter));
 
             // key presses
             let events = ui.input(|i| i.events.clone());
             for event in events {
-                let did_mutate_text = match event {
-                    // First handle events that only changes the selection cursor, not the text:
-                    event if cursor_range.on_event(os, &event, &galley, id) => None,
-
-                    Event::Copy => {
-                        if cursor_range.is_empty() {
-                            ui.ctx().copy_text(
-                                text.as_str()
-                                    .to_string()
-                                    .split('\n')
-                                    .nth(cursor_range.primary.rcursor.row)
-                                    .unwrap_or_default()
-                                    .to_string()
-                                    + "\n",
-                            );
-                        } else {
-                            ui.ctx()
-                                .copy_text(cursor_range.slice_str(text.as_str()).to_owned());
-                        }
-                        None
-                    }
-                    Event::Cut => {
-                        if cursor_range.is_empty() {
-                            ui.ctx().copy_text(
-                                text.as_str()
-                                    .to_string()
-                                    .split('\n')
-                                    .nth(cursor_range.primary.rcursor.row)
-                                    .unwrap_or_default()
-                                    .to_string()
-                                    + "\n",
-                            );
-                            text.delete_paragraph_after_cursor(&galley, &cursor_range);
-                            text.delete_paragraph_at_cursor(&galley, &cursor_range);
-                            Some(CCursorRange::one(cursor_range.primary.ccursor))
-                        } else {
-                            ui.ctx()
-                                .copy_text(cursor_range.slice_str(text.as_str()).to_owned());
-                            Some(CCursorRange::one(text.delete_selected(&cursor_range)))
+                let completion_key =
+                    Self::completion_key(&event, &mut editor.completion, text, &cursor_range);
+                let did_mutate_text = match completion_key {
+                    Some(accepted) => accepted,
+                    None => match vim.on_event(
+                        &event,
+                        text,
+                        &mut editor.state,
+                        cursor_range.as_ccursor_range(),
+                    ) {
+                        VimResponse::Consumed => None,
+                        VimResponse::Cursor(new_ccursor_range) => {
+                            cursor_range = CursorRange {
+                                primary: galley.from_ccursor(new_ccursor_range.primary),
+                                secondary: galley.from_ccursor(new_ccursor_range.secondary),
+                            };
+                            None
                         }
-                    }
-                    Event::Paste(text_to_insert) => {
-                        if !text_to_insert.is_empty() {
-                            let mut ccursor = text.delete_selected(&cursor_range);
+                        VimResponse::Changed(new_ccursor_range) => Some(new_ccursor_range),
+                        VimResponse::Pass => match keymap.resolve_editor(&event) {
+                            Resolution::Pending => None,
+                            Resolution::Action(Action::TriggerCompletion) => {
+                                completion_requested = true;
+                                None
+                            }
+                            Resolution::Action(action) if !action.is_editor() => {
+                                keymap.queue_app(action);
+                                None
+                            }
+                            Resolution::Action(action) => Self::snippet_action(
+                                action,
+                                &mut cursor_range,
+                                text,
+                                &galley,
+                                &mut editor.snippet,
+                                scope,
+                                indent,
+                            )
+                            .unwrap_or_else(|| {
+                                Self::run_action(
+                                    action,
+                                    &mut cursor_range,
+                                    text,
+                                    &galley,
+                                    &mut editor.state,
+                                    indent,
+                                )
+                            }),
+                            Resolution::None => match event {
+                                // First handle events that only changes the selection cursor, not the text:
+                                event if cursor_range.on_event(os, &event, &galley, id) => None,
 
-                            text.insert_text_at(&mut ccursor, &text_to_insert, char_limit);
+                                Event::Copy => {
+                                    if cursor_range.is_empty() {
+                                        ui.ctx().copy_text(
+                                            text.as_str()
+                                                .split('\n')
+                                                .nth(cursor_range.primary.rcursor.row)
+                                                .unwrap_or_default()
+                                                .to_string()
+                                                + "\n",
+                                        );
+                                    } else {
+                                        ui.ctx().copy_text(
+                                            cursor_range.slice_str(text.as_str()).to_owned(),
+                                        );
+                                    }
+                                    None
+                                }
+                                Event::Cut => {
+                                    if cursor_range.is_empty() {
+                                        ui.ctx().copy_text(
+                                            text.as_str()
+                                                .split('\n')
+                                                .nth(cursor_range.primary.rcursor.row)
+                                                .unwrap_or_default()
+                                                .to_string()
+                                                + "\n",
+                                        );
+                                        text.delete_paragraph_after_cursor(&galley, &cursor_range);
+                                        text.delete_paragraph_at_cursor(&galley, &cursor_range);
+                                        Some(CCursorRange::one(cursor_range.primary.ccursor))
+                                    } else {
+                                        ui.ctx().copy_text(
+                                            cursor_range.slice_str(text.as_str()).to_owned(),
+                                        );
+                                        Some(CCursorRange::one(text.delete_selected(&cursor_range)))
+                                    }
+                                }
+                                Event::Paste(text_to_insert) => {
+                                    if !text_to_insert.is_empty() {
+                                        let mut ccursor = text.delete_selected(&cursor_range);
 
-                            Some(CCursorRange::one(ccursor))
-                        } else {
-                            None
-                        }
-                    }
-                    Event::Text(text_to_insert) => {
-                        // Newlines are handled by `Key::Enter`.
-                        if !text_to_insert.is_empty()
-                            && text_to_insert != "\n"
-                            && text_to_insert != "\r"
-                        {
-                            let mut ccursor = text.delete_selected(&cursor_range);
-
-                            text.insert_text_at(&mut ccursor, &text_to_insert, char_limit);
-
-                            Some(CCursorRange::one(ccursor))
-                        } else {
-                            None
-                        }
-                    }
-                    Event::Key {
-                        key: Key::Tab,
-                        pressed: true,
-                        modifiers,
-                        ..
-                    } => {
-                        let mut ccursor = text.delete_selected(&cursor_range);
-                        if modifiers.shift {
-                            // TODO(emilk): support removing indentation over a selection?
-                            text.decrease_indentation(&mut ccursor);
-                        } else {
-                            text.insert_text_at(&mut ccursor, "\t", char_limit);
-                        }
-                        Some(CCursorRange::one(ccursor))
-                    }
-                    Event::Key {
-                        key: Key::Enter,
-                        pressed: true,
-                        ..
-                    } => {
-                        let mut ccursor = text.delete_selected(&cursor_range);
-                        text.insert_text_at(&mut ccursor, "\n", char_limit);
-                        // TODO(emilk): if code editor, auto-indent by same leading tabs, + one if the lines end on an opening bracket
-                        Some(CCursorRange::one(ccursor))
-                    }
-                    Event::Key {
-                        key: Key::Z,
-                        pressed: true,
-                        modifiers,
-                        ..
-                    } if modifiers.matches_logically(Modifiers::COMMAND) => {
-                        if let Some((undo_ccursor_range, undo_txt)) = editor
-                            .state
-                            .undoer()
-                            .undo(&(cursor_range.as_ccursor_range(), text.as_str().to_string()))
-                        {
-                            text.replace_with(undo_txt);
-                            Some(*undo_ccursor_range)
-                        } else {
-                            None
-                        }
-                    }
-                    Event::Key {
-                        key,
-                        pressed: true,
-                        modifiers,
-                        ..
-                    } if (modifiers.matches_logically(Modifiers::COMMAND) && key == Key::Y)
-                        || (modifiers.matches_logically(Modifiers::SHIFT | Modifiers::COMMAND)
-                            && key == Key::Z) =>
-                    {
-                        if let Some((redo_ccursor_range, redo_txt)) = editor
-                            .state
-                            .undoer()
-                            .redo(&(cursor_range.as_ccursor_range(), text.as_str().to_string()))
-                        {
-                            text.replace_with(redo_txt);
-                            Some(*redo_ccursor_range)
-                        } else {
-                            None
-                        }
-                    }
-                    Event::Key {
-                        modifiers,
-                        key,
-                        pressed: true,
-                        ..
-                    } => Self::check_for_mutating_key_press(
-                        os,
-                        &mut cursor_range,
-                        text,
-                        &galley,
-                        &modifiers,
-                        key,
-                    ),
-                    _ => None,
+                                        text.insert_text_at(
+                                            &mut ccursor,
+                                            &text_to_insert,
+                                            char_limit,
+                                        );
+
+                                        Some(CCursorRange::one(ccursor))
+                                    } else {
+                                        None
+                                    }
+                                }
+                                Event::Text(text_to_insert) => {
+                                    // Newlines are handled by `Key::Enter`.
+                                    if !text_to_insert.is_empty()
+                                        && text_to_insert != "\n"
+                                        && text_to_insert != "\r"
+                                    {
+                                        let mut ccursor = text.delete_selected(&cursor_range);
+
+                                        text.insert_text_at(
+                                            &mut ccursor,
+                                            &text_to_insert,
+                                            char_limit,
+                                        );
+
+                                        Some(CCursorRange::one(ccursor))
+                                    } else {
+                                        None
+                                    }
+                                }
+                                _ => None,
+                            },
+                        },
+                    },
                 };
                 if let Some(new_ccursor_range) = did_mutate_text {
                     // Layout again to avoid frame delay, and to keep `text` and `galley` in sync. TODO: Add this
@@ -304,7 +408,49 @@ impl ExtendedCodeEditor {
             }
         }
 
+        // snippet mirrors follow their placeholder
+        if let Some(session) = &mut editor.snippet {
+            let before = text.as_str().to_string();
+            let mut ccursor_range = cursor_range.as_ccursor_range();
+            // the first Escape closes the completions
+            let escaped =
+                !vim.enabled && !completion_open && ui.input(|i| i.key_pressed(Key::Escape));
+            if escaped || !session.track(text, &mut ccursor_range) {
+                editor.snippet = None;
+            }
+            if text.as_str() != before {
+                galley = painter.layout(
+                    text.as_str().to_string(),
+                    font.clone(),
+                    ui.visuals().text_color(),
+                    f32::INFINITY,
+                );
+                cursor_range = CursorRange {
+                    primary: galley.from_ccursor(ccursor_range.primary),
+                    secondary: galley.from_ccursor(ccursor_range.secondary),
+                };
+                response.mark_changed();
+            }
+        }
+
+        // word completion
+        let typed = text.as_str() != prev_text
+            && ui.input(|i| i.events.iter().any(|e| matches!(e, Event::Text(_))));
+        if completion_requested || editor.completion.is_some() || typed {
+            editor.completion = Self::update_completion(
+                editor.completion.take(),
+                completion,
+                text.as_str(),
+                &cursor_range,
+                completion_requested,
+                typed,
+            );
+        }
+
         editor.state.cursor.set_range(Some(cursor_range));
+        if let Some(yanked) = vim.take_clipboard() {
+            ui.ctx().copy_text(yanked);
+        }
 
         // once after key input
         let mut undoer = editor.state.undoer();
@@ -330,6 +476,48 @@ impl ExtendedCodeEditor {
                 ui.visuals().text_color(),
             );
 
+            Self::paint_whitespace(
+                &painter.with_clip_rect(rect),
+                adjusted_text_position,
+                &galley,
+                text.as_str(),
+                cursor_range.primary.rcursor.row,
+                indent,
+                settings,
+                ui.fonts(|f| f.glyph_width(&font, ' ')),
+            );
+
+            paint_text_selection(
+                &painter.with_clip_rect(rect),
+                ui.visuals(),
+                adjusted_text_position,
+                &galley,
+                &cursor_range,
+                None,
+            );
+
+            // snippet placeholders, except the final `$0`
+            if let Some(session) = &editor.snippet {
+                let placeholder_stroke = Stroke::new(1.0, ui.visuals().weak_text_color());
+                let stops = &session.stops[..session.stops.len() - 1];
+                for range in stops.iter().flat_map(|stop| &stop.ranges) {
+                    let start = galley.from_ccursor(CCursor::new(range.start));
+                    let end = galley.from_ccursor(CCursor::new(range.end));
+                    if start.rcursor.row != end.rcursor.row {
+                        continue;
+                    }
+                    let placeholder = Rect::from_min_max(
+                        galley.pos_from_cursor(&start).min,
+                        galley.pos_from_cursor(&end).max,
+                    );
+                    painter.with_clip_rect(rect).rect_stroke(
+                        placeholder.translate(adjusted_text_position.to_vec2()),
+                        1.0,
+                        placeholder_stroke,
+                    );
+                }
+            }
+
             painter
                 .with_clip_rect(rect)
                 .galley(adjusted_text_position, galley, Color32::WHITE);
@@ -340,20 +528,117 @@ impl ExtendedCodeEditor {
                 && adjusted_cursor_pos.y <= rect.max.y;
             // Render the cursor
             if ui.memory(|r| r.has_focus(id)) && cursor_is_visible {
-                let cursor_width = 1.5;
+                let cursor_width = if vim.block_cursor() {
+                    ui.fonts(|f| f.glyph_width(&font, ' '))
+                } else {
+                    1.5
+                };
                 let cursor_height = line_height + 2.0;
                 let cursor_rect = egui::Rect::from_min_size(
                     adjusted_cursor_pos - vec2(cursor_width / 2., 0.),
                     Vec2::new(cursor_width, cursor_height),
                 );
 
+                let cursor_color = if vim.block_cursor() {
+                    ui.visuals().strong_text_color().gamma_multiply(0.5)
+                } else {
+                    ui.visuals().strong_text_color()
+                };
                 painter.with_clip_rect(rect).rect(
                     cursor_rect,
                     egui::Rounding::same(0.75),
-                    ui.visuals().strong_text_color(),
+                    cursor_color,
                     Stroke::NONE,
                 );
             }
+
+            // completions of the word before the cursor
+            let has_focus = ui.memory(|r| r.has_focus(id));
+            let chosen = editor
+                .completion
+                .as_ref()
+                .filter(|_| has_focus)
+                .and_then(|popup| {
+                    egui::Area::new(id.with("completion"))
+                        .order(Order::Foreground)
+                        .fixed_pos(adjusted_cursor_pos + vec2(0.0, line_height + 2.0))
+                        .show(ui.ctx(), |ui| {
+                            egui::Frame::popup(ui.style())
+                                .show(ui, |ui| {
+                                    ScrollArea::vertical()
+                                        .max_height(200.0)
+                                        .show(ui, |ui| {
+                                            let mut chosen = None;
+                                            for (i, (item, indices)) in
+                                                popup.items.iter().enumerate()
+                                            {
+                                                let label = ui.selectable_label(
+                                                    i == popup.selected,
+                                                    highlighted(ui, item, indices),
+                                                );
+                                                if i == popup.selected
+                                                    && !ui.clip_rect().contains_rect(label.rect)
+                                                {
+                                                    label.scroll_to_me(None);
+                                                }
+                                                if label.clicked() {
+                                                    chosen = Some(i);
+                                                }
+                                            }
+                                            chosen
+                                        })
+                                        .inner
+                                })
+                                .inner
+                        })
+                        .inner
+                });
+            if let Some(i) = chosen {
+                if let Some(mut popup) = editor.completion.take() {
+                    popup.selected = i;
+                    let accepted = popup.accept(text, cursor_range.primary.ccursor.index);
+                    editor.state.cursor.set_char_range(Some(accepted));
+                    ui.memory_mut(|mem| mem.request_focus(id));
+                    response.mark_changed();
+                }
+            }
+
+            // choices of the current tab stop
+            if let Some(session) = editor.snippet.as_mut().filter(|s| !s.choices().is_empty()) {
+                let chosen = egui::Area::new(id.with("snippet_choices"))
+                    .order(Order::Foreground)
+                    .fixed_pos(adjusted_cursor_pos + vec2(0.0, line_height + 2.0))
+                    .show(ui.ctx(), |ui| {
+                        egui::Frame::popup(ui.style())
+                            .show(ui, |ui| {
+                                let selected = session.selection();
+                                let current = text
+                                    .char_range(selected.secondary.index..selected.primary.index);
+                                let mut chosen = None;
+                                for choice in session.choices() {
+                                    if ui.selectable_label(*choice == current, choice).clicked() {
+                                        chosen = Some(choice.clone());
+                                    }
+                                }
+                                chosen
+                            })
+                            .inner
+                    })
+                    .inner;
+                if let Some(choice) = chosen {
+                    let selected = session.selection();
+                    let range = selected.secondary.index..selected.primary.index;
+                    text.delete_char_range(range.clone());
+                    text.insert_text(&choice, range.start);
+                    let end = CCursor::new(range.start + choice.chars().count());
+                    editor
+                        .state
+                        .cursor
+                        .set_char_range(Some(CCursorRange::one(end)));
+                    ui.memory_mut(|mem| mem.request_focus(id));
+                    response.mark_changed();
+                }
+            }
         }
 
         if response.clicked() && !response.lost_focus() {
@@ -363,92 +648,436 @@ impl ExtendedCodeEditor {
         response
     }
 
-    pub fn ui(data: &mut FileData) -> impl egui::Widget + '_ {
-        move |ui: &mut egui::Ui| Self::_ui(ui, data)
+    pub fn ui<'a>(
+        data: &'a mut FileData,
+        vim: &'a mut Vim,
+        keymap: &'a mut Keymap,
+        scope: &'a SnippetScope,
+        completion: &'a Completion,
+        settings: &'a EditorSettings,
+    ) -> impl egui::Widget + 'a {
+        move |ui: &mut egui::Ui| Self::_ui(ui, data, vim, keymap, scope, completion, settings)
     }
 
-    /// Returns `Some(new_cursor)` if we did mutate `text`.
-    fn check_for_mutating_key_press(
-        os: OperatingSystem,
-        cursor_range: &mut CursorRange,
-        text: &mut dyn TextBuffer,
+    /// Rulers, indentation guides and whitespace of the `galley` painted at `origin`.
+    #[allow(clippy::too_many_arguments)]
+    fn paint_whitespace(
+        painter: &Painter,
+        origin: Pos2,
         galley: &Galley,
-        modifiers: &Modifiers,
-        key: Key,
-    ) -> Option<CCursorRange> {
-        match key {
-            Key::Backspace => {
-                let ccursor = if modifiers.mac_cmd {
-                    text.delete_paragraph_before_cursor(galley, cursor_range)
-                } else if let Some(cursor) = cursor_range.single() {
-                    if modifiers.alt || modifiers.ctrl {
-                        // alt on mac, ctrl on windows
-                        text.delete_previous_word(cursor.ccursor)
-                    } else {
-                        text.delete_previous_char(cursor.ccursor)
-                    }
-                } else {
-                    text.delete_selected(cursor_range)
+        text: &str,
+        cursor_row: usize,
+        indent: Indent,
+        settings: &EditorSettings,
+        space_width: f32,
+    ) {
+        let visuals = painter.ctx().style().visuals.clone();
+        let clip = painter.clip_rect();
+        let faint = Stroke::new(1.0, visuals.weak_text_color().gamma_multiply(0.4));
+
+        for column in &settings.rulers {
+            painter.vline(
+                origin.x + *column as f32 * space_width,
+                clip.y_range(),
+                faint,
+            );
+        }
+
+        let visible_rows = galley
+            .rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| (i, row, row.rect.translate(origin.to_vec2())))
+            .filter(|(_, _, rect)| rect.max.y >= clip.min.y && rect.min.y <= clip.max.y);
+
+        let step = match indent {
+            Indent::Tabs => TAB_SIZE,
+            Indent::Spaces(width) => width.max(1),
+        };
+        let levels = match settings.indent_guides {
+            true => indent_levels(text, step),
+            false => Vec::new(),
+        };
+        let active = active_guide(&levels, cursor_row);
+        let active_stroke = Stroke::new(1.0, visuals.text_color().gamma_multiply(0.6));
+
+        for (i, row, rect) in visible_rows {
+            for level in 0..levels.get(i).copied().unwrap_or_default() {
+                let stroke = match &active {
+                    Some((active, rows)) if *active == level && rows.contains(&i) => active_stroke,
+                    _ => faint,
                 };
-                Some(CCursorRange::one(ccursor))
+                let x = origin.x + (level * step) as f32 * space_width;
+                painter.vline(x, rect.y_range(), stroke);
+            }
+
+            // not on the line being typed in
+            let trailing = row
+                .glyphs
+                .iter()
+                .rposition(|glyph| !glyph.chr.is_whitespace())
+                .map_or(0, |i| i + 1);
+            if let (true, Some(first), Some(last)) = (
+                settings.highlight_trailing_whitespace && i != cursor_row,
+                row.glyphs.get(trailing),
+                row.glyphs.last(),
+            ) {
+                let x = origin.x + first.pos.x..=origin.x + last.max_x();
+                painter.rect_filled(
+                    Rect::from_x_y_ranges(x, rect.y_range()),
+                    0.0,
+                    visuals.warn_fg_color.gamma_multiply(0.3),
+                );
             }
 
-            Key::Delete if !modifiers.shift || os != OperatingSystem::Windows => {
-                let ccursor = if modifiers.mac_cmd {
-                    text.delete_paragraph_after_cursor(galley, cursor_range)
-                } else if let Some(cursor) = cursor_range.single() {
-                    if modifiers.alt || modifiers.ctrl {
-                        // alt on mac, ctrl on windows
-                        text.delete_next_word(cursor.ccursor)
-                    } else {
-                        text.delete_next_char(cursor.ccursor)
+            if settings.render_whitespace {
+                for glyph in &row.glyphs {
+                    let left = origin.x + glyph.pos.x;
+                    let center = pos2(left + glyph.size.x / 2.0, rect.center().y);
+                    match glyph.chr {
+                        ' ' => {
+                            painter.circle_filled(center, 1.0, faint.color);
+                        }
+                        '\t' => painter.arrow(
+                            pos2(left + 2.0, center.y),
+                            vec2(glyph.size.x - 4.0, 0.0),
+                            faint,
+                        ),
+                        _ => {}
                     }
-                } else {
-                    text.delete_selected(cursor_range)
-                };
-                let ccursor = CCursor {
-                    prefer_next_row: true,
-                    ..ccursor
-                };
-                Some(CCursorRange::one(ccursor))
+                }
             }
+        }
+    }
 
-            Key::H if modifiers.ctrl => {
-                let ccursor = text.delete_previous_char(cursor_range.primary.ccursor);
-                Some(CCursorRange::one(ccursor))
+    /// Up and Down pick a completion, Enter and Tab accept it, Escape closes the popup.
+    /// Returns `None` if the `event` is not for the popup.
+    fn completion_key(
+        event: &Event,
+        popup: &mut Option<CompletionPopup>,
+        text: &mut dyn TextBuffer,
+        cursor_range: &CursorRange,
+    ) -> Option<Option<CCursorRange>> {
+        let Event::Key {
+            key,
+            pressed: true,
+            modifiers,
+            ..
+        } = event
+        else {
+            return None;
+        };
+        let active = popup.as_mut().filter(|_| modifiers.is_none())?;
+        let items = active.items.len();
+        match key {
+            Key::ArrowDown => active.selected = (active.selected + 1) % items,
+            Key::ArrowUp => active.selected = (active.selected + items - 1) % items,
+            Key::Escape => *popup = None,
+            Key::Enter | Key::Tab => {
+                let accepted = active.accept(text, cursor_range.primary.ccursor.index);
+                *popup = None;
+                return Some(Some(accepted));
             }
+            _ => return None,
+        }
+        Some(None)
+    }
+
+    /// Opens, filters or closes the completions after the text or the cursor changed.
+    fn update_completion(
+        popup: Option<CompletionPopup>,
+        completion: &Completion,
+        text: &str,
+        cursor_range: &CursorRange,
+        requested: bool,
+        typed: bool,
+    ) -> Option<CompletionPopup> {
+        if !cursor_range.is_empty() {
+            return None;
+        }
+        let (start, word) = completion::word_before(text, cursor_range.primary.ccursor.index);
+        let same_word = popup.as_ref().is_some_and(|popup| popup.start == start);
+        if let Some(popup) = popup.filter(|popup| same_word && popup.word == word) {
+            return Some(popup);
+        }
+        let settings = &completion.settings;
+        let auto = typed && settings.auto && word.chars().count() >= settings.min_chars;
+        if !(same_word || requested || auto) || (word.is_empty() && !requested) {
+            return None;
+        }
+        let items = completion.complete(&word, text);
+        (!items.is_empty()).then_some(CompletionPopup {
+            start,
+            word,
+            items,
+            selected: 0,
+        })
+    }
 
-            Key::K if modifiers.ctrl => {
-                let ccursor = text.delete_paragraph_after_cursor(galley, cursor_range);
-                Some(CCursorRange::one(ccursor))
+    /// Tab expands the snippet before the cursor or moves to the next tab stop, Shift+Tab moves
+    /// back. Returns `None` if the `action` is not about snippets.
+    #[allow(clippy::too_many_arguments)]
+    fn snippet_action(
+        action: Action,
+        cursor_range: &mut CursorRange,
+        text: &mut dyn TextBuffer,
+        galley: &Galley,
+        session: &mut Option<SnippetSession>,
+        scope: &SnippetScope,
+        indent: Indent,
+    ) -> Option<Option<CCursorRange>> {
+        let selection = match action {
+            Action::Indent if session.is_some() => {
+                let active = session.as_mut()?;
+                let selection = active.next_stop();
+                if active.is_done() {
+                    *session = None;
+                }
+                selection
             }
+            Action::Outdent if session.is_some() => session.as_mut()?.previous_stop(),
+            Action::Indent if cursor_range.is_empty() => {
+                let cursor = cursor_range.primary.ccursor.index;
+                let (snippet, len) = snippets::find_prefix(scope.snippets, text.as_str(), cursor)?;
+                let start = cursor - len;
+                let chars = text.as_str().chars().collect::<Vec<_>>();
+                let line_start = chars[..start]
+                    .iter()
+                    .rposition(|c| *c == '\n')
+                    .map_or(0, |i| i + 1);
+                let line_indent = chars[line_start..start]
+                    .iter()
+                    .take_while(|c| **c == ' ' || **c == '\t')
+                    .collect::<String>();
+                let expansion = {
+                    let variables = snippets::variables(scope, text.as_str(), cursor_range);
+                    snippets::expand(&snippet.body, &variables, &line_indent, indent)
+                };
 
-            Key::U if modifiers.ctrl => {
-                let ccursor = text.delete_paragraph_before_cursor(galley, cursor_range);
-                Some(CCursorRange::one(ccursor))
+                text.delete_char_range(start..cursor);
+                text.insert_text(&expansion.text, start);
+                let expanded = SnippetSession::new(expansion, start, text.as_str());
+                let selection = expanded.selection();
+                *session = (!expanded.is_done()).then_some(expanded);
+                return Some(Some(selection));
             }
+            _ => return None,
+        };
+        *cursor_range = CursorRange {
+            primary: galley.from_ccursor(selection.primary),
+            secondary: galley.from_ccursor(selection.secondary),
+        };
+        Some(None)
+    }
 
-            Key::W if modifiers.ctrl => {
-                let ccursor = if let Some(cursor) = cursor_range.single() {
-                    text.delete_previous_word(cursor.ccursor)
-                } else {
-                    text.delete_selected(cursor_range)
+    /// Runs an editor `action`.
+    /// Returns `Some(new_cursor)` if we did mutate `text`.
+    fn run_action(
+        action: Action,
+        cursor_range: &mut CursorRange,
+        text: &mut dyn TextBuffer,
+        galley: &Galley,
+        state: &mut TextEditState,
+        indent: Indent,
+    ) -> Option<CCursorRange> {
+        let char_limit = usize::MAX;
+        let ccursor = match action {
+            Action::Undo => {
+                let (undo_ccursor_range, undo_txt) = state
+                    .undoer()
+                    .undo(&(cursor_range.as_ccursor_range(), text.as_str().to_string()))
+                    .cloned()?;
+                text.replace_with(&undo_txt);
+                return Some(undo_ccursor_range);
+            }
+            Action::Redo => {
+                let (redo_ccursor_range, redo_txt) = state
+                    .undoer()
+                    .redo(&(cursor_range.as_ccursor_range(), text.as_str().to_string()))
+                    .cloned()?;
+                text.replace_with(&redo_txt);
+                return Some(redo_ccursor_range);
+            }
+            Action::Indent => {
+                let mut ccursor = text.delete_selected(cursor_range);
+                text.insert_text_at(&mut ccursor, &indent.unit(), char_limit);
+                ccursor
+            }
+            Action::Outdent => {
+                let mut ccursor = text.delete_selected(cursor_range);
+                // TODO(emilk): support removing indentation over a selection?
+                let chars = text.as_str().chars().collect::<Vec<_>>();
+                let line_start = chars[..ccursor.index.min(chars.len())]
+                    .iter()
+                    .rposition(|c| *c == '\n')
+                    .map_or(0, |i| i + 1);
+                let width = indent.outdent_width(chars[line_start..].iter().copied());
+                text.delete_char_range(line_start..line_start + width);
+                if ccursor.index > line_start {
+                    ccursor.index = ccursor.index.saturating_sub(width).max(line_start);
+                }
+                ccursor
+            }
+            Action::NewLine => {
+                let mut ccursor = text.delete_selected(cursor_range);
+                // keep the indentation, one level more after an opening bracket
+                let chars = text.as_str().chars().collect::<Vec<_>>();
+                let cursor = ccursor.index.min(chars.len());
+                let line_start = chars[..cursor]
+                    .iter()
+                    .rposition(|c| *c == '\n')
+                    .map_or(0, |i| i + 1);
+                let leading = chars[line_start..cursor]
+                    .iter()
+                    .take_while(|c| **c == ' ' || **c == '\t')
+                    .collect::<String>();
+                let opening = chars[line_start..cursor]
+                    .iter()
+                    .rev()
+                    .find(|c| !c.is_whitespace())
+                    .and_then(|c| ['{', '[', '('].iter().position(|open| open == c));
+                let mut inserted = format!("\n{leading}");
+                if opening.is_some() {
+                    inserted += &indent.unit();
+                }
+                text.insert_text_at(&mut ccursor, &inserted, char_limit);
+                // the closing bracket goes on its own line
+                if let Some(opening) = opening {
+                    if chars.get(cursor) == Some(&['}', ']', ')'][opening]) {
+                        let mut after = ccursor;
+                        text.insert_text_at(&mut after, &format!("\n{leading}"), char_limit);
+                    }
+                }
+                ccursor
+            }
+            Action::DeleteCharBackward => match cursor_range.single() {
+                Some(cursor) => text.delete_previous_char(cursor.ccursor),
+                None => text.delete_selected(cursor_range),
+            },
+            Action::DeleteWordBackward => match cursor_range.single() {
+                Some(cursor) => text.delete_previous_word(cursor.ccursor),
+                None => text.delete_selected(cursor_range),
+            },
+            Action::DeleteToLineStart => text.delete_paragraph_before_cursor(galley, cursor_range),
+            Action::DeleteCharForward => CCursor {
+                prefer_next_row: true,
+                ..match cursor_range.single() {
+                    Some(cursor) => text.delete_next_char(cursor.ccursor),
+                    None => text.delete_selected(cursor_range),
+                }
+            },
+            Action::DeleteWordForward => CCursor {
+                prefer_next_row: true,
+                ..match cursor_range.single() {
+                    Some(cursor) => text.delete_next_word(cursor.ccursor),
+                    None => text.delete_selected(cursor_range),
+                }
+            },
+            Action::DeleteToLineEnd => text.delete_paragraph_after_cursor(galley, cursor_range),
+            _ => {
+                // everything else only moves the cursor
+                let cursor = cursor_range.primary;
+                let primary = match action {
+                    Action::CursorLeft => galley.cursor_left_one_character(&cursor),
+                    Action::CursorRight => galley.cursor_right_one_character(&cursor),
+                    Action::CursorUp => galley.cursor_up_one_row(&cursor),
+                    Action::CursorDown => galley.cursor_down_one_row(&cursor),
+                    Action::WordLeft => {
+                        galley.from_ccursor(ccursor_previous_word(text.as_str(), cursor.ccursor))
+                    }
+                    Action::WordRight => {
+                        galley.from_ccursor(ccursor_next_word(text.as_str(), cursor.ccursor))
+                    }
+                    Action::LineStart => galley.cursor_begin_of_row(&cursor),
+                    Action::LineEnd => galley.cursor_end_of_row(&cursor),
+                    Action::DocumentStart => galley.begin(),
+                    Action::DocumentEnd => galley.end(),
+                    _ => return None,
                 };
-                Some(CCursorRange::one(ccursor))
+                *cursor_range = CursorRange::one(primary);
+                return None;
             }
+        };
+        Some(CCursorRange::one(ccursor))
+    }
+}
 
-            _ => None,
+/// Indentation of every line in units of `step` columns, blank lines take the smaller one of
+/// the lines around them.
+fn indent_levels(text: &str, step: usize) -> Vec<usize> {
+    let columns = text
+        .split('\n')
+        .map(|line| {
+            let indentation = line.len() - line.trim_start_matches([' ', '\t']).len();
+            let blank = indentation == line.trim_end_matches('\r').len();
+            let width = line[..indentation]
+                .chars()
+                .map(|c| if c == '\t' { TAB_SIZE } else { 1 })
+                .sum::<usize>();
+            (!blank).then_some(width)
+        })
+        .collect::<Vec<_>>();
+
+    let mut levels = Vec::with_capacity(columns.len());
+    let mut previous = 0;
+    for (i, width) in columns.iter().enumerate() {
+        let width = width.unwrap_or_else(|| {
+            let next = columns[i..].iter().flatten().next().copied().unwrap_or(0);
+            previous.min(next)
+        });
+        if columns[i].is_some() {
+            previous = width;
         }
+        levels.push(width.div_ceil(step));
     }
+    levels
+}
+
+/// The guide of the block around `row` and the rows it spans.
+fn active_guide(levels: &[usize], row: usize) -> Option<(usize, Range<usize>)> {
+    let current = *levels.get(row)?;
+    // a line opening a block activates the guide of that block
+    let (level, from) = match levels.get(row + 1) {
+        Some(next) if *next > current => (current, row + 1),
+        _ => (current.checked_sub(1)?, row),
+    };
+    let end = (from..levels.len())
+        .take_while(|i| levels[*i] > level)
+        .last()?
+        + 1;
+    let start = (0..from)
+        .rev()
+        .take_while(|i| levels[*i] > level)
+        .last()
+        .unwrap_or(from);
+    Some((level, start..end))
 }
 
 pub trait ExtendedCodeEditorSpawner {
-    fn ext_code_ui(&mut self, data: &mut FileData) -> Response;
+    fn ext_code_ui(
+        &mut self,
+        data: &mut FileData,
+        vim: &mut Vim,
+        keymap: &mut Keymap,
+        scope: &SnippetScope,
+        completion: &Completion,
+        settings: &EditorSettings,
+    ) -> Response;
 }
 
 impl ExtendedCodeEditorSpawner for Ui {
-    fn ext_code_ui(&mut self, data: &mut FileData) -> Response {
-        self.add(ExtendedCodeEditor::ui(data))
+    fn ext_code_ui(
+        &mut self,
+        data: &mut FileData,
+        vim: &mut Vim,
+        keymap: &mut Keymap,
+        scope: &SnippetScope,
+        completion: &Completion,
+        settings: &EditorSettings,
+    ) -> Response {
+        self.add(ExtendedCodeEditor::ui(
+            data, vim, keymap, scope, completion, settings,
+        ))
     }
 }
 
@@ -0,0 +1,188 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    thread,
+};
+
+use crossbeam_channel::Receiver;
+use eframe::egui::{text::CCursor, text_selection::CCursorRange, Context, TextBuffer};
+use ignore::WalkBuilder;
+
+use crate::{code_editor::FileData, fuzzy::fuzzy_match, settings::CompletionSettings};
+
+/// Shorter words are not worth completing.
+const MIN_WORD_LEN: usize = 3;
+const MAX_FILE_SIZE: u64 = 512 * 1024;
+const MAX_PROJECT_WORDS: usize = 100_000;
+const MAX_ITEMS: usize = 50;
+
+/// Words to complete from the open files and, indexed in the background, the project files.
+#[derive(Default)]
+pub struct Completion {
+    pub settings: CompletionSettings,
+    roots: Vec<PathBuf>,
+    project_words: Vec<String>,
+    receiver: Option<Receiver<Vec<String>>>,
+    /// Words of the open files except the current one.
+    buffer_words: HashSet<String>,
+    /// Current file and number of open files `buffer_words` were collected for.
+    buffers_for: Option<(PathBuf, usize)>,
+}
+
+impl Completion {
+    /// Collects the words of the other open files when the current one changed and indexes the
+    /// project files again when the roots changed.
+    pub fn sync(
+        &mut self,
+        ctx: &Context,
+        settings: &CompletionSettings,
+        roots: &[PathBuf],
+        files: &HashMap<PathBuf, FileData>,
+        current_file: &Path,
+    ) {
+        self.settings = settings.clone();
+        let roots = if settings.project_words { roots } else { &[] };
+        if self.roots != roots {
+            self.roots = roots.to_vec();
+            self.project_words.clear();
+            self.index(ctx);
+        }
+        if let Some(words) = self.receiver.as_ref().and_then(|r| r.try_recv().ok()) {
+            self.project_words = words;
+            self.receiver = None;
+        }
+
+        let key = (current_file.to_path_buf(), files.len());
+        if self.buffers_for.as_ref() != Some(&key) {
+            self.buffer_words = files
+                .iter()
+                .filter(|(path, _)| *path != current_file)
+                .flat_map(|(_, file)| words(&file.text))
+                .map(str::to_string)
+                .collect();
+            self.buffers_for = Some(key);
+        }
+    }
+
+    fn index(&mut self, ctx: &Context) {
+        self.receiver = None;
+        if self.roots.is_empty() {
+            return;
+        }
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        let roots = self.roots.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let mut found = HashSet::new();
+            let files = roots.iter().flat_map(|root| {
+                WalkBuilder::new(root)
+                    .require_git(false)
+                    .build()
+                    .filter_map(Result::ok)
+                    .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+                    .filter(|entry| {
+                        entry
+                            .metadata()
+                            .is_ok_and(|metadata| metadata.len() <= MAX_FILE_SIZE)
+                    })
+            });
+            for entry in files {
+                // binary files are skipped
+                let Ok(content) = fs::read_to_string(entry.path()) else {
+                    continue;
+                };
+                found.extend(words(&content).map(str::to_string));
+                if found.len() >= MAX_PROJECT_WORDS {
+                    break;
+                }
+            }
+            let mut words = found.into_iter().collect::<Vec<_>>();
+            words.sort();
+            sender.send(words).unwrap_or_default();
+            ctx.request_repaint();
+        });
+        self.receiver = Some(receiver);
+    }
+
+    /// The words matching `word` best first with the matched char indices, words of `text`, the
+    /// current file, rank higher.
+    pub fn complete(&self, word: &str, text: &str) -> Vec<(String, Vec<usize>)> {
+        let first = word
+            .chars()
+            .next()
+            .map(|c| c.to_lowercase().collect::<String>());
+        let mut seen = HashSet::new();
+        let mut items = words(text)
+            .map(|candidate| (candidate, 2))
+            .chain(self.buffer_words.iter().map(|c| (c.as_str(), 1)))
+            .chain(self.project_words.iter().map(|c| (c.as_str(), 0)))
+            .filter(|(candidate, _)| {
+                // the first character has to match, like in most editors
+                let starts = match &first {
+                    Some(first) => candidate
+                        .chars()
+                        .next()
+                        .is_some_and(|c| c.to_lowercase().collect::<String>() == *first),
+                    None => true,
+                };
+                starts && *candidate != word && seen.insert(*candidate)
+            })
+            .filter_map(|(candidate, bonus)| {
+                let found = fuzzy_match(word, candidate)?;
+                Some((found.score + bonus, candidate, found.indices))
+            })
+            .collect::<Vec<_>>();
+        items.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then_with(|| a.1.len().cmp(&b.1.len()))
+                .then_with(|| a.1.cmp(b.1))
+        });
+        items.truncate(MAX_ITEMS);
+        items
+            .into_iter()
+            .map(|(_, candidate, indices)| (candidate.to_string(), indices))
+            .collect()
+    }
+}
+
+/// The completions shown below the cursor of an editor.
+#[derive(Clone, Debug)]
+pub struct CompletionPopup {
+    /// Char index where the completed word starts.
+    pub start: usize,
+    pub word: String,
+    pub items: Vec<(String, Vec<usize>)>,
+    pub selected: usize,
+}
+
+impl CompletionPopup {
+    /// Replaces the word up to `cursor` with the selected item.
+    pub fn accept(&self, text: &mut dyn TextBuffer, cursor: usize) -> CCursorRange {
+        let (item, _) = &self.items[self.selected];
+        text.delete_char_range(self.start..cursor);
+        text.insert_text(item, self.start);
+        CCursorRange::one(CCursor::new(self.start + item.chars().count()))
+    }
+}
+
+/// Identifier-like words of `text`.
+pub fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !is_word_char(c)).filter(|word| {
+        word.chars().count() >= MIN_WORD_LEN && !word.starts_with(|c: char| c.is_ascii_digit())
+    })
+}
+
+/// The start of the word before `cursor` and the word.
+pub fn word_before(text: &str, cursor: usize) -> (usize, String) {
+    let before = text.chars().take(cursor).collect::<Vec<_>>();
+    let start = before
+        .iter()
+        .rposition(|c| !is_word_char(*c))
+        .map_or(0, |i| i + 1);
+    (start, before[start..].iter().collect())
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
@@ -0,0 +1,41 @@
+use std::{fs, io, path::PathBuf};
+
+use similar::{ChangeTag, TextDiff};
+
+/// Line by line differences between two files.
+pub struct Diff {
+    pub left: PathBuf,
+    pub right: PathBuf,
+    pub lines: Vec<DiffLine>,
+}
+
+pub struct DiffLine {
+    pub tag: ChangeTag,
+    /// 1-based line numbers in the left and right file.
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+    pub text: String,
+}
+
+impl Diff {
+    pub fn load(left: PathBuf, right: PathBuf) -> io::Result<Self> {
+        let left_text = fs::read_to_string(&left)?;
+        let right_text = fs::read_to_string(&right)?;
+        let lines = TextDiff::from_lines(&left_text, &right_text)
+            .iter_all_changes()
+            .map(|change| DiffLine {
+                tag: change.tag(),
+                left: change.old_index().map(|i| i + 1),
+                right: change.new_index().map(|i| i + 1),
+                text: change.value().trim_end_matches(['\n', '\r']).to_string(),
+            })
+            .collect();
+        Ok(Self { left, right, lines })
+    }
+
+    /// Number of inserted and deleted lines.
+    pub fn stats(&self) -> (usize, usize) {
+        let count = |tag| self.lines.iter().filter(|line| line.tag == tag).count();
+        (count(ChangeTag::Insert), count(ChangeTag::Delete))
+    }
+}
@@ -0,0 +1,481 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::indent::Indent;
+
+const FILE_NAME: &str = ".editorconfig";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EndOfLine {
+    Lf,
+    CrLf,
+    Cr,
+}
+
+impl EndOfLine {
+    fn as_str(self) -> &'static str {
+        match self {
+            EndOfLine::Lf => "\n",
+            EndOfLine::CrLf => "\r\n",
+            EndOfLine::Cr => "\r",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Charset {
+    Utf8,
+    Utf8Bom,
+    Latin1,
+    Utf16Be,
+    Utf16Le,
+}
+
+/// The `.editorconfig` properties of a file, `None` where nothing is set.
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct EditorConfig {
+    pub indent: Option<Indent>,
+    pub tab_width: Option<usize>,
+    pub end_of_line: Option<EndOfLine>,
+    pub charset: Option<Charset>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+}
+
+impl EditorConfig {
+    /// Reads the `.editorconfig` files from the directory of `path` up to the one with
+    /// `root = true`, closer files win.
+    pub fn resolve(path: &Path) -> Self {
+        let mut files = Vec::new();
+        for dir in path.ancestors().skip(1) {
+            let Ok(content) = fs::read_to_string(dir.join(FILE_NAME)) else {
+                continue;
+            };
+            let root = is_root(&content);
+            files.push((dir.to_path_buf(), content));
+            if root {
+                break;
+            }
+        }
+
+        let mut properties = HashMap::new();
+        for (dir, content) in files.iter().rev() {
+            apply_file(&mut properties, dir, content, path);
+        }
+        Self::from_properties(&properties)
+    }
+
+    fn from_properties(properties: &HashMap<String, String>) -> Self {
+        let get = |key: &str| properties.get(key).map(String::as_str);
+        // a width of zero would make indenting a no-op
+        let number = |key: &str| {
+            get(key)
+                .and_then(|value| value.parse::<usize>().ok())
+                .filter(|number| *number > 0)
+        };
+        let boolean = |key: &str| match get(key) {
+            Some("true") => Some(true),
+            Some("false") => Some(false),
+            _ => None,
+        };
+
+        let tab_width = number("tab_width").or(number("indent_size"));
+        let indent_size = number("indent_size").or(tab_width);
+        let indent = match get("indent_style") {
+            Some("tab") => Some(Indent::Tabs),
+            Some("space") => Some(Indent::Spaces(indent_size.unwrap_or(4))),
+            _ => None,
+        };
+        let end_of_line = match get("end_of_line") {
+            Some("lf") => Some(EndOfLine::Lf),
+            Some("crlf") => Some(EndOfLine::CrLf),
+            Some("cr") => Some(EndOfLine::Cr),
+            _ => None,
+        };
+        let charset = match get("charset") {
+            Some("utf-8") => Some(Charset::Utf8),
+            Some("utf-8-bom") => Some(Charset::Utf8Bom),
+            Some("latin1") => Some(Charset::Latin1),
+            Some("utf-16be") => Some(Charset::Utf16Be),
+            Some("utf-16le") => Some(Charset::Utf16Le),
+            _ => None,
+        };
+        Self {
+            indent,
+            tab_width,
+            end_of_line,
+            charset,
+            trim_trailing_whitespace: boolean("trim_trailing_whitespace"),
+            insert_final_newline: boolean("insert_final_newline"),
+        }
+    }
+
+    /// The text as it should be saved: line endings, trailing whitespace and final newline.
+    pub fn normalize(&self, text: &str) -> String {
+        if self.end_of_line.is_none()
+            && self.trim_trailing_whitespace.is_none()
+            && self.insert_final_newline.is_none()
+        {
+            return text.to_string();
+        }
+
+        let eol = match self.end_of_line {
+            Some(end_of_line) => end_of_line.as_str(),
+            None if text.contains("\r\n") => "\r\n",
+            None => "\n",
+        };
+        let mut lines = text.split('\n').collect::<Vec<_>>();
+        let ends_with_newline = text.ends_with('\n');
+        if ends_with_newline {
+            lines.pop();
+        }
+        let mut normalized = lines
+            .iter()
+            .map(|line| {
+                let line = line.strip_suffix('\r').unwrap_or(line);
+                if self.trim_trailing_whitespace == Some(true) {
+                    line.trim_end_matches([' ', '\t'])
+                } else {
+                    line
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(eol);
+
+        let final_newline = match self.insert_final_newline {
+            Some(true) => !normalized.is_empty(),
+            Some(false) => {
+                let trimmed = normalized.trim_end_matches(['\r', '\n']).len();
+                normalized.truncate(trimmed);
+                false
+            }
+            None => ends_with_newline,
+        };
+        if final_newline {
+            normalized += eol;
+        }
+        normalized
+    }
+
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        match self.charset {
+            None | Some(Charset::Utf8) => text.as_bytes().to_vec(),
+            Some(Charset::Utf8Bom) => [&[0xEF, 0xBB, 0xBF], text.as_bytes()].concat(),
+            Some(Charset::Latin1) => text
+                .chars()
+                .map(|c| u8::try_from(c).unwrap_or(b'?'))
+                .collect(),
+            Some(Charset::Utf16Be) => [0xFEFF]
+                .into_iter()
+                .chain(text.encode_utf16())
+                .flat_map(u16::to_be_bytes)
+                .collect(),
+            Some(Charset::Utf16Le) => [0xFEFF]
+                .into_iter()
+                .chain(text.encode_utf16())
+                .flat_map(u16::to_le_bytes)
+                .collect(),
+        }
+    }
+
+    /// Reads `bytes` in the configured charset, a byte order mark takes precedence.
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        let utf16 = |bytes: &[u8], from: fn([u8; 2]) -> u16| {
+            let units = bytes
+                .chunks_exact(2)
+                .map(|pair| from([pair[0], pair[1]]))
+                .collect::<Vec<_>>();
+            String::from_utf16_lossy(&units)
+        };
+        match bytes {
+            [0xEF, 0xBB, 0xBF, rest @ ..] if self.charset == Some(Charset::Utf8Bom) => {
+                String::from_utf8_lossy(rest).into_owned()
+            }
+            [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+            [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+            _ => match self.charset {
+                Some(Charset::Latin1) => bytes.iter().map(|b| char::from(*b)).collect(),
+                Some(Charset::Utf16Be) => utf16(bytes, u16::from_be_bytes),
+                Some(Charset::Utf16Le) => utf16(bytes, u16::from_le_bytes),
+                _ => String::from_utf8_lossy(bytes).into_owned(),
+            },
+        }
+    }
+}
+
+fn is_root(content: &str) -> bool {
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            return false;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            if key.trim().eq_ignore_ascii_case("root") {
+                return value.trim().eq_ignore_ascii_case("true");
+            }
+        }
+    }
+    false
+}
+
+/// Sets the properties of the sections of `content` matching `path`.
+fn apply_file(properties: &mut HashMap<String, String>, dir: &Path, content: &str, path: &Path) {
+    let relative = path
+        .strip_prefix(dir)
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .to_string_lossy()
+        .replace('\\', "/");
+    let mut matches = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            // patterns without a slash match in any directory
+            let pattern = match section.strip_prefix('/') {
+                Some(pattern) => pattern.to_string(),
+                None if section.contains('/') => section.to_string(),
+                None => format!("**/{section}"),
+            };
+            // `**/` also matches no directory at all
+            let relative = match pattern.starts_with("**/") {
+                true => format!("/{relative}"),
+                false => relative.clone(),
+            };
+            matches = glob_match(pattern.as_bytes(), relative.as_bytes());
+            continue;
+        }
+        if !matches {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            let key = key.trim().to_lowercase();
+            let value = value.trim().to_lowercase();
+            if value == "unset" {
+                properties.remove(&key);
+            } else {
+                properties.insert(key, value);
+            }
+        }
+    }
+}
+
+/// EditorConfig globs: `*`, `**`, `?`, `[abc]`, `[!abc]`, `{a,b}` and `{1..10}`.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', rest @ ..] => (0..=text.len()).any(|i| glob_match(rest, &text[i..])),
+        [b'*', rest @ ..] => (0..=text.len())
+            .take_while(|i| *i == 0 || text[i - 1] != b'/')
+            .any(|i| glob_match(rest, &text[i..])),
+        [b'?', rest @ ..] => {
+            matches!(text, [c, text @ ..] if *c != b'/' && glob_match(rest, text))
+        }
+        [b'[', rest @ ..] => {
+            let Some(end) = rest.iter().position(|c| *c == b']') else {
+                return literal(pattern, text);
+            };
+            let (negated, set) = match &rest[..end] {
+                [b'!', set @ ..] => (true, set),
+                set => (false, set),
+            };
+            let [c, text @ ..] = text else {
+                return false;
+            };
+            let mut found = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == b'-' {
+                    found |= (set[i]..=set[i + 2]).contains(c);
+                    i += 3;
+                } else {
+                    found |= set[i] == *c;
+                    i += 1;
+                }
+            }
+            found != negated && *c != b'/' && glob_match(&rest[end + 1..], text)
+        }
+        [b'{', rest @ ..] => {
+            let Some(end) = closing_brace(rest) else {
+                return literal(pattern, text);
+            };
+            let inner = &rest[..end];
+            let after = &rest[end + 1..];
+            if let Some((from, to)) = number_range(inner) {
+                let digits = text
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit() || **c == b'-')
+                    .count();
+                return (1..=digits).any(|len| {
+                    std::str::from_utf8(&text[..len])
+                        .ok()
+                        .and_then(|n| n.parse::<i64>().ok())
+                        .is_some_and(|n| (from..=to).contains(&n))
+                        && glob_match(after, &text[len..])
+                });
+            }
+            let alternatives = split_alternatives(inner);
+            // a single alternative is no alternative, `{a}` matches itself
+            if alternatives.len() == 1 {
+                return literal(pattern, text);
+            }
+            alternatives
+                .into_iter()
+                .any(|alternative| glob_match(&[alternative, after].concat(), text))
+        }
+        [b'\\', c, rest @ ..] => matches!(text, [t, text @ ..] if t == c && glob_match(rest, text)),
+        _ => literal(pattern, text),
+    }
+}
+
+fn literal(pattern: &[u8], text: &[u8]) -> bool {
+    matches!((pattern, text), ([p, pattern @ ..], [t, text @ ..]) if p == t && glob_match(pattern, text))
+}
+
+fn closing_brace(pattern: &[u8]) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in pattern.iter().enumerate() {
+        match c {
+            b'{' => depth += 1,
+            b'}' if depth == 0 => return Some(i),
+            b'}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn split_alternatives(pattern: &[u8]) -> Vec<&[u8]> {
+    let mut alternatives = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in pattern.iter().enumerate() {
+        match c {
+            b'{' => depth += 1,
+            b'}' => depth -= 1,
+            b',' if depth == 0 => {
+                alternatives.push(&pattern[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    alternatives.push(&pattern[start..]);
+    alternatives
+}
+
+fn number_range(pattern: &[u8]) -> Option<(i64, i64)> {
+    let (from, to) = std::str::from_utf8(pattern).ok()?.split_once("..")?;
+    Some((from.parse().ok()?, to.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        glob_match(pattern.as_bytes(), text.as_bytes())
+    }
+
+    #[test]
+    fn matches_globs() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(!matches("*.rs", "src/main.rs"));
+        assert!(matches("src/?.c", "src/a.c"));
+        assert!(matches("[Mm]akefile", "makefile"));
+        assert!(matches("[!a]b", "cb"));
+        assert!(!matches("[!a]b", "ab"));
+    }
+
+    #[test]
+    fn matches_alternatives_and_ranges() {
+        assert!(matches("*.{rs,toml}", "Cargo.toml"));
+        assert!(matches("*.{rs,toml}", "main.rs"));
+        assert!(!matches("*.{rs,toml}", "main.c"));
+        assert!(matches("{src,tests}/*.rs", "tests/a.rs"));
+        assert!(matches("*.{json,{yml,yaml}}", "a.yaml"));
+        assert!(matches("file{1..3}.txt", "file2.txt"));
+        assert!(!matches("file{1..3}.txt", "file4.txt"));
+        assert!(matches("{single}", "{single}"));
+    }
+
+    #[test]
+    fn matches_any_directories() {
+        assert!(matches("**/*.rs", "/main.rs"));
+        assert!(matches("**/*.rs", "/src/bin/main.rs"));
+        assert!(matches("src/**/mod.rs", "src/a/b/mod.rs"));
+        assert!(!matches("src/**/mod.rs", "lib/a/mod.rs"));
+    }
+
+    #[test]
+    fn closer_sections_win() {
+        let dir = std::env::temp_dir().join("eta-editorconfig");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(
+            dir.join(FILE_NAME),
+            "root = true\n\n[*]\nindent_style = space\nindent_size = 2\nend_of_line = crlf\n\n\
+             [*.md]\ntrim_trailing_whitespace = false\n\n[sub/*.rs]\nindent_size = 8\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("sub").join(FILE_NAME),
+            "[*.rs]\ninsert_final_newline = true\ntrim_trailing_whitespace = true\n\
+             end_of_line = unset\n",
+        )
+        .unwrap();
+
+        let config = EditorConfig::resolve(&dir.join("sub/a.rs"));
+        assert_eq!(config.indent, Some(Indent::Spaces(8)));
+        assert_eq!(config.tab_width, Some(8));
+        assert_eq!(config.end_of_line, None);
+        assert_eq!(config.normalize("a  \nb\t"), "a\nb\n");
+
+        let config = EditorConfig::resolve(&dir.join("x.md"));
+        assert_eq!(config.indent, Some(Indent::Spaces(2)));
+        assert_eq!(config.trim_trailing_whitespace, Some(false));
+        assert_eq!(config.normalize("a \nb\n"), "a \r\nb\r\n");
+    }
+
+    #[test]
+    fn ignores_zero_widths() {
+        let properties = |pairs: &[(&str, &str)]| {
+            let properties = pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            EditorConfig::from_properties(&properties)
+        };
+        let config = properties(&[("indent_style", "space"), ("indent_size", "0")]);
+        assert_eq!(config.indent, Some(Indent::Spaces(4)));
+        assert_eq!(config.tab_width, None);
+        let config = properties(&[
+            ("indent_style", "space"),
+            ("indent_size", "0"),
+            ("tab_width", "2"),
+        ]);
+        assert_eq!(config.indent, Some(Indent::Spaces(2)));
+        assert_eq!(config.tab_width, Some(2));
+        let config = properties(&[("indent_style", "tab"), ("tab_width", "0")]);
+        assert_eq!(config.indent, Some(Indent::Tabs));
+        assert_eq!(config.tab_width, None);
+    }
+
+    #[test]
+    fn encodes_charsets() {
+        let mut config = EditorConfig {
+            charset: Some(Charset::Utf16Le),
+            insert_final_newline: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(config.decode(&config.encode("hé")), "hé");
+        config.charset = Some(Charset::Latin1);
+        assert_eq!(config.encode("hé"), b"h\xe9");
+        assert_eq!(config.decode(&config.encode("hé")), "hé");
+        assert_eq!(config.normalize("x\n\n"), "x");
+    }
+}
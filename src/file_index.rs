@@ -0,0 +1,104 @@
+use std::{
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::Receiver;
+use eframe::egui::Context;
+use ignore::WalkBuilder;
+
+/// A walk younger than this is reused when nothing changed in the file tree.
+const MAX_AGE: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexedFile {
+    pub path: PathBuf,
+    /// Relative to its root, starting with the name of the root in a workspace.
+    pub relative: PathBuf,
+}
+
+/// All files of a project, built in the background.
+#[derive(Default)]
+pub struct FileIndex {
+    roots: Vec<PathBuf>,
+    files: Vec<IndexedFile>,
+    receiver: Option<Receiver<Vec<IndexedFile>>>,
+    /// When the last walk started and the file tree changes it has seen.
+    walked: Option<(Instant, usize)>,
+    /// Bumped every time the files change.
+    pub generation: usize,
+}
+
+impl FileIndex {
+    /// Starts rebuilding the index of `roots` unless the last walk is recent and the file tree has
+    /// not seen changes since, the old one stays usable until the new one is done.
+    pub fn refresh(&mut self, ctx: &Context, roots: &[PathBuf], tree_changes: usize) {
+        if self.roots != roots {
+            self.roots = roots.to_vec();
+            self.files.clear();
+            self.walked = None;
+            self.generation += 1;
+        } else if self.is_building()
+            || self
+                .walked
+                .is_some_and(|(at, changes)| at.elapsed() < MAX_AGE && changes == tree_changes)
+        {
+            return;
+        }
+        self.walked = Some((Instant::now(), tree_changes));
+
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        let roots = roots.to_vec();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let files = roots
+                .iter()
+                .flat_map(|root| {
+                    let name = match roots.len() {
+                        1 => PathBuf::new(),
+                        _ => PathBuf::from(root.file_name().unwrap_or_default()),
+                    };
+                    walk(root).into_iter().map(move |relative| IndexedFile {
+                        path: root.join(&relative),
+                        relative: name.join(relative),
+                    })
+                })
+                .collect();
+            sender.send(files).unwrap_or_default();
+            ctx.request_repaint();
+        });
+        self.receiver = Some(receiver);
+    }
+
+    /// Picks up a finished rebuild.
+    pub fn poll(&mut self) {
+        if let Some(files) = self.receiver.as_ref().and_then(|r| r.try_recv().ok()) {
+            self.files = files;
+            self.receiver = None;
+            self.generation += 1;
+        }
+    }
+
+    pub fn is_building(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    pub fn files(&self) -> &[IndexedFile] {
+        &self.files
+    }
+}
+
+fn walk(root: &Path) -> Vec<PathBuf> {
+    let mut files = WalkBuilder::new(root)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .filter_map(|entry| entry.path().strip_prefix(root).ok().map(Path::to_path_buf))
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
@@ -0,0 +1,167 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Copies `from` to `to`, directories recursively. Symlinks are copied as links.
+pub fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    if to.starts_with(from) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Cannot copy a directory into itself",
+        ));
+    }
+    let metadata = fs::symlink_metadata(from)?;
+    if metadata.is_symlink() {
+        copy_link(from, to)
+    } else if metadata.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+#[cfg(unix)]
+fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    fs::copy(from, to).map(|_| ())
+}
+
+/// `path`, or if that exists `name (1).ext`, `name (2).ext`...
+pub fn unique_path(path: &Path) -> PathBuf {
+    if fs::symlink_metadata(path).is_err() {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|i| path.with_file_name(format!("{stem} ({i}){extension}")))
+        .find(|path| fs::symlink_metadata(path).is_err())
+        .unwrap_or_default()
+}
+
+/// A file tree operation which can be undone.
+pub enum FileOp {
+    Created(PathBuf),
+    Moved {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// With the seconds since the epoch before it was trashed, to find it in the trash.
+    Trashed(PathBuf, i64),
+}
+
+/// Moves `path` to the trash.
+pub fn trash(path: &Path) -> io::Result<FileOp> {
+    let deleted_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() as i64);
+    trash::delete(path).map_err(io::Error::other)?;
+    Ok(FileOp::Trashed(path.to_path_buf(), deleted_at))
+}
+
+/// Puts the item trashed from `path` at `deleted_at` back where it was.
+pub fn restore(path: &Path, deleted_at: i64) -> io::Result<()> {
+    if fs::symlink_metadata(path).is_ok() {
+        return Err(io::ErrorKind::AlreadyExists.into());
+    }
+    let Some(items) = trashed_items() else {
+        return Err(io::Error::other(format!(
+            "Cannot restore {} from the trash on this platform",
+            path.display()
+        )));
+    };
+    // the earliest deletion of the path since then, later ones were trashed again
+    let item = items
+        .into_iter()
+        .filter(|item| item.original_path() == path && item.time_deleted >= deleted_at)
+        .min_by_key(|item| item.time_deleted)
+        .ok_or_else(|| io::Error::other(format!("Cannot find {} in the trash", path.display())))?;
+    restore_items(item)
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+))]
+fn trashed_items() -> Option<Vec<trash::TrashItem>> {
+    trash::os_limited::list().ok()
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+)))]
+fn trashed_items() -> Option<Vec<trash::TrashItem>> {
+    None
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+))]
+fn restore_items(item: trash::TrashItem) -> io::Result<()> {
+    trash::os_limited::restore_all([item]).map_err(io::Error::other)
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+)))]
+fn restore_items(_item: trash::TrashItem) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Why `name` cannot replace the name of `path`.
+pub fn check_name(path: &Path, name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("A name is required".into());
+    }
+    if name == "." || name == ".." {
+        return Err(format!("\"{name}\" is not a valid name"));
+    }
+    if name.contains(std::path::is_separator) || name.contains('\0') {
+        return Err("A name cannot contain slashes".into());
+    }
+    if name != name.trim() {
+        return Err("A name cannot start or end with whitespace".into());
+    }
+    let target = path.with_file_name(name);
+    if target != path && fs::symlink_metadata(&target).is_ok() {
+        return Err(format!("\"{name}\" already exists"));
+    }
+    Ok(())
+}
@@ -0,0 +1,960 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crossbeam_channel::Receiver;
+use eframe::egui::Context;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use notify::{event::ModifyKind, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+
+use crate::file_ops::FileOp;
+
+const MAX_UNDO: usize = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Directory,
+    /// Sockets, pipes, devices...
+    Special,
+    /// A symlink pointing nowhere.
+    Broken,
+    /// A directory which could not be read, the name is the error.
+    Error,
+}
+
+/// A visible line of the tree.
+#[derive(Clone, Debug)]
+pub struct Row {
+    pub path: PathBuf,
+    pub name: String,
+    pub depth: usize,
+    pub kind: EntryKind,
+    /// Target of a symlink.
+    pub link: Option<PathBuf>,
+    /// Matched by `.gitignore`, `.ignore` or the exclude list.
+    pub ignored: bool,
+    /// Size of files in bytes.
+    pub size: Option<u64>,
+    pub modified: Option<SystemTime>,
+    /// A file with other files nested below it.
+    pub nests: bool,
+    /// A folder of a workspace with several ones.
+    pub root: bool,
+}
+
+impl Row {
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Directory
+    }
+
+    /// Directories and files with nested files.
+    pub fn is_expandable(&self) -> bool {
+        self.is_dir() || self.nests
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    path: PathBuf,
+    name: String,
+    kind: EntryKind,
+    link: Option<PathBuf>,
+    size: Option<u64>,
+    modified: Option<SystemTime>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClipboardMode {
+    Copy,
+    Cut,
+}
+
+/// What to do when a pasted entry already exists.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Conflict {
+    KeepBoth,
+    Replace,
+    Skip,
+}
+
+/// A paste which is waiting for a conflict to be resolved.
+pub struct Paste {
+    pub sources: VecDeque<PathBuf>,
+    pub dir: PathBuf,
+    pub mode: ClipboardMode,
+    /// Chosen with "apply to all".
+    pub resolution: Option<Conflict>,
+    pub apply_to_all: bool,
+    /// What was pasted so far, undone together.
+    pub done: Vec<FileOp>,
+}
+
+/// An entry being renamed inline.
+pub struct Rename {
+    pub path: PathBuf,
+    pub name: String,
+    /// Focus the input in the next frame.
+    pub focus: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortBy {
+    #[default]
+    Name,
+    /// By extension, then by name.
+    Type,
+    /// Newest first.
+    Modified,
+    /// Largest first.
+    Size,
+}
+
+/// Shows the files matching `children` below the file matching `parent`.
+///
+/// A `*` in `parent` is captured and replaces `$(capture)` in `children`, which may contain a `*` as well.
+struct NestingRule {
+    parent: String,
+    children: Vec<String>,
+}
+
+impl NestingRule {
+    /// Parses `parent: child, child`.
+    fn parse(line: &str) -> Option<Self> {
+        let (parent, children) = line.split_once(':')?;
+        let parent = parent.trim();
+        let children = children
+            .split(',')
+            .map(str::trim)
+            .filter(|child| !child.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        (!parent.is_empty() && !children.is_empty()).then(|| Self {
+            parent: parent.into(),
+            children,
+        })
+    }
+
+    /// Whether `child` nests below `parent`.
+    fn nests(&self, parent: &str, child: &str) -> bool {
+        let Some(captured) = capture(&self.parent, parent) else {
+            return false;
+        };
+        self.children.iter().any(|pattern| {
+            capture(&pattern.replace("$(capture)", captured), child).is_some() && child != parent
+        })
+    }
+}
+
+/// What the `*` of `pattern` matches in `name`.
+fn capture<'a>(pattern: &str, name: &'a str) -> Option<&'a str> {
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => name
+            .strip_prefix(prefix)?
+            .strip_suffix(suffix)
+            .filter(|_| name.len() >= prefix.len() + suffix.len()),
+        None => (pattern == name).then_some(""),
+    }
+}
+
+/// How the tree orders and groups its entries.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TreeView {
+    pub sort: SortBy,
+    /// Size and modification time on hover.
+    pub details: bool,
+    pub nesting: bool,
+    /// `parent: child, child`, see [`NestingRule`].
+    pub nesting_rules: Vec<String>,
+}
+
+impl Default for TreeView {
+    fn default() -> Self {
+        Self {
+            sort: SortBy::default(),
+            details: true,
+            nesting: true,
+            nesting_rules: [
+                "Cargo.toml: Cargo.lock, rust-toolchain.toml",
+                "package.json: package-lock.json, yarn.lock, pnpm-lock.yaml",
+                "*.ts: $(capture).js, $(capture).d.ts",
+                "*.js: $(capture).js.map, $(capture).min.js",
+                "*.c: $(capture).h",
+                "*.cpp: $(capture).h, $(capture).hpp",
+                ".gitignore: .gitattributes, .gitmodules",
+            ]
+            .map(str::to_string)
+            .to_vec(),
+        }
+    }
+}
+
+/// Compares numbers in names by value, `file2` before `file10`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a_chars, mut b_chars) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(c);
+                    }
+                    digits
+                };
+                let (x, y) = (number(&mut a_chars), number(&mut b_chars));
+                let (x_trimmed, y_trimmed) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let ordering = x_trimmed
+                    .len()
+                    .cmp(&y_trimmed.len())
+                    .then_with(|| x_trimmed.cmp(y_trimmed));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+/// What the tree of a project shows.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TreeFilter {
+    /// Show ignored entries dimmed instead of hiding them.
+    pub show_ignored: bool,
+    pub show_hidden: bool,
+    /// Gitignore patterns on top of the ignore files.
+    pub exclude: Vec<String>,
+}
+
+/// The project directory, read lazily when directories are expanded and kept up to date by a watcher.
+#[derive(Default, Serialize, Deserialize)]
+pub struct FileTree {
+    expanded: HashSet<PathBuf>,
+    /// The last failed operation, shown above the tree.
+    #[serde(skip)]
+    pub error: Option<String>,
+    /// In the order of selection.
+    #[serde(skip)]
+    selection: Vec<PathBuf>,
+    /// Where shift-click ranges start.
+    #[serde(skip)]
+    anchor: Option<PathBuf>,
+    /// The row moved with the arrow keys.
+    #[serde(skip)]
+    cursor: Option<PathBuf>,
+    /// Scroll this row into view.
+    #[serde(skip)]
+    pub scroll_to: Option<PathBuf>,
+    #[serde(skip)]
+    pub rename: Option<Rename>,
+    /// Typed since `typed_at` to select a row by its name.
+    #[serde(skip)]
+    typed: String,
+    #[serde(skip)]
+    typed_at: f64,
+    #[serde(skip)]
+    pub clipboard: Option<(Vec<PathBuf>, ClipboardMode)>,
+    #[serde(skip)]
+    pub paste: Option<Paste>,
+    /// Entries waiting for the delete to be confirmed.
+    #[serde(skip)]
+    pub delete: Option<Vec<PathBuf>>,
+    /// Delete to the trash without asking.
+    #[serde(default)]
+    pub skip_delete_confirmation: bool,
+    /// Undoable operations with their titles, latest last.
+    #[serde(skip)]
+    history: Vec<(String, Vec<FileOp>)>,
+    /// Per project root.
+    #[serde(default)]
+    filters: HashMap<PathBuf, TreeFilter>,
+    #[serde(default)]
+    view: TreeView,
+    #[serde(skip)]
+    roots: Vec<PathBuf>,
+    /// Children of the directories which were read, or why they could not be read.
+    #[serde(skip)]
+    children: HashMap<PathBuf, Result<Vec<Entry>, String>>,
+    /// Directories to read again.
+    #[serde(skip)]
+    dirty: HashSet<PathBuf>,
+    #[serde(skip)]
+    rows: Vec<Row>,
+    #[serde(skip)]
+    rows_dirty: bool,
+    #[serde(skip)]
+    watcher: Option<RecommendedWatcher>,
+    #[serde(skip)]
+    events: Option<Receiver<notify::Result<notify::Event>>>,
+    /// Bumped when the watcher saw entries being created, removed or renamed.
+    #[serde(skip)]
+    changes: usize,
+    /// `.gitignore` and `.ignore` of the directories.
+    #[serde(skip)]
+    ignores: HashMap<PathBuf, Gitignore>,
+    /// `.git/info/exclude`, the global gitignore and the exclude list per root.
+    #[serde(skip)]
+    excludes: HashMap<PathBuf, Vec<Gitignore>>,
+}
+
+impl FileTree {
+    /// Catches up with `roots` and the changes on disk, call once per frame.
+    pub fn sync(&mut self, ctx: &Context, roots: &[PathBuf]) {
+        if self.roots != roots {
+            self.set_roots(ctx, roots);
+        }
+
+        if let Some(events) = &self.events {
+            for event in events.try_iter().flatten() {
+                if !matches!(
+                    event.kind,
+                    EventKind::Access(_)
+                        | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Metadata(_))
+                ) {
+                    self.changes += 1;
+                }
+                for path in event.paths {
+                    let name = path.file_name().unwrap_or_default();
+                    if name == ".gitignore" || name == ".ignore" || name == "exclude" {
+                        self.ignores.clear();
+                        self.excludes.clear();
+                        self.rows_dirty = true;
+                    }
+                    if self.children.contains_key(&path) {
+                        self.dirty.insert(path.clone());
+                    }
+                    if let Some(parent) = path.parent() {
+                        self.dirty.insert(parent.to_path_buf());
+                    }
+                }
+            }
+        }
+        for dir in std::mem::take(&mut self.dirty) {
+            if self.children.contains_key(&dir) {
+                self.load(&dir);
+                self.rows_dirty = true;
+            }
+        }
+
+        if self.rows_dirty {
+            self.rows_dirty = false;
+            self.rows.clear();
+            for root in roots {
+                if !self.excludes.contains_key(root) {
+                    self.build_excludes(root);
+                }
+            }
+            match roots {
+                [root] => self.collect_rows(root.clone(), 0, false),
+                // the folders of a workspace are the top level
+                roots => {
+                    for root in roots {
+                        self.rows.push(Row {
+                            path: root.clone(),
+                            name: root
+                                .file_name()
+                                .unwrap_or(root.as_os_str())
+                                .to_string_lossy()
+                                .to_string(),
+                            depth: 0,
+                            kind: EntryKind::Directory,
+                            link: None,
+                            ignored: false,
+                            size: None,
+                            modified: None,
+                            nests: false,
+                            root: true,
+                        });
+                        if self.expanded.contains(root) {
+                            self.collect_rows(root.clone(), 1, false);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// The visible rows, expanded directories include their children.
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    pub fn is_expanded(&self, path: &Path) -> bool {
+        self.expanded.contains(path)
+    }
+
+    /// Swaps the expanded directories for the ones of another project.
+    pub fn replace_expanded(&mut self, expanded: HashSet<PathBuf>) -> HashSet<PathBuf> {
+        self.rows_dirty = true;
+        std::mem::replace(&mut self.expanded, expanded)
+    }
+
+    pub fn toggle(&mut self, path: &Path) {
+        if !self.expanded.remove(path) {
+            self.expanded.insert(path.to_path_buf());
+        }
+        self.rows_dirty = true;
+    }
+
+    /// Of the project, shared by the folders of a workspace.
+    pub fn filter(&self) -> TreeFilter {
+        self.roots
+            .first()
+            .and_then(|root| self.filters.get(root))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn set_filter(&mut self, filter: TreeFilter) {
+        if let Some(root) = self.roots.first() {
+            if self.filters.get(root) != Some(&filter) {
+                self.filters.insert(root.clone(), filter);
+                self.excludes.clear();
+                self.rows_dirty = true;
+            }
+        }
+    }
+
+    pub fn view(&self) -> TreeView {
+        self.view.clone()
+    }
+
+    pub fn set_view(&mut self, view: TreeView) {
+        if self.view != view {
+            self.view = view;
+            self.rows_dirty = true;
+        }
+    }
+
+    /// Remembers `ops` to undo them together.
+    pub fn record(&mut self, title: impl Into<String>, ops: Vec<FileOp>) {
+        if ops.is_empty() {
+            return;
+        }
+        self.history.push((title.into(), ops));
+        if self.history.len() > MAX_UNDO {
+            self.history.remove(0);
+        }
+    }
+
+    /// Title of the operation `take_undo` would return.
+    pub fn undo_title(&self) -> Option<&str> {
+        self.history.last().map(|(title, _)| title.as_str())
+    }
+
+    pub fn take_undo(&mut self) -> Option<(String, Vec<FileOp>)> {
+        self.history.pop()
+    }
+
+    pub fn is_selected(&self, path: &Path) -> bool {
+        self.selection.iter().any(|selected| selected == path)
+    }
+
+    pub fn selection(&self) -> &[PathBuf] {
+        &self.selection
+    }
+
+    /// Selects only `path`.
+    pub fn select(&mut self, path: &Path) {
+        self.selection = vec![path.to_path_buf()];
+        self.anchor = Some(path.to_path_buf());
+        self.cursor = Some(path.to_path_buf());
+    }
+
+    pub fn cursor(&self) -> Option<&Path> {
+        self.cursor.as_deref()
+    }
+
+    /// Moves the cursor by `offset` rows, `extend` selects the rows on the way.
+    pub fn move_cursor(&mut self, offset: isize, extend: bool) {
+        let selectable = self
+            .rows
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| row.kind != EntryKind::Error)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        let Some(last) = selectable.len().checked_sub(1) else {
+            return;
+        };
+        let current = self
+            .cursor
+            .as_deref()
+            .and_then(|cursor| selectable.iter().position(|i| self.rows[*i].path == cursor));
+        // without a cursor, down starts at the top and up at the bottom
+        let next = match current {
+            Some(current) => current.saturating_add_signed(offset),
+            None if offset < 0 => (last + 1).saturating_add_signed(offset),
+            None => offset.unsigned_abs() - 1,
+        }
+        .min(last);
+        let path = self.rows[selectable[next]].path.clone();
+        if extend {
+            self.select_range(&path);
+        } else {
+            self.select(&path);
+        }
+        self.scroll_to = Some(path);
+    }
+
+    /// Selects the next row starting with the typed text, typing within a second continues it.
+    pub fn type_to_select(&mut self, text: &str, time: f64) {
+        if time - self.typed_at > 1.0 {
+            self.typed.clear();
+        }
+        self.typed_at = time;
+        self.typed.push_str(&text.to_lowercase());
+
+        let start = self
+            .cursor
+            .as_deref()
+            .and_then(|cursor| self.rows.iter().position(|row| row.path == cursor))
+            .unwrap_or_default();
+        // a single character cycles through the matches, longer text refines the current one
+        let skip = usize::from(self.typed.chars().count() == 1);
+        let found = (0..self.rows.len())
+            .map(|i| &self.rows[(start + skip + i) % self.rows.len()])
+            .find(|row| {
+                row.kind != EntryKind::Error && row.name.to_lowercase().starts_with(&self.typed)
+            })
+            .map(|row| row.path.clone());
+        if let Some(path) = found {
+            self.select(&path);
+            self.scroll_to = Some(path);
+        }
+    }
+
+    /// Expands the parents of `path` and selects it.
+    pub fn reveal(&mut self, path: &Path) {
+        let Some(root) = self.root_of(path) else {
+            return;
+        };
+        // including the root, the top level of a workspace
+        for parent in path.ancestors().skip(1) {
+            if !parent.starts_with(&root) {
+                break;
+            }
+            self.expanded.insert(parent.to_path_buf());
+        }
+        self.rows_dirty = true;
+        self.select(path);
+        self.scroll_to = Some(path.to_path_buf());
+    }
+
+    /// Starts renaming `path` inline.
+    pub fn start_rename(&mut self, path: &Path) {
+        self.rename = Some(Rename {
+            path: path.to_path_buf(),
+            name: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            focus: true,
+        });
+        self.scroll_to = Some(path.to_path_buf());
+    }
+
+    /// Adds or removes `path` from the selection, e.g. on ctrl-click.
+    pub fn toggle_selected(&mut self, path: &Path) {
+        if self.is_selected(path) {
+            self.selection.retain(|selected| selected != path);
+        } else {
+            self.selection.push(path.to_path_buf());
+        }
+        self.anchor = Some(path.to_path_buf());
+        self.cursor = Some(path.to_path_buf());
+    }
+
+    /// Selects the visible rows between the last clicked one and `path`, e.g. on shift-click.
+    pub fn select_range(&mut self, path: &Path) {
+        let position = |target: &Path| self.rows.iter().position(|row| row.path == target);
+        let (Some(start), Some(end)) = (self.anchor.as_deref().and_then(position), position(path))
+        else {
+            return self.select(path);
+        };
+        self.selection = self.rows[start.min(end)..=start.max(end)]
+            .iter()
+            .filter(|row| row.kind != EntryKind::Error)
+            .map(|row| row.path.clone())
+            .collect();
+        self.cursor = Some(path.to_path_buf());
+    }
+
+    /// Keeps the expanded directories below `from` expanded after a move.
+    pub fn moved(&mut self, from: &Path, to: &Path) {
+        let moved = self
+            .expanded
+            .iter()
+            .filter(|path| path.starts_with(from))
+            .cloned()
+            .collect::<Vec<_>>();
+        for path in moved {
+            self.expanded.remove(&path);
+            let rest = path.strip_prefix(from).unwrap_or(&path);
+            self.expanded.insert(match rest.as_os_str().is_empty() {
+                true => to.to_path_buf(),
+                false => to.join(rest),
+            });
+        }
+        // a renamed entry stays selected
+        for path in self
+            .selection
+            .iter_mut()
+            .chain(&mut self.anchor)
+            .chain(&mut self.cursor)
+        {
+            if path == from {
+                *path = to.to_path_buf();
+            }
+        }
+        self.rows_dirty = true;
+    }
+
+    pub fn changes(&self) -> usize {
+        self.changes
+    }
+
+    /// Reads `dir` again in the next frame, e.g. when the watcher is not available.
+    pub fn refresh(&mut self, dir: &Path) {
+        self.dirty.insert(dir.to_path_buf());
+    }
+
+    /// The innermost root containing `path`.
+    fn root_of(&self, path: &Path) -> Option<PathBuf> {
+        self.roots
+            .iter()
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.components().count())
+            .cloned()
+    }
+
+    fn set_roots(&mut self, ctx: &Context, roots: &[PathBuf]) {
+        // new folders of a workspace start expanded
+        for root in roots {
+            if !self.roots.contains(root) && roots.len() > 1 {
+                self.expanded.insert(root.clone());
+            }
+        }
+        self.roots = roots.to_vec();
+        self.children.clear();
+        self.dirty.clear();
+        self.ignores.clear();
+        self.excludes.clear();
+        self.rows_dirty = true;
+
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let ctx = ctx.clone();
+        self.watcher = notify::recommended_watcher(move |event| {
+            sender.send(event).unwrap_or_default();
+            ctx.request_repaint();
+        })
+        .ok();
+        self.events = Some(receiver);
+    }
+
+    fn collect_rows(&mut self, dir: PathBuf, depth: usize, ignored: bool) {
+        if !self.children.contains_key(&dir) {
+            self.load(&dir);
+        }
+        let children = match self.children.get(&dir).cloned() {
+            Some(Ok(children)) => children,
+            Some(Err(err)) => {
+                self.rows.push(Row {
+                    path: dir,
+                    name: err,
+                    depth,
+                    kind: EntryKind::Error,
+                    link: None,
+                    ignored,
+                    size: None,
+                    modified: None,
+                    nests: false,
+                    root: false,
+                });
+                return;
+            }
+            None => return,
+        };
+        let filter = self.filter();
+        let mut visible = Vec::new();
+        for entry in children {
+            let is_dir = entry.kind == EntryKind::Directory;
+            // everything inside an ignored directory is ignored as well
+            let ignored = ignored || self.is_ignored(&entry.path, is_dir);
+            if (ignored && !filter.show_ignored)
+                || (entry.name.starts_with('.') && !filter.show_hidden)
+            {
+                continue;
+            }
+            visible.push((entry, ignored));
+        }
+        visible.sort_by(|(a, _), (b, _)| self.view.sort.compare(a, b));
+
+        // indices of the files nested below each file
+        let mut nested = vec![Vec::new(); visible.len()];
+        let mut is_nested = vec![false; visible.len()];
+        let rules = self
+            .view
+            .nesting_rules
+            .iter()
+            .filter_map(|rule| NestingRule::parse(rule))
+            .collect::<Vec<_>>();
+        if self.view.nesting {
+            for parent in 0..visible.len() {
+                if is_nested[parent] || visible[parent].0.kind == EntryKind::Directory {
+                    continue;
+                }
+                for child in 0..visible.len() {
+                    let (parent_name, child_entry) = (&visible[parent].0.name, &visible[child].0);
+                    if child != parent
+                        && !is_nested[child]
+                        && nested[child].is_empty()
+                        && child_entry.kind != EntryKind::Directory
+                        && rules
+                            .iter()
+                            .any(|rule| rule.nests(parent_name, &child_entry.name))
+                    {
+                        nested[parent].push(child);
+                        is_nested[child] = true;
+                    }
+                }
+            }
+        }
+
+        for (i, (entry, ignored)) in visible.iter().enumerate() {
+            if is_nested[i] {
+                continue;
+            }
+            let expanded = self.expanded.contains(&entry.path);
+            self.rows
+                .push(row(entry, depth, *ignored, !nested[i].is_empty()));
+            if expanded && entry.kind == EntryKind::Directory {
+                self.collect_rows(entry.path.clone(), depth + 1, *ignored);
+            } else if expanded {
+                for child in &nested[i] {
+                    let (entry, ignored) = &visible[*child];
+                    self.rows.push(row(entry, depth + 1, *ignored, false));
+                }
+            }
+        }
+    }
+
+    fn build_excludes(&mut self, root: &Path) {
+        // the exclude list wins over everything else
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in self.filter().exclude {
+            builder.add_line(None, &pattern).ok();
+        }
+        let mut excludes = vec![builder.build().unwrap_or(Gitignore::empty())];
+
+        let mut builder = GitignoreBuilder::new(root);
+        builder.add(root.join(".git").join("info").join("exclude"));
+        excludes.push(builder.build().unwrap_or(Gitignore::empty()));
+        excludes.push(Gitignore::global().0);
+        self.excludes.insert(root.to_path_buf(), excludes);
+    }
+
+    fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        if path.file_name().is_some_and(|name| name == ".git") {
+            return true;
+        }
+        let root = self.root_of(path).unwrap_or_default();
+        if let Some(exclude) = self
+            .excludes
+            .get(&root)
+            .and_then(|excludes| excludes.first())
+        {
+            if exclude.matched(path, is_dir).is_ignore() {
+                return true;
+            }
+        }
+        // the closest ignore file decides
+        for dir in path.ancestors().skip(1) {
+            if !dir.starts_with(&root) {
+                break;
+            }
+            let ignore = self.ignores.entry(dir.to_path_buf()).or_insert_with(|| {
+                let mut builder = GitignoreBuilder::new(dir);
+                builder.add(dir.join(".gitignore"));
+                builder.add(dir.join(".ignore"));
+                builder.build().unwrap_or(Gitignore::empty())
+            });
+            match ignore.matched(path, is_dir) {
+                m if m.is_ignore() => return true,
+                m if m.is_whitelist() => return false,
+                _ => {}
+            }
+        }
+        self.excludes.get(&root).is_some_and(|excludes| {
+            excludes
+                .iter()
+                .skip(1)
+                .any(|exclude| exclude.matched(path, is_dir).is_ignore())
+        })
+    }
+
+    fn load(&mut self, dir: &Path) {
+        if !self.children.contains_key(dir) {
+            if let Some(watcher) = &mut self.watcher {
+                watcher
+                    .watch(dir, RecursiveMode::NonRecursive)
+                    .unwrap_or_default();
+            }
+        }
+
+        let entries = fs::read_dir(dir).map(|read_dir| {
+            read_dir
+                .filter_map(Result::ok)
+                .map(|entry| {
+                    let path = entry.path();
+                    // of the target for symlinks
+                    let metadata = fs::metadata(&path).ok();
+                    let (kind, link) = match entry.file_type() {
+                        Ok(file_type) if file_type.is_symlink() => {
+                            let kind = match &metadata {
+                                Some(metadata) => kind_of(metadata.file_type()),
+                                None => EntryKind::Broken,
+                            };
+                            (kind, fs::read_link(&path).ok())
+                        }
+                        Ok(file_type) => (kind_of(file_type), None),
+                        Err(_) => (EntryKind::Special, None),
+                    };
+                    Entry {
+                        name: entry.file_name().to_string_lossy().to_string(),
+                        path,
+                        kind,
+                        link,
+                        size: metadata
+                            .as_ref()
+                            .filter(|metadata| metadata.is_file())
+                            .map(|metadata| metadata.len()),
+                        modified: metadata.and_then(|metadata| metadata.modified().ok()),
+                    }
+                })
+                .collect::<Vec<_>>()
+        });
+        self.children
+            .insert(dir.to_path_buf(), entries.map_err(|err| err.to_string()));
+    }
+}
+
+impl SortBy {
+    /// Directories first, then by `self`, ties by name.
+    fn compare(self, a: &Entry, b: &Entry) -> Ordering {
+        let a_is_dir = a.kind == EntryKind::Directory;
+        let b_is_dir = b.kind == EntryKind::Directory;
+        let extension = |entry: &Entry| {
+            Path::new(&entry.name)
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+        };
+        b_is_dir
+            .cmp(&a_is_dir)
+            .then_with(|| match self {
+                SortBy::Name => Ordering::Equal,
+                SortBy::Type => extension(a).cmp(&extension(b)),
+                SortBy::Modified => b.modified.cmp(&a.modified),
+                SortBy::Size => b.size.cmp(&a.size),
+            })
+            .then_with(|| natural_cmp(&a.name, &b.name))
+    }
+}
+
+fn row(entry: &Entry, depth: usize, ignored: bool, nests: bool) -> Row {
+    Row {
+        path: entry.path.clone(),
+        name: entry.name.clone(),
+        depth,
+        kind: entry.kind,
+        link: entry.link.clone(),
+        ignored,
+        size: entry.size,
+        modified: entry.modified,
+        nests,
+        root: false,
+    }
+}
+
+fn kind_of(file_type: fs::FileType) -> EntryKind {
+    if file_type.is_dir() {
+        EntryKind::Directory
+    } else if file_type.is_file() {
+        EntryKind::File
+    } else {
+        EntryKind::Special
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_naturally() {
+        let mut names = ["file10", "File2", "file1", "a01", "a1", "b", "A", "a"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            ["A", "a", "a01", "a1", "b", "file1", "File2", "file10"]
+        );
+        // leading zeros only break ties
+        assert_eq!(natural_cmp("a01", "a1"), Ordering::Less);
+        assert_eq!(natural_cmp("a01b", "a1c"), Ordering::Less);
+        assert_eq!(natural_cmp("a002", "a10"), Ordering::Less);
+        assert_eq!(natural_cmp("x", "x"), Ordering::Equal);
+    }
+
+    #[test]
+    fn captures_the_star() {
+        assert_eq!(capture("*.ts", "main.ts"), Some("main"));
+        assert_eq!(capture("*.ts", ".ts"), Some(""));
+        assert_eq!(capture("*.ts", "main.js"), None);
+        assert_eq!(capture("ab*ba", "aba"), None);
+        assert_eq!(capture("Cargo.toml", "Cargo.toml"), Some(""));
+        assert_eq!(capture("Cargo.toml", "Cargo.lock"), None);
+    }
+
+    #[test]
+    fn parses_nesting_rules() {
+        let rule = NestingRule::parse(" *.ts : $(capture).js, ,$(capture).d.ts ").unwrap();
+        assert_eq!(rule.parent, "*.ts");
+        assert_eq!(rule.children, ["$(capture).js", "$(capture).d.ts"]);
+        assert!(NestingRule::parse("*.ts").is_none());
+        assert!(NestingRule::parse(": a.js").is_none());
+        assert!(NestingRule::parse("*.ts: ,").is_none());
+    }
+
+    #[test]
+    fn nests_children() {
+        let rule = NestingRule::parse("*.ts: $(capture).js, $(capture).*.map").unwrap();
+        assert!(rule.nests("main.ts", "main.js"));
+        assert!(rule.nests("main.ts", "main.js.map"));
+        assert!(!rule.nests("main.ts", "other.js"));
+        assert!(!rule.nests("main.js", "main.js"));
+
+        // a parent never nests below itself
+        let rule = NestingRule::parse("*.js: *.js").unwrap();
+        assert!(rule.nests("a.js", "b.js"));
+        assert!(!rule.nests("a.js", "a.js"));
+    }
+}
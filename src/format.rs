@@ -0,0 +1,218 @@
+use std::{
+    io::{Read, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::Receiver;
+use eframe::egui::{text_selection::CCursorRange, Context};
+use similar::{DiffTag, TextDiff};
+
+use crate::settings::FormatSettings;
+
+/// The formatter for the extension of `path`.
+pub fn command_for<'a>(settings: &'a FormatSettings, path: &Path) -> Option<&'a str> {
+    let extension = path.extension()?.to_str()?;
+    settings.commands.get(extension).map(String::as_str)
+}
+
+/// A formatter running in the background.
+pub struct Job {
+    pub path: PathBuf,
+    /// The text of the file when the formatter started, the result is dropped if it changed.
+    pub text: String,
+    /// The formatted chars of `text`.
+    pub range: Range<usize>,
+    /// Save the file once the formatter is done.
+    pub save: bool,
+    receiver: Receiver<Result<String, String>>,
+}
+
+impl Job {
+    /// Starts piping the `range` of `text` through `command`.
+    pub fn start(
+        ctx: &Context,
+        command: &str,
+        path: PathBuf,
+        text: String,
+        range: Range<usize>,
+        timeout: Duration,
+        save: bool,
+    ) -> Self {
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        let input = text
+            .chars()
+            .skip(range.start)
+            .take(range.len())
+            .collect::<String>();
+        let (command, file, ctx) = (command.to_string(), path.clone(), ctx.clone());
+        thread::spawn(move || {
+            sender
+                .send(run(&command, &file, &input, timeout))
+                .unwrap_or_default();
+            ctx.request_repaint();
+        });
+        Self {
+            path,
+            text,
+            range,
+            save,
+            receiver,
+        }
+    }
+
+    /// The formatted text of the whole file once the formatter is done.
+    pub fn poll(&self) -> Option<Result<String, String>> {
+        let output = match self.receiver.try_recv() {
+            Ok(output) => output,
+            Err(crossbeam_channel::TryRecvError::Empty) => return None,
+            Err(err) => Err(err.to_string()),
+        };
+        Some(output.map(|output| {
+            let chars = self.text.chars().collect::<Vec<_>>();
+            let mut text = chars[..self.range.start].iter().collect::<String>();
+            text += &output;
+            text.extend(&chars[self.range.end..]);
+            text
+        }))
+    }
+}
+
+/// Pipes `input` through `command`, `{path}` is replaced by the file being formatted.
+pub fn run(command: &str, path: &Path, input: &str, timeout: Duration) -> Result<String, String> {
+    let mut args = split_command(command)?
+        .into_iter()
+        .map(|arg| arg.replace("{path}", &path.to_string_lossy()));
+    let program = args.next().ok_or("The formatter command is empty")?;
+    let mut child = Command::new(&program)
+        .args(args)
+        .current_dir(path.parent().unwrap_or(Path::new(".")))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("{program}: {err}"))?;
+
+    // separate threads, so full pipes do not block the formatter
+    let mut stdin = child.stdin.take();
+    let input = input.to_string();
+    thread::spawn(move || {
+        stdin
+            .as_mut()
+            .map(|stdin| stdin.write_all(input.as_bytes()))
+    });
+    let read = |pipe: Option<Box<dyn Read + Send>>| {
+        thread::spawn(move || {
+            let mut output = String::new();
+            if let Some(mut pipe) = pipe {
+                pipe.read_to_string(&mut output).unwrap_or_default();
+            }
+            output
+        })
+    };
+    let stdout = read(child.stdout.take().map(|p| Box::new(p) as _));
+    let stderr = read(child.stderr.take().map(|p| Box::new(p) as _));
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|err| err.to_string())? {
+            break status;
+        }
+        if start.elapsed() > timeout {
+            child.kill().unwrap_or_default();
+            child.wait().unwrap_or_default();
+            return Err(format!(
+                "{program} timed out after {:.1}s",
+                timeout.as_secs_f32()
+            ));
+        }
+        thread::sleep(Duration::from_millis(5));
+    };
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    if !status.success() {
+        let message = stderr.trim();
+        return Err(match message.is_empty() {
+            true => format!("{program} failed with {status}"),
+            false => format!("{program}: {message}"),
+        });
+    }
+    Ok(stdout)
+}
+
+/// Splits `command` into its arguments like a shell, with `'single'` and `"double"` quotes.
+/// A backslash escapes quotes, whitespace and itself, otherwise it is kept for Windows paths.
+pub fn split_command(command: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut arg = None::<String>;
+    let mut quote = None;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some('"') | None, '\\') => {
+                let escaped = chars
+                    .next_if(|next| matches!(next, '\'' | '"' | '\\') || next.is_whitespace())
+                    .unwrap_or(c);
+                arg.get_or_insert_with(String::new).push(escaped);
+            }
+            (Some(_), c) => arg.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                arg.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => args.extend(arg.take()),
+            (None, c) => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err("The formatter command has an unclosed quote".to_string());
+    }
+    args.extend(arg);
+    Ok(args)
+}
+
+/// Changes `text` into `new` by replacing only the lines which differ, `cursor` moves along.
+pub fn apply(text: &mut String, new: &str, cursor: &mut CCursorRange) {
+    let old = text.clone();
+    let diff = TextDiff::from_lines(old.as_str(), new);
+    let old_lines = diff.old_slices();
+    let new_lines = diff.new_slices();
+    let char_offset = |lines: &[&str], line: usize| -> usize {
+        lines[..line].iter().map(|line| line.chars().count()).sum()
+    };
+
+    // from the end, so the offsets of earlier changes stay valid
+    let mut ops = diff.ops().to_vec();
+    ops.reverse();
+    let mut primary = cursor.primary.index;
+    let mut secondary = cursor.secondary.index;
+    for op in ops {
+        if op.tag() == DiffTag::Equal {
+            continue;
+        }
+        let start = char_offset(old_lines, op.old_range().start);
+        let end = char_offset(old_lines, op.old_range().end);
+        let replacement = new_lines[op.new_range()].concat();
+        let byte_start = text
+            .char_indices()
+            .nth(start)
+            .map_or(text.len(), |(i, _)| i);
+        let byte_end = text.char_indices().nth(end).map_or(text.len(), |(i, _)| i);
+        text.replace_range(byte_start..byte_end, &replacement);
+
+        let inserted = replacement.chars().count();
+        for index in [&mut primary, &mut secondary] {
+            if *index >= end {
+                *index = *index + inserted - (end - start);
+            } else if *index > start {
+                *index = start + (*index - start).min(inserted);
+            }
+        }
+    }
+    cursor.primary.index = primary;
+    cursor.secondary.index = secondary;
+}
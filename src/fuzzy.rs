@@ -0,0 +1,79 @@
+/// A fuzzy match of a query against some text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub score: i32,
+    /// Char indices of the matched characters in the text.
+    pub indices: Vec<usize>,
+}
+
+/// Matches the characters of `query` in order against `text`, ignoring case and whitespace in the query.
+/// Consecutive characters and characters at word starts score higher, gaps cost a bit.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<Match> {
+    let query = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect::<Vec<_>>();
+    let chars = text.chars().collect::<Vec<_>>();
+
+    let mut score = 0;
+    let mut indices = Vec::with_capacity(query.len());
+    let mut next = 0;
+    for q in query {
+        let index = (next..chars.len()).find(|&i| chars[i].to_lowercase().eq(Some(q)))?;
+        score += 1;
+        if index == 0 {
+            score += 8;
+        } else if indices.last() == Some(&(index - 1)) {
+            score += 5;
+        } else if is_word_start(&chars, index) {
+            score += 4;
+            score -= (index - next).min(3) as i32;
+        } else {
+            score -= (index - next).min(5) as i32;
+        }
+        indices.push(index);
+        next = index + 1;
+    }
+    // prefer shorter texts
+    score -= (chars.len() / 16) as i32;
+    Some(Match { score, indices })
+}
+
+fn is_word_start(chars: &[char], index: usize) -> bool {
+    let prev = chars[index - 1];
+    let current = chars[index];
+    !prev.is_alphanumeric() || (prev.is_lowercase() && current.is_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indices(query: &str, text: &str) -> Option<Vec<usize>> {
+        fuzzy_match(query, text).map(|found| found.indices)
+    }
+
+    #[test]
+    fn matches_in_order() {
+        assert_eq!(indices("mrs", "main.rs"), Some(vec![0, 5, 6]));
+        assert_eq!(indices("MAIN", "main.rs"), Some(vec![0, 1, 2, 3]));
+        assert_eq!(indices("m rs", "main.rs"), Some(vec![0, 5, 6]));
+        assert_eq!(indices("", "main.rs"), Some(vec![]));
+        assert_eq!(indices("sm", "main.rs"), None);
+        assert_eq!(indices("x", "main.rs"), None);
+        assert_eq!(indices("ü", "Über"), Some(vec![0]));
+    }
+
+    #[test]
+    fn scores_word_starts_and_runs() {
+        let score = |query, text| fuzzy_match(query, text).unwrap().score;
+        // consecutive characters beat scattered ones
+        assert!(score("tree", "file_tree.rs") > score("tree", "the_rebase.rs"));
+        // word starts beat characters inside words
+        assert!(score("ft", "file_tree.rs") > score("ft", "fixture.rs"));
+        assert!(score("ft", "FileTree.rs") > score("ft", "fixture.rs"));
+        // shorter texts win ties
+        assert!(score("ab", "ab") > score("ab", "ab_and_some_long_suffix.rs"));
+    }
+}
@@ -0,0 +1,163 @@
+use serde::{Deserialize, Serialize};
+
+/// What one level of indentation is made of.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Indent {
+    #[default]
+    Tabs,
+    Spaces(usize),
+}
+
+impl Indent {
+    /// The text inserted for one level.
+    pub fn unit(self) -> String {
+        match self {
+            Indent::Tabs => "\t".to_string(),
+            Indent::Spaces(width) => " ".repeat(width),
+        }
+    }
+
+    /// Columns of one level, tabs count as four.
+    pub fn width(self) -> usize {
+        match self {
+            Indent::Tabs => 4,
+            Indent::Spaces(width) => width,
+        }
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            Indent::Tabs => "Tabs".to_string(),
+            Indent::Spaces(width) => format!("Spaces: {width}"),
+        }
+    }
+
+    /// How many of the leading `chars` of a line one outdent removes.
+    pub fn outdent_width(self, chars: impl IntoIterator<Item = char>) -> usize {
+        let width = self.width();
+        let mut chars = chars.into_iter().peekable();
+        if chars.peek() == Some(&'\t') {
+            return 1;
+        }
+        chars.take(width).take_while(|c| *c == ' ').count()
+    }
+}
+
+/// The indentation most lines of `text` use, `None` if no line is indented.
+pub fn detect(text: &str) -> Option<Indent> {
+    let mut tab_lines = 0;
+    let mut space_lines = 0;
+    // how often the indentation grows by each width
+    let mut steps = [0; 9];
+    let mut previous = 0;
+    for line in text.lines() {
+        let content = line.trim_start_matches([' ', '\t']);
+        if content.trim().is_empty() {
+            continue;
+        }
+        let leading = &line[..line.len() - content.len()];
+        if leading.starts_with('\t') {
+            tab_lines += 1;
+            continue;
+        }
+        if leading.contains('\t') {
+            continue;
+        }
+        let width = leading.len();
+        if width > 0 {
+            space_lines += 1;
+        }
+        // continuation lines of block comments are off by one
+        if content.starts_with('*') {
+            continue;
+        }
+        if width > previous && width - previous < steps.len() {
+            steps[width - previous] += 1;
+        }
+        previous = width;
+    }
+
+    if tab_lines == 0 && space_lines == 0 {
+        return None;
+    }
+    if tab_lines >= space_lines {
+        return Some(Indent::Tabs);
+    }
+    let width = (1..steps.len())
+        .filter(|width| steps[*width] > 0)
+        .max_by_key(|width| (steps[*width], *width))
+        .unwrap_or(4);
+    Some(Indent::Spaces(width))
+}
+
+/// Rewrites the leading whitespace of every line with `to`, a tab spans `tab_width` columns.
+pub fn convert(text: &str, tab_width: usize, to: Indent) -> String {
+    let tab_width = tab_width.max(1);
+    text.split('\n')
+        .map(|line| {
+            let content = line.trim_start_matches([' ', '\t']);
+            let columns = line[..line.len() - content.len()]
+                .chars()
+                .fold(0, |column, c| match c {
+                    '\t' => (column / tab_width + 1) * tab_width,
+                    _ => column + 1,
+                });
+            let leading = match to {
+                Indent::Spaces(_) => " ".repeat(columns),
+                Indent::Tabs => "\t".repeat(columns / tab_width) + &" ".repeat(columns % tab_width),
+            };
+            leading + content
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_spaces() {
+        assert_eq!(detect("a\n  b\n    c\n  d\n"), Some(Indent::Spaces(2)));
+        assert_eq!(
+            detect("a {\n    b\n}\nc {\n    d {\n        e\n"),
+            Some(Indent::Spaces(4))
+        );
+        // block comments do not count as a width of one
+        assert_eq!(
+            detect("/**\n * x\n */\nfn a() {\n  b\n}"),
+            Some(Indent::Spaces(2))
+        );
+        assert_eq!(detect("a\nb\n\n   \n"), None);
+        assert_eq!(detect(""), None);
+    }
+
+    #[test]
+    fn detects_mixed_indentation() {
+        // most lines win
+        assert_eq!(detect("a\n\tb\n\t\tc\n    d"), Some(Indent::Tabs));
+        assert_eq!(detect("a\n\tb\n  c\n    d\n  e"), Some(Indent::Spaces(2)));
+        // a tie goes to tabs
+        assert_eq!(detect("a\n\tb\n  c"), Some(Indent::Tabs));
+        // spaces before a tab are not counted
+        assert_eq!(detect("a\n  \tb\n\tc"), Some(Indent::Tabs));
+    }
+
+    #[test]
+    fn converts_indentation() {
+        assert_eq!(
+            convert("a\n\tb\n\t  c\n", 4, Indent::Spaces(4)),
+            "a\n    b\n      c\n"
+        );
+        assert_eq!(convert("  \tb", 4, Indent::Spaces(4)), "    b");
+        assert_eq!(convert("      c\n  d", 4, Indent::Tabs), "\t  c\n  d");
+        assert_eq!(convert("\t x", 0, Indent::Tabs), "\t\tx");
+    }
+
+    #[test]
+    fn outdents() {
+        assert_eq!(Indent::Spaces(4).outdent_width("      a".chars()), 4);
+        assert_eq!(Indent::Spaces(4).outdent_width("  a".chars()), 2);
+        assert_eq!(Indent::Spaces(4).outdent_width("\t\ta".chars()), 1);
+    }
+}
@@ -0,0 +1,341 @@
+use std::path::PathBuf;
+
+pub const SOCKET_VAR: &str = "ETA_SOCKET";
+
+/// The socket of the running instance of the graphical session, terminals of eta get it through
+/// `ETA_SOCKET`.
+pub fn socket_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(SOCKET_VAR) {
+        return path.into();
+    }
+    // one instance per display, e.g. `eta-wayland-0.sock` or `eta-0.sock` for `:0`
+    let display = std::env::var("WAYLAND_DISPLAY")
+        .or_else(|_| std::env::var("DISPLAY"))
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>();
+    let display = display.trim_matches('-');
+    let name = match display {
+        "" => "eta".to_string(),
+        display => format!("eta-{display}"),
+    };
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join(format!("{name}.sock")),
+        None => {
+            let user = std::env::var("USER").unwrap_or_default();
+            std::env::temp_dir().join(format!("{name}-{user}.sock"))
+        }
+    }
+}
+
+#[cfg(unix)]
+pub use unix::{forward, Server};
+
+#[cfg(unix)]
+mod unix {
+    use std::{
+        collections::HashMap,
+        ffi::OsString,
+        fs,
+        io::{self, BufRead, BufReader, Write},
+        os::unix::{
+            ffi::{OsStrExt, OsStringExt},
+            net::{UnixListener, UnixStream},
+        },
+        path::{Path, PathBuf},
+        thread,
+        time::Duration,
+    };
+
+    use crossbeam_channel::Receiver;
+    use eframe::egui::Context;
+
+    use super::socket_path;
+
+    /// Clients which do not finish their request in time are dropped.
+    const READ_TIMEOUT: Duration = Duration::from_secs(5);
+    use crate::{
+        cli::{Args, FilePosition},
+        code_editor::FileData,
+    };
+
+    /// One line per item followed by an empty line: `folder\t<path>`,
+    /// `file\t<path>[\t<line>\t<column>]`, `diff\t<a>\t<b>` or `wait`, paths are [`escape`]d.
+    fn encode(args: &Args) -> String {
+        let mut request = String::new();
+        for folder in &args.folders {
+            request += &format!("folder\t{}\n", escape(folder));
+        }
+        for file in &args.files {
+            request += &format!("file\t{}", escape(&file.path));
+            if let Some((line, column)) = file.position {
+                request += &format!("\t{line}\t{column}");
+            }
+            request += "\n";
+        }
+        if let Some((left, right)) = &args.diff {
+            request += &format!("diff\t{}\t{}\n", escape(left), escape(right));
+        }
+        if args.wait {
+            request += "wait\n";
+        }
+        request + "\n"
+    }
+
+    fn decode(lines: &[String]) -> Args {
+        let mut args = Args::default();
+        for line in lines {
+            let parts = line.split('\t').collect::<Vec<_>>();
+            match parts.as_slice() {
+                ["folder", path] => args.folders.push(unescape(path)),
+                ["file", path] => args.files.push(FilePosition {
+                    path: unescape(path),
+                    position: None,
+                }),
+                ["file", path, line, column] => args.files.push(FilePosition {
+                    path: unescape(path),
+                    position: line.parse().ok().zip(column.parse().ok()),
+                }),
+                ["diff", left, right] => args.diff = Some((unescape(left), unescape(right))),
+                ["wait"] => args.wait = true,
+                _ => {}
+            }
+        }
+        args
+    }
+
+    /// Escapes the separators of the protocol and the bytes of `path` which are not UTF-8, so
+    /// every path survives the request as it is.
+    fn escape(path: &Path) -> String {
+        let mut escaped = String::new();
+        for chunk in path.as_os_str().as_bytes().utf8_chunks() {
+            for c in chunk.valid().chars() {
+                match c {
+                    '\\' => escaped += "\\\\",
+                    '\t' => escaped += "\\t",
+                    '\n' => escaped += "\\n",
+                    '\r' => escaped += "\\r",
+                    c => escaped.push(c),
+                }
+            }
+            for byte in chunk.invalid() {
+                escaped += &format!("\\x{byte:02x}");
+            }
+        }
+        escaped
+    }
+
+    fn unescape(escaped: &str) -> PathBuf {
+        let mut bytes = Vec::new();
+        let mut rest = escaped.as_bytes();
+        while let [first, tail @ ..] = rest {
+            rest = tail;
+            if *first != b'\\' {
+                bytes.push(*first);
+                continue;
+            }
+            let hex =
+                |digits: &[u8]| u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok();
+            // the byte and the length of its escape after the backslash
+            let (byte, len) = match rest {
+                [b't', ..] => (b'\t', 1),
+                [b'n', ..] => (b'\n', 1),
+                [b'r', ..] => (b'\r', 1),
+                [b'\\', ..] => (b'\\', 1),
+                [b'x', digits @ ..] => match digits.get(..2).and_then(hex) {
+                    Some(byte) => (byte, 3),
+                    None => (b'\\', 0),
+                },
+                _ => (b'\\', 0),
+            };
+            bytes.push(byte);
+            rest = &rest[len..];
+        }
+        OsString::from_vec(bytes).into()
+    }
+
+    /// Hands `args` to a running instance, blocking until its files are closed with `--wait`.
+    /// Returns false if there is no instance to take them.
+    pub fn forward(args: &Args) -> bool {
+        let Ok(mut stream) = UnixStream::connect(socket_path()) else {
+            return false;
+        };
+        if stream.write_all(encode(args).as_bytes()).is_err() {
+            return false;
+        }
+        // any answer or a closed connection means the request was taken
+        let mut answer = String::new();
+        BufReader::new(stream)
+            .read_line(&mut answer)
+            .unwrap_or_default();
+        true
+    }
+
+    /// Receives the arguments of later launches, answering `ok`, or `closed` once the files of
+    /// a `--wait` request are closed.
+    pub struct Server {
+        path: PathBuf,
+        requests: Receiver<(Args, UnixStream)>,
+        /// Clients started with `--wait` and their files.
+        waiting: Vec<(Vec<PathBuf>, UnixStream)>,
+    }
+
+    impl Server {
+        /// Listens for other launches, `None` if another instance already does.
+        pub fn start(ctx: &Context) -> Option<Self> {
+            let path = socket_path();
+            let listener = match UnixListener::bind(&path) {
+                Ok(listener) => listener,
+                Err(err) if err.kind() == io::ErrorKind::AddrInUse => {
+                    if UnixStream::connect(&path).is_ok() {
+                        return None;
+                    }
+                    // left behind by an instance that crashed
+                    fs::remove_file(&path).ok()?;
+                    UnixListener::bind(&path).ok()?
+                }
+                Err(_) => return None,
+            };
+
+            let (sender, requests) = crossbeam_channel::unbounded();
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    // a slow client does not hold up the next ones
+                    let sender = sender.clone();
+                    let ctx = ctx.clone();
+                    thread::spawn(move || {
+                        stream.set_read_timeout(Some(READ_TIMEOUT)).ok()?;
+                        let lines = read_request(&stream)?;
+                        stream.set_read_timeout(None).ok()?;
+                        sender.send((decode(&lines), stream)).ok()?;
+                        ctx.request_repaint();
+                        Some(())
+                    });
+                }
+            });
+            Some(Self {
+                path,
+                requests,
+                waiting: Vec::new(),
+            })
+        }
+
+        /// The requests received since the last call.
+        pub fn take_requests(&mut self) -> Vec<Args> {
+            let mut requests = Vec::new();
+            for (args, mut stream) in self.requests.try_iter() {
+                match args.wait_for() {
+                    files if files.is_empty() => stream.write_all(b"ok\n").unwrap_or_default(),
+                    files => self.waiting.push((files, stream)),
+                }
+                requests.push(args);
+            }
+            requests
+        }
+
+        /// Lets clients waiting for files that are no longer open exit.
+        pub fn release_closed(&mut self, files: &HashMap<PathBuf, FileData>) {
+            self.waiting.retain_mut(|(waiting_for, stream)| {
+                if waiting_for.iter().any(|file| files.contains_key(file)) {
+                    return true;
+                }
+                stream.write_all(b"closed\n").unwrap_or_default();
+                false
+            });
+        }
+    }
+
+    impl Drop for Server {
+        fn drop(&mut self) {
+            fs::remove_file(&self.path).unwrap_or_default();
+        }
+    }
+
+    /// The lines up to the empty one, `None` if the connection ends before.
+    fn read_request(stream: &UnixStream) -> Option<Vec<String>> {
+        let mut lines = Vec::new();
+        for line in BufReader::new(stream).lines() {
+            let line = line.ok()?;
+            if line.is_empty() {
+                return Some(lines);
+            }
+            lines.push(line);
+        }
+        None
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn escapes_paths() {
+            for path in [
+                "/tmp/a b/main.rs",
+                "/tmp/tab\there",
+                "/tmp/line\nbreak\r",
+                "C:\\Users\\x41",
+                "/tmp/\\t",
+            ] {
+                assert_eq!(unescape(&escape(Path::new(path))), PathBuf::from(path));
+            }
+            let invalid = PathBuf::from(OsString::from_vec(b"/tmp/\xff\xfe.rs".to_vec()));
+            assert_eq!(escape(&invalid), "/tmp/\\xff\\xfe.rs");
+            assert_eq!(unescape(&escape(&invalid)), invalid);
+            assert_eq!(unescape("a\\x4"), PathBuf::from("a\\x4"));
+            assert_eq!(unescape("a\\"), PathBuf::from("a\\"));
+        }
+
+        #[test]
+        fn encodes_requests() {
+            let args = Args {
+                folders: vec!["/tmp/new\nline".into()],
+                files: vec![FilePosition {
+                    path: "/tmp/a\tb.rs".into(),
+                    position: Some((4, 2)),
+                }],
+                diff: Some(("/tmp/l".into(), "/tmp/r".into())),
+                new_window: false,
+                wait: true,
+            };
+            let request = encode(&args);
+            let lines = request.lines().map(str::to_string).collect::<Vec<_>>();
+            assert_eq!(lines.len(), 5);
+            assert_eq!(lines[4], "");
+            assert_eq!(decode(&lines[..4]), args);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub use fallback::{forward, Server};
+
+/// Every launch starts its own instance.
+#[cfg(not(unix))]
+mod fallback {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use eframe::egui::Context;
+
+    use crate::{cli::Args, code_editor::FileData};
+
+    pub fn forward(_args: &Args) -> bool {
+        false
+    }
+
+    pub struct Server;
+
+    impl Server {
+        pub fn start(_ctx: &Context) -> Option<Self> {
+            None
+        }
+
+        pub fn take_requests(&mut self) -> Vec<Args> {
+            Vec::new()
+        }
+
+        pub fn release_closed(&mut self, _files: &HashMap<PathBuf, FileData>) {}
+    }
+}
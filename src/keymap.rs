@@ -0,0 +1,667 @@
+use std::{fs, path::PathBuf};
+
+use eframe::egui::{Context, Event, InputState, Key, KeyboardShortcut, Modifiers};
+use serde::Deserialize;
+
+use crate::actions::Action;
+
+/// Written to the keymap file when it is opened for the first time.
+pub const TEMPLATE: &str = r#"# eta keymap
+#
+# preset = "default" | "emacs"
+# when   = "global" | "editor" | "terminal" (editor actions are always "editor")
+#
+# A chord wins over a single key binding with the same first key.
+#
+# preset = "emacs"
+#
+# # toggles the terminal while the editor has focus
+# [[bindings]]
+# keys = "Ctrl+Q Ctrl+T"
+# action = "toggle_terminal"
+# when = "editor"
+"#;
+
+/// Where a binding is active.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum When {
+    Global,
+    Editor,
+    Terminal,
+}
+
+/// The widget which had keyboard focus in the last frame.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Focus {
+    #[default]
+    None,
+    Editor,
+    Terminal,
+    Tree,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    #[default]
+    Default,
+    Emacs,
+}
+
+#[derive(Clone, Debug)]
+pub struct Binding {
+    pub chord: Vec<KeyboardShortcut>,
+    pub action: Action,
+    pub when: When,
+}
+
+pub enum Resolution {
+    /// The event is not bound.
+    None,
+    /// The event is part of an unfinished chord and was swallowed.
+    Pending,
+    Action(Action),
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct KeymapFile {
+    preset: Preset,
+    bindings: Vec<BindingEntry>,
+}
+
+#[derive(Deserialize)]
+struct BindingEntry {
+    keys: String,
+    action: Action,
+    when: Option<When>,
+}
+
+const DEFAULT_BINDINGS: &[(&str, Action)] = &[
+    ("Cmd+Shift+P", Action::CommandPalette),
+    ("Cmd+S", Action::SaveFile),
+    ("Cmd+O", Action::OpenProject),
+    ("Cmd+P", Action::QuickOpen),
+    ("Ctrl+G", Action::GoToLine),
+    ("Cmd+Shift+O", Action::GoToSymbol),
+    ("Ctrl+Backtick", Action::ToggleTerminal),
+    ("Shift+Alt+F", Action::FormatDocument),
+    ("Cmd+Equals", Action::ZoomIn),
+    ("Cmd+Plus", Action::ZoomIn),
+    ("Cmd+Minus", Action::ZoomOut),
+    ("Cmd+0", Action::ResetZoom),
+    ("Cmd+Z", Action::Undo),
+    ("Cmd+Y", Action::Redo),
+    ("Cmd+Shift+Z", Action::Redo),
+    ("Tab", Action::Indent),
+    ("Shift+Tab", Action::Outdent),
+    ("Ctrl+Space", Action::TriggerCompletion),
+    ("Enter", Action::NewLine),
+    ("Shift+Enter", Action::NewLine),
+    ("Backspace", Action::DeleteCharBackward),
+    ("Shift+Backspace", Action::DeleteCharBackward),
+    ("Ctrl+H", Action::DeleteCharBackward),
+    ("Ctrl+Backspace", Action::DeleteWordBackward),
+    ("Alt+Backspace", Action::DeleteWordBackward),
+    ("Ctrl+W", Action::DeleteWordBackward),
+    ("MacCmd+Backspace", Action::DeleteToLineStart),
+    ("Ctrl+U", Action::DeleteToLineStart),
+    ("Delete", Action::DeleteCharForward),
+    ("Ctrl+Delete", Action::DeleteWordForward),
+    ("Alt+Delete", Action::DeleteWordForward),
+    ("MacCmd+Delete", Action::DeleteToLineEnd),
+    ("Ctrl+K", Action::DeleteToLineEnd),
+];
+
+/// Shift+Delete cuts on Windows.
+const NON_WINDOWS_BINDINGS: &[(&str, Action)] = &[("Shift+Delete", Action::DeleteCharForward)];
+
+const EMACS_BINDINGS: &[(&str, Action)] = &[
+    ("Ctrl+X Ctrl+S", Action::SaveFile),
+    ("Ctrl+X Ctrl+F", Action::OpenProject),
+    ("Ctrl+X K", Action::CloseFile),
+    ("Ctrl+X Ctrl+C", Action::Quit),
+    ("Ctrl+Slash", Action::Undo),
+    ("Ctrl+F", Action::CursorRight),
+    ("Ctrl+B", Action::CursorLeft),
+    ("Ctrl+N", Action::CursorDown),
+    ("Ctrl+P", Action::CursorUp),
+    ("Alt+F", Action::WordRight),
+    ("Alt+B", Action::WordLeft),
+    ("Ctrl+A", Action::LineStart),
+    ("Ctrl+E", Action::LineEnd),
+    ("Ctrl+Home", Action::DocumentStart),
+    ("Ctrl+End", Action::DocumentEnd),
+    ("Ctrl+D", Action::DeleteCharForward),
+    ("Alt+D", Action::DeleteWordForward),
+    ("Ctrl+J", Action::NewLine),
+];
+
+/// Maps key chords to actions.
+pub struct Keymap {
+    bindings: Vec<Binding>,
+    /// Parse errors and conflicting bindings.
+    pub problems: Vec<String>,
+    app_pending: Vec<KeyboardShortcut>,
+    editor_pending: Vec<KeyboardShortcut>,
+    swallow_text: bool,
+    /// Editor actions which were not triggered by a key, e.g. from the command palette.
+    queued: Vec<Action>,
+    /// App actions bound in the editor.
+    app_queued: Vec<Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::from_preset(Preset::Default)
+    }
+}
+
+impl Keymap {
+    pub fn path() -> Option<PathBuf> {
+        eframe::storage_dir("eta").map(|dir| dir.join("keymap.toml"))
+    }
+
+    /// Loads the keymap file, falling back to the default preset.
+    pub fn load() -> Self {
+        match Self::path().and_then(|path| fs::read_to_string(path).ok()) {
+            Some(content) => Self::parse(&content),
+            None => Self::default(),
+        }
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let file = match toml::from_str::<KeymapFile>(content) {
+            Ok(file) => file,
+            Err(err) => {
+                let mut keymap = Self::default();
+                keymap
+                    .problems
+                    .push(format!("keymap.toml: {}", err.message()));
+                return keymap;
+            }
+        };
+
+        let mut keymap = Self::from_preset(file.preset);
+        for entry in file.bindings {
+            let chord = match parse_chord(&entry.keys) {
+                Ok(chord) => chord,
+                Err(err) => {
+                    keymap.problems.push(err);
+                    continue;
+                }
+            };
+            let when = entry.when.unwrap_or(default_when(entry.action));
+            if entry.action.is_editor() && when != When::Editor {
+                keymap.problems.push(format!(
+                    "{}: \"{}\" only works in the editor",
+                    entry.keys,
+                    entry.action.title()
+                ));
+                continue;
+            }
+            keymap.bind(chord, entry.action, when);
+        }
+        keymap.problems.extend(keymap.conflicts());
+        keymap
+    }
+
+    pub fn from_preset(preset: Preset) -> Self {
+        let mut keymap = Self {
+            bindings: Vec::new(),
+            problems: Vec::new(),
+            app_pending: Vec::new(),
+            editor_pending: Vec::new(),
+            swallow_text: false,
+            queued: Vec::new(),
+            app_queued: Vec::new(),
+        };
+        let preset_bindings = match preset {
+            Preset::Default => &[],
+            Preset::Emacs => EMACS_BINDINGS,
+        };
+        let platform_bindings = match cfg!(target_os = "windows") {
+            true => &[],
+            false => NON_WINDOWS_BINDINGS,
+        };
+        for (keys, action) in DEFAULT_BINDINGS.iter().chain(platform_bindings) {
+            let chord = parse_chord(keys).expect("built-in bindings should be valid");
+            keymap.bind(chord, *action, default_when(*action));
+        }
+        // presets win over the defaults in every context
+        for (keys, action) in preset_bindings {
+            let chord = parse_chord(keys).expect("built-in bindings should be valid");
+            keymap
+                .bindings
+                .retain(|binding| !same_chord(&binding.chord, &chord));
+            keymap.bind(chord, *action, default_when(*action));
+        }
+        keymap
+    }
+
+    /// Adds a binding, replacing one with the same chord and context.
+    pub fn bind(&mut self, chord: Vec<KeyboardShortcut>, action: Action, when: When) {
+        self.bindings
+            .retain(|binding| !same_chord(&binding.chord, &chord) || binding.when != when);
+        self.bindings.push(Binding {
+            chord,
+            action,
+            when,
+        });
+    }
+
+    /// Runs an editor action in the next frame.
+    pub fn queue(&mut self, action: Action) {
+        self.queued.push(action);
+    }
+
+    pub fn take_queued(&mut self) -> Vec<Action> {
+        std::mem::take(&mut self.queued)
+    }
+
+    /// Runs an app action resolved by the editor after the editor.
+    pub fn queue_app(&mut self, action: Action) {
+        self.app_queued.push(action);
+    }
+
+    pub fn take_app_queued(&mut self) -> Vec<Action> {
+        std::mem::take(&mut self.app_queued)
+    }
+
+    pub fn bindings(&self) -> &[Binding] {
+        &self.bindings
+    }
+
+    /// The first chord bound to `action`, formatted for the current platform.
+    pub fn shortcut_text(&self, ctx: &Context, action: Action) -> Option<String> {
+        let binding = self.bindings.iter().find(|b| b.action == action)?;
+        Some(
+            binding
+                .chord
+                .iter()
+                .map(|shortcut| ctx.format_shortcut(shortcut))
+                .collect::<Vec<_>>()
+                .join(" "),
+        )
+    }
+
+    /// Bindings which shadow each other.
+    pub fn conflicts(&self) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        for (i, a) in self.bindings.iter().enumerate() {
+            for b in &self.bindings[i + 1..] {
+                let overlapping =
+                    a.when == b.when || a.when == When::Global || b.when == When::Global;
+                let len = a.chord.len().min(b.chord.len());
+                if !overlapping || !same_chord(&a.chord[..len], &b.chord[..len]) {
+                    continue;
+                }
+                if a.chord.len() == b.chord.len() {
+                    if a.action != b.action {
+                        conflicts.push(format!(
+                            "{} is bound to both \"{}\" and \"{}\"",
+                            chord_text(&a.chord),
+                            a.action.title(),
+                            b.action.title()
+                        ));
+                    }
+                } else {
+                    // global bindings are resolved first, otherwise the longer chord wins
+                    let (winner, loser) = if a.when != b.when && a.when == When::Global {
+                        (a, b)
+                    } else if a.when != b.when && b.when == When::Global {
+                        (b, a)
+                    } else if a.chord.len() > b.chord.len() {
+                        (a, b)
+                    } else {
+                        (b, a)
+                    };
+                    conflicts.push(format!(
+                        "{} (\"{}\") shadows {} (\"{}\")",
+                        chord_text(&winner.chord),
+                        winner.action.title(),
+                        chord_text(&loser.chord),
+                        loser.action.title()
+                    ));
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Consumes the key events of global and terminal bindings.
+    pub fn dispatch(&mut self, input: &mut InputState, focus: Focus) -> Vec<Action> {
+        let mut actions = Vec::new();
+        input
+            .events
+            .retain(|event| match self.resolve(event, false, focus) {
+                Resolution::None => true,
+                Resolution::Pending => false,
+                Resolution::Action(action) => {
+                    actions.push(action);
+                    false
+                }
+            });
+        actions
+    }
+
+    /// Resolves an event against the editor bindings.
+    pub fn resolve_editor(&mut self, event: &Event) -> Resolution {
+        self.resolve(event, true, Focus::Editor)
+    }
+
+    fn resolve(&mut self, event: &Event, editor: bool, focus: Focus) -> Resolution {
+        // clipboard shortcuts only reach us as their own events
+        let clipboard = Modifiers::CTRL | Modifiers::COMMAND;
+        let shortcut = match event {
+            Event::Key {
+                key,
+                modifiers,
+                pressed: true,
+                ..
+            } => KeyboardShortcut::new(*modifiers, *key),
+            Event::Copy => KeyboardShortcut::new(clipboard, Key::C),
+            Event::Cut => KeyboardShortcut::new(clipboard, Key::X),
+            Event::Paste(_) => KeyboardShortcut::new(clipboard, Key::V),
+            Event::Text(_) if self.swallow_text => {
+                self.swallow_text = false;
+                return Resolution::Pending;
+            }
+            _ => return Resolution::None,
+        };
+        self.swallow_text = false;
+
+        let active = |when: When| {
+            if editor {
+                when == When::Editor
+            } else {
+                when == When::Global || (when == When::Terminal && focus == Focus::Terminal)
+            }
+        };
+        let pending = if editor {
+            &mut self.editor_pending
+        } else {
+            &mut self.app_pending
+        };
+        pending.push(shortcut);
+
+        // a longer chord wins, otherwise its first key could never be pressed
+        let mut resolution = Resolution::None;
+        for binding in self.bindings.iter().filter(|b| active(b.when)) {
+            let matches = binding.chord.len() >= pending.len()
+                && pending.iter().zip(&binding.chord).all(|(pressed, bound)| {
+                    pressed.logical_key == bound.logical_key
+                        && pressed.modifiers.matches_exact(bound.modifiers)
+                });
+            if matches && binding.chord.len() > pending.len() {
+                resolution = Resolution::Pending;
+                break;
+            } else if matches && matches!(resolution, Resolution::None) {
+                resolution = Resolution::Action(binding.action);
+            }
+        }
+
+        match resolution {
+            Resolution::Pending => {}
+            // a key which breaks a chord is swallowed as well
+            Resolution::None if pending.len() > 1 => {
+                pending.clear();
+                resolution = Resolution::Pending;
+            }
+            _ => pending.clear(),
+        }
+        if !matches!(resolution, Resolution::None) {
+            self.swallow_text = produces_text(&shortcut);
+        }
+        resolution
+    }
+}
+
+fn default_when(action: Action) -> When {
+    if action.is_editor() {
+        When::Editor
+    } else {
+        When::Global
+    }
+}
+
+/// Compares chords the way they are pressed, `Cmd` is `Ctrl` outside of macOS.
+fn same_chord(a: &[KeyboardShortcut], b: &[KeyboardShortcut]) -> bool {
+    let normalized = |mut modifiers: Modifiers| {
+        if modifiers.command {
+            if cfg!(target_os = "macos") {
+                modifiers.mac_cmd = true;
+            } else {
+                modifiers.ctrl = true;
+            }
+        }
+        modifiers.command = false;
+        modifiers
+    };
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(a, b)| {
+            a.logical_key == b.logical_key && normalized(a.modifiers) == normalized(b.modifiers)
+        })
+}
+
+/// Whether pressing `shortcut` also emits a text event.
+fn produces_text(shortcut: &KeyboardShortcut) -> bool {
+    let modifiers = shortcut.modifiers;
+    !(modifiers.ctrl || modifiers.command || modifiers.alt || modifiers.mac_cmd)
+        && (shortcut.logical_key == Key::Space
+            || shortcut.logical_key.symbol_or_name().chars().count() == 1)
+}
+
+/// Parses chords like `Ctrl+K Ctrl+C`.
+pub fn parse_chord(keys: &str) -> Result<Vec<KeyboardShortcut>, String> {
+    let chord = keys
+        .split_whitespace()
+        .map(parse_shortcut)
+        .collect::<Result<Vec<_>, _>>()?;
+    if chord.is_empty() {
+        return Err("empty key binding".to_string());
+    }
+    Ok(chord)
+}
+
+fn parse_shortcut(keys: &str) -> Result<KeyboardShortcut, String> {
+    let (modifiers, key) = match keys.strip_suffix("++") {
+        Some(modifiers) => (modifiers, "+"),
+        None => keys.rsplit_once('+').unwrap_or(("", keys)),
+    };
+    let mut parsed = Modifiers::NONE;
+    for modifier in modifiers.split('+').filter(|m| !m.is_empty()) {
+        parsed = parsed
+            | match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => Modifiers::CTRL,
+                "cmd" | "command" => Modifiers::COMMAND,
+                "maccmd" | "super" => Modifiers::MAC_CMD,
+                "alt" | "option" | "meta" => Modifiers::ALT,
+                "shift" => Modifiers::SHIFT,
+                _ => return Err(format!("{keys}: unknown modifier \"{modifier}\"")),
+            };
+    }
+    let key = Key::from_name(key).ok_or_else(|| format!("{keys}: unknown key \"{key}\""))?;
+    Ok(KeyboardShortcut::new(parsed, key))
+}
+
+fn chord_text(chord: &[KeyboardShortcut]) -> String {
+    chord
+        .iter()
+        .map(|shortcut| shortcut.format(&eframe::egui::ModifierNames::NAMES, false))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(key: Key, modifiers: Modifiers) -> Event {
+        Event::Key {
+            key,
+            physical_key: None,
+            pressed: true,
+            repeat: false,
+            modifiers,
+        }
+    }
+
+    fn resolved(keymap: &mut Keymap, events: &[Event], editor: bool) -> Option<Action> {
+        let focus = if editor { Focus::Editor } else { Focus::None };
+        let mut action = None;
+        for event in events {
+            action = match keymap.resolve(event, editor, focus) {
+                Resolution::Action(action) => Some(action),
+                _ => None,
+            };
+        }
+        action
+    }
+
+    #[test]
+    fn parses_chords() {
+        let chord = parse_chord("Ctrl+K  Shift+Alt+F").unwrap();
+        assert_eq!(chord.len(), 2);
+        assert_eq!(chord[0], KeyboardShortcut::new(Modifiers::CTRL, Key::K));
+        assert_eq!(
+            chord[1],
+            KeyboardShortcut::new(Modifiers::SHIFT | Modifiers::ALT, Key::F)
+        );
+        assert_eq!(
+            parse_chord("cmd+Backtick").unwrap()[0],
+            KeyboardShortcut::new(Modifiers::COMMAND, Key::Backtick)
+        );
+        assert_eq!(parse_chord("Ctrl++").unwrap()[0].logical_key, Key::Plus);
+        assert!(parse_chord("").is_err());
+        assert!(parse_chord("Hyper+Q").is_err());
+        assert!(parse_chord("Ctrl+Nope").is_err());
+    }
+
+    #[test]
+    fn reports_problems() {
+        assert!(Keymap::default().problems.is_empty());
+        assert!(Keymap::from_preset(Preset::Emacs).conflicts().is_empty());
+
+        let keymap = Keymap::parse(
+            r#"
+            [[bindings]]
+            keys = "Hyper+Q"
+            action = "quit"
+
+            [[bindings]]
+            keys = "Ctrl+Q"
+            action = "undo"
+            when = "global"
+            "#,
+        );
+        assert_eq!(keymap.problems.len(), 2, "{:?}", keymap.problems);
+        assert_eq!(Keymap::parse("nonsense").problems.len(), 1);
+    }
+
+    #[test]
+    fn detects_conflicts() {
+        let mut keymap = Keymap::from_preset(Preset::Default);
+        keymap.bind(parse_chord("Ctrl+Q").unwrap(), Action::Quit, When::Global);
+        keymap.bind(
+            parse_chord("Ctrl+Q").unwrap(),
+            Action::ClearCache,
+            When::Terminal,
+        );
+        keymap.bind(parse_chord("Alt+K").unwrap(), Action::LineEnd, When::Editor);
+        keymap.bind(
+            parse_chord("Alt+K Alt+T").unwrap(),
+            Action::LineStart,
+            When::Editor,
+        );
+        let conflicts = keymap.conflicts();
+        assert_eq!(conflicts.len(), 2, "{conflicts:?}");
+        assert!(conflicts[0].contains("\"Quit\" and \"Clear Cache\""));
+        // the chord wins over the single key
+        assert!(conflicts[1].starts_with("Alt+K Alt+T (\"Line Start\") shadows Alt+K"));
+
+        // the same chord in other contexts does not overlap
+        let mut keymap = Keymap::from_preset(Preset::Default);
+        keymap.bind(parse_chord("Alt+Q").unwrap(), Action::LineEnd, When::Editor);
+        keymap.bind(
+            parse_chord("Alt+Q").unwrap(),
+            Action::ClearCache,
+            When::Terminal,
+        );
+        assert!(keymap.conflicts().is_empty());
+    }
+
+    #[test]
+    fn resolves_chords() {
+        let mut keymap = Keymap::parse(
+            r#"
+            preset = "emacs"
+
+            [[bindings]]
+            keys = "Ctrl+K Ctrl+T"
+            action = "toggle_terminal"
+            when = "editor"
+            "#,
+        );
+        let ctrl = |key| press(key, Modifiers::CTRL);
+        assert_eq!(
+            resolved(&mut keymap, &[ctrl(Key::X), ctrl(Key::S)], false),
+            Some(Action::SaveFile)
+        );
+        // a pending chord swallows the clipboard events as well
+        assert!(matches!(
+            keymap.resolve(&ctrl(Key::X), false, Focus::None),
+            Resolution::Pending
+        ));
+        assert!(matches!(
+            keymap.resolve(&Event::Cut, false, Focus::None),
+            Resolution::Pending
+        ));
+        assert_eq!(
+            resolved(&mut keymap, &[ctrl(Key::A)], true),
+            Some(Action::LineStart)
+        );
+        assert_eq!(
+            resolved(&mut keymap, &[press(Key::A, Modifiers::NONE)], true),
+            None
+        );
+
+        // the chord wins over the default `Ctrl+K`
+        assert!(matches!(
+            keymap.resolve(&ctrl(Key::K), true, Focus::Editor),
+            Resolution::Pending
+        ));
+        assert!(matches!(
+            keymap.resolve(&ctrl(Key::T), true, Focus::Editor),
+            Resolution::Action(Action::ToggleTerminal)
+        ));
+    }
+
+    #[test]
+    fn shift_keeps_editing_keys() {
+        let mut keymap = Keymap::default();
+        for (key, action) in [
+            (Key::Enter, Action::NewLine),
+            (Key::Backspace, Action::DeleteCharBackward),
+        ] {
+            assert_eq!(
+                resolved(&mut keymap, &[press(key, Modifiers::SHIFT)], true),
+                Some(action)
+            );
+        }
+        assert_eq!(
+            resolved(&mut keymap, &[press(Key::Tab, Modifiers::SHIFT)], true),
+            Some(Action::Outdent)
+        );
+        let shift_delete = resolved(&mut keymap, &[press(Key::Delete, Modifiers::SHIFT)], true);
+        match cfg!(target_os = "windows") {
+            true => assert_eq!(shift_delete, None),
+            false => assert_eq!(shift_delete, Some(Action::DeleteCharForward)),
+        }
+        assert_eq!(
+            resolved(&mut keymap, &[press(Key::K, Modifiers::CTRL)], true),
+            Some(Action::DeleteToLineEnd)
+        );
+    }
+}
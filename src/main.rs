@@ -1,24 +1,82 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+pub mod actions;
+pub mod cli;
 pub mod code_editor;
+pub mod completion;
+pub mod diff;
+pub mod editorconfig;
+pub mod file_index;
+pub mod file_ops;
+pub mod file_tree;
+pub mod format;
+pub mod fuzzy;
+pub mod indent;
+pub mod ipc;
+pub mod keymap;
 pub mod panels;
+pub mod session;
+pub mod settings;
+pub mod snippets;
+pub mod symbols;
 pub mod terminal;
+pub mod vim;
+pub mod workspace;
 
-use std::{collections::HashMap, fs, path::PathBuf, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io, mem,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
+use actions::Action;
+use cli::{Args, Command};
 use code_editor::FileData;
+use completion::Completion;
+use diff::Diff;
+use editorconfig::EditorConfig;
 use eframe::{
     egui::{self},
     get_value, icon_data, set_value, Storage,
 };
+use file_ops::FileOp;
+use file_tree::FileTree;
+use indent::Indent;
+use keymap::{Focus, Keymap};
+use panels::{command_palette::CommandPalette, quick_open::QuickOpen, settings::SettingsEditor};
+use portable_pty::CommandBuilder;
 use serde::{Deserialize, Serialize};
+use session::Session;
+use settings::{Config, FontSettings, Settings};
+use snippets::Snippets;
 use terminal::TermHandler;
+use vim::{ExCommand, Vim};
+use workspace::Workspace;
 
 fn main() -> Result<(), eframe::Error> {
     env_logger::init();
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let args = match cli::parse(std::env::args().skip(1), &cwd) {
+        Ok(Command::Open(args)) => args,
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            std::process::exit(0);
+        }
+        Err(err) => {
+            eprint!("error: {err}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if !args.new_window && ipc::forward(&args) {
+        return Ok(());
+    }
+    // the window is created before a project is known
+    let (settings, _) = Config::load(None);
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([1000.0, 700.0])
+            .with_inner_size([settings.window.width, settings.window.height])
             .with_min_inner_size([600.0, 400.0])
             .with_icon(Arc::new(
                 icon_data::from_png_bytes(include_bytes!("../assets/icon.png")).unwrap(),
@@ -28,20 +86,42 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "eta",
         options,
-        Box::new(|cc| Box::new(App::new(cc.storage))),
+        Box::new(|cc| Box::new(App::new(&cc.egui_ctx, cc.storage, args))),
     )
 }
 
 struct App {
     project: Project,
+    /// Windows opened with `--new-window` are not saved.
+    persist: bool,
+    server: Option<ipc::Server>,
 }
 
 impl App {
-    fn new(storage: Option<&dyn Storage>) -> Self {
+    fn new(ctx: &egui::Context, storage: Option<&dyn Storage>, args: Args) -> Self {
+        let mut project: Project = storage
+            .filter(|_| !args.new_window)
+            .and_then(|s| get_value(s, "project"))
+            .unwrap_or_default();
+        project.keymap = Keymap::load();
+        project.config.sync(ctx, project.project_path.as_deref());
+        // zooming only scales the editor and the terminal
+        ctx.options_mut(|options| options.zoom_with_keyboard = false);
+        let problems = apply_fonts(ctx, &project.config.settings.font);
+        project.config.problems.extend(problems);
+        apply_settings(ctx, &project.config.settings, project.zoom_factor());
+        for root in mem::take(&mut project.open_terminals) {
+            project.open_terminal(&root);
+        }
+        project.terminal_root = None;
+        let persist = !args.new_window;
+        let server = persist.then(|| ipc::Server::start(ctx)).flatten();
+        project.wait_for = args.wait_for();
+        project.open_args(args);
         Self {
-            project: storage
-                .and_then(|s| get_value(s, "project"))
-                .unwrap_or_default(),
+            project,
+            persist,
+            server,
         }
     }
 }
@@ -50,15 +130,67 @@ impl App {
 pub struct Project {
     #[serde(skip)]
     pub terminals: HashMap<PathBuf, TermHandler>,
+    /// The first root of the workspace.
     pub project_path: Option<PathBuf>,
+    #[serde(default)]
+    pub workspace: Workspace,
+    /// Root whose terminal is shown.
+    #[serde(skip)]
+    pub terminal_root: Option<PathBuf>,
     pub current_file: Option<PathBuf>,
     pub files: HashMap<PathBuf, FileData>,
     pub files_edited: HashMap<PathBuf, bool>,
+    #[serde(default)]
+    pub vim: Vim,
+    #[serde(skip)]
+    pub keymap: Keymap,
+    #[serde(skip)]
+    pub focus: Focus,
+    #[serde(default)]
+    pub palette: CommandPalette,
+    #[serde(skip)]
+    pub quick_open: QuickOpen,
+    #[serde(default)]
+    pub file_tree: FileTree,
+    /// Sessions of the projects that are not open, keyed by project path or workspace file.
+    #[serde(default)]
+    pub sessions: HashMap<PathBuf, Session>,
+    /// Recently opened project paths and workspace files, the latest first.
+    #[serde(default)]
+    pub recent: Vec<PathBuf>,
+    /// Roots whose terminal is started again on the next launch.
+    #[serde(default)]
+    pub open_terminals: Vec<PathBuf>,
+    #[serde(skip)]
+    pub diff: Option<Diff>,
+    /// Files opened with `--wait`, eta quits once all of them are closed.
+    #[serde(skip)]
+    pub wait_for: Vec<PathBuf>,
+    #[serde(skip)]
+    pub config: Config,
+    /// Why the last formatting failed.
+    #[serde(skip)]
+    pub format_error: Option<String>,
+    #[serde(skip)]
+    pub formatting: Option<format::Job>,
+    #[serde(skip)]
+    pub settings_editor: SettingsEditor,
+    #[serde(skip)]
+    pub snippets: Snippets,
+    #[serde(skip)]
+    pub completion: Completion,
+    /// Zoom steps of the editor and the terminal, 0 is the size of the settings.
+    #[serde(default)]
+    pub zoom: i32,
 }
 
 impl Project {
+    pub fn zoom_factor(&self) -> f32 {
+        1.1_f32.powi(self.zoom)
+    }
+
     pub fn is_file_edited(&self, path: &PathBuf) -> bool {
-        self.files_edited.get(path).is_some()
+        self.files_edited.contains_key(path)
     }
 
     pub fn is_current_file_edited(&self) -> bool {
@@ -78,8 +210,7 @@ impl Project {
     }
 
     pub fn get_current_terminal(&self) -> Option<&TermHandler> {
-        self.terminals
-            .get(self.project_path.as_ref().unwrap_or(&PathBuf::default()))
+        self.terminals.get(&self.current_terminal_root())
     }
 
     pub fn get_mut_terminal(&mut self, path: &PathBuf) -> Option<&mut TermHandler> {
@@ -87,8 +218,239 @@ impl Project {
     }
 
     pub fn get_mut_current_terminal(&mut self) -> Option<&mut TermHandler> {
-        self.terminals
-            .get_mut(self.project_path.as_ref().unwrap_or(&PathBuf::default()))
+        self.terminals.get_mut(&self.current_terminal_root())
+    }
+
+    /// The project path followed by the other folders of the workspace.
+    pub fn roots(&self) -> Vec<PathBuf> {
+        self.project_path
+            .iter()
+            .chain(&self.workspace.folders)
+            .cloned()
+            .collect()
+    }
+
+    /// The root containing `path`.
+    pub fn root_of(&self, path: &Path) -> Option<PathBuf> {
+        self.roots()
+            .into_iter()
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.components().count())
+    }
+
+    /// The chosen root or the one of the current file.
+    pub fn current_terminal_root(&self) -> PathBuf {
+        self.terminal_root
+            .clone()
+            .or_else(|| {
+                self.current_file
+                    .as_deref()
+                    .and_then(|file| self.root_of(file))
+            })
+            .or(self.project_path.clone())
+            .unwrap_or_default()
+    }
+
+    /// Makes `path` the current file, (re)loading it from disk if it has no unsaved changes.
+    pub fn open_file(&mut self, path: PathBuf) {
+        if !self.is_file_edited(&path) {
+            let (editor, indent_override) = self
+                .files
+                .get(&path)
+                .map(|file| (file.editor.clone(), file.indent_override))
+                .unwrap_or_default();
+            let editorconfig = EditorConfig::resolve(&path);
+            let text = editorconfig.decode(&fs::read(&path).unwrap_or_default());
+            self.files.insert(
+                path.clone(),
+                FileData {
+                    text,
+                    editor,
+                    editorconfig: Some(editorconfig),
+                    indent_override,
+                    detected_indent: None,
+                },
+            );
+        }
+        self.current_file = Some(path);
+    }
+
+    /// Opens what was given on the command line.
+    fn open_args(&mut self, args: Args) {
+        if !args.folders.is_empty() {
+            self.switch_project(args.folders, None);
+        } else if self.project_path.is_none() {
+            // a project is needed for the tree and the terminal
+            if let Some(parent) = args.files.first().and_then(|file| file.path.parent()) {
+                self.switch_project(vec![parent.to_path_buf()], None);
+            }
+        }
+        for file in args.files {
+            self.open_file(file.path);
+            if let Some((line, column)) = file.position {
+                self.go_to(line.saturating_sub(1), column.saturating_sub(1));
+            }
+        }
+        if let Some((left, right)) = args.diff {
+            match Diff::load(left, right) {
+                Ok(diff) => self.diff = Some(diff),
+                Err(err) => self.file_tree.error = Some(err.to_string()),
+            }
+        }
+    }
+
+    /// Moves the cursor of the current file to the 0-based `line` and `column`.
+    pub fn go_to(&mut self, line: usize, column: usize) {
+        if let Some(current_file) = &self.current_file {
+            if let Some(file) = self.files.get_mut(current_file) {
+                file.editor.jump_to = Some((line, column));
+            }
+        }
+    }
+
+    /// Jumps to a 1-based `line[:column]` in the current file.
+    pub fn go_to_position(&mut self, position: &str) -> io::Result<()> {
+        if self.current_file.is_none() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "No file opened"));
+        }
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, "Expected line[:column]");
+        let (line, column) = position.trim().split_once(':').unwrap_or((position, "1"));
+        let line = line.trim().parse::<usize>().map_err(|_| invalid())?;
+        let column = column.trim().parse::<usize>().map_err(|_| invalid())?;
+        self.go_to(line.saturating_sub(1), column.saturating_sub(1));
+        Ok(())
+    }
+
+    /// Starts piping the current file or the lines of its selection through the formatter,
+    /// saving it afterwards with `save`.
+    pub fn format_current_file(
+        &mut self,
+        ctx: &egui::Context,
+        selection: bool,
+        save: bool,
+    ) -> Result<(), String> {
+        let Some(path) = self.current_file.clone() else {
+            return Err("No file opened".to_string());
+        };
+        if self.formatting.is_some() {
+            return Err("The formatter is still running".to_string());
+        }
+        let settings = &self.config.settings.format;
+        let command = format::command_for(settings, &path)
+            .ok_or_else(|| format!("No formatter for {}", path.display()))?;
+        let Some(file) = self.files.get(&path) else {
+            return Ok(());
+        };
+        let cursor = file.editor.state.cursor.char_range().unwrap_or_default();
+        let timeout = Duration::from_secs_f32(settings.timeout);
+
+        let range = if selection && cursor.primary.index != cursor.secondary.index {
+            let chars = file.text.chars().collect::<Vec<_>>();
+            let [start, end] = {
+                let mut range = [cursor.primary.index, cursor.secondary.index]
+                    .map(|index| index.min(chars.len()));
+                range.sort();
+                range
+            };
+            // whole lines, formatters do not take parts of them
+            let start = chars[..start]
+                .iter()
+                .rposition(|c| *c == '\n')
+                .map_or(0, |i| i + 1);
+            let end = match chars[..end].last() {
+                Some('\n') => end,
+                _ => chars[end..]
+                    .iter()
+                    .position(|c| *c == '\n')
+                    .map_or(chars.len(), |i| end + i + 1),
+            };
+            start..end
+        } else {
+            0..file.text.chars().count()
+        };
+        self.formatting = Some(format::Job::start(
+            ctx,
+            command,
+            path,
+            file.text.clone(),
+            range,
+            timeout,
+            save,
+        ));
+        Ok(())
+    }
+
+    /// Applies the output of a finished formatter and saves the file if it was asked to.
+    pub fn poll_formatting(&mut self) {
+        let Some(result) = self.formatting.as_ref().and_then(format::Job::poll) else {
+            return;
+        };
+        let Some(job) = self.formatting.take() else {
+            return;
+        };
+        match (result, self.files.get_mut(&job.path)) {
+            (Ok(formatted), Some(file)) if file.text == job.text => {
+                self.format_error = None;
+                if formatted != file.text {
+                    let mut cursor = file.editor.state.cursor.char_range().unwrap_or_default();
+                    format::apply(&mut file.text, &formatted, &mut cursor);
+                    file.editor.state.cursor.set_char_range(Some(cursor));
+                    self.files_edited.insert(job.path.clone(), true);
+                }
+            }
+            // typed while the formatter was running
+            (Ok(_), Some(_)) => {
+                self.format_error = Some("The file changed while it was formatted".to_string())
+            }
+            (Ok(_), None) => {}
+            (Err(err), _) => self.format_error = Some(err),
+        }
+        if job.save {
+            self.save_file(&job.path);
+        }
+    }
+
+    /// Saves the current file, formatting it first in the background if enabled.
+    pub fn save_current_file(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.current_file.clone() else {
+            return;
+        };
+        if self.config.settings.format.on_save
+            && format::command_for(&self.config.settings.format, &path).is_some()
+        {
+            match self.format_current_file(ctx, false, true) {
+                Ok(()) => return,
+                Err(err) => self.format_error = Some(err),
+            }
+        }
+        self.save_file(&path);
+    }
+
+    /// Saves `file_path` as its `.editorconfig` asks for.
+    pub fn save_file(&mut self, file_path: &Path) {
+        if let Some(content) = self.files.get_mut(file_path) {
+            let editorconfig = content.editorconfig(file_path).clone();
+            let text = editorconfig.normalize(&content.text);
+            fs::write(file_path, editorconfig.encode(&text)).unwrap_or_default();
+            if text != content.text {
+                let mut cursor = content.editor.state.cursor.char_range().unwrap_or_default();
+                format::apply(&mut content.text, &text, &mut cursor);
+                content.editor.state.cursor.set_char_range(Some(cursor));
+            }
+            self.files_edited.remove(file_path);
+        }
+        // the properties of the open files may have changed
+        if file_path.ends_with(".editorconfig") {
+            for file in self.files.values_mut() {
+                file.editorconfig = None;
+            }
+        }
+        if file_path
+            .parent()
+            .is_some_and(|dir| dir.ends_with("snippets"))
+        {
+            self.snippets.clear();
+        }
     }
 
     pub fn remove_file(&mut self, path: &PathBuf) {
@@ -105,52 +467,696 @@ impl Project {
         self.terminals.remove(path);
     }
 
+    /// Resets everything except the loaded keymap.
+    pub fn clear_cache(&mut self) {
+        *self = Project {
+            keymap: std::mem::take(&mut self.keymap),
+            ..Default::default()
+        };
+    }
+
+    pub fn toggle_terminal(&mut self) {
+        let path = self.current_terminal_root();
+        if self.get_terminal(&path).is_some() {
+            self.remove_terminal(&path);
+        } else {
+            self.open_terminal(&path);
+        }
+    }
+
+    /// Shows the terminal of `root`, starting it if needed.
+    pub fn open_terminal(&mut self, root: &Path) {
+        if self.get_terminal(&root.to_path_buf()).is_none() {
+            let mut cmd = CommandBuilder::new_default_prog();
+            cmd.cwd(root);
+            // `eta` in the terminal opens files here
+            cmd.env(ipc::SOCKET_VAR, ipc::socket_path());
+            self.terminals
+                .insert(root.to_path_buf(), TermHandler::new(cmd));
+        }
+        self.terminal_root = Some(root.to_path_buf());
+    }
+
+    /// Directory new files are created in: the one of the current file or the project root.
+    fn current_directory(&self) -> Option<PathBuf> {
+        self.current_file
+            .as_ref()
+            .and_then(|file| file.parent().map(Path::to_path_buf))
+            .or(self.project_path.clone())
+    }
+
+    pub fn new_file(&mut self, name: &str) -> io::Result<()> {
+        let directory = self.current_directory().ok_or(io::ErrorKind::NotFound)?;
+        let path = directory.join(name);
+        if path.exists() {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, "")?;
+        self.file_tree
+            .record("New File", vec![FileOp::Created(path.clone())]);
+        self.open_file(path);
+        Ok(())
+    }
+
+    pub fn new_directory(&mut self, name: &str) -> io::Result<()> {
+        let directory = self.current_directory().ok_or(io::ErrorKind::NotFound)?;
+        let path = directory.join(name);
+        if path.exists() {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        fs::create_dir_all(&path)?;
+        self.file_tree
+            .record("New Directory", vec![FileOp::Created(path)]);
+        Ok(())
+    }
+
+    pub fn rename_current_file(&mut self, name: &str) -> io::Result<()> {
+        let current_file = self.current_file.clone().ok_or(io::ErrorKind::NotFound)?;
+        let to = current_file.with_file_name(name);
+        self.rename_path(&current_file, &to)?;
+        self.file_tree.record(
+            "Rename",
+            vec![FileOp::Moved {
+                from: current_file,
+                to,
+            }],
+        );
+        Ok(())
+    }
+
+    /// Renames `from` on disk and moves the open files below it along.
+    pub fn rename_path(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        if to.exists() {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        fs::rename(from, to)?;
+        let moved = |path: &Path| match path.strip_prefix(from) {
+            Ok(rest) if rest.as_os_str().is_empty() => Some(to.to_path_buf()),
+            Ok(rest) => Some(to.join(rest)),
+            Err(_) => None,
+        };
+        for path in self.files.keys().cloned().collect::<Vec<_>>() {
+            if let Some(new_path) = moved(&path) {
+                if let Some(file) = self.files.remove(&path) {
+                    self.files.insert(new_path.clone(), file);
+                }
+                if let Some(edited) = self.files_edited.remove(&path) {
+                    self.files_edited.insert(new_path, edited);
+                }
+            }
+        }
+        if let Some(new_path) = self.current_file.as_deref().and_then(moved) {
+            self.current_file = Some(new_path);
+        }
+        self.file_tree.moved(from, to);
+        Ok(())
+    }
+
+    /// Moves `paths` into the directory `dir`.
+    pub fn move_paths(&mut self, paths: &[PathBuf], dir: &Path) -> io::Result<()> {
+        let mut ops = Vec::new();
+        let result = paths.iter().try_for_each(|path| {
+            let Some(name) = path.file_name() else {
+                return Ok(());
+            };
+            if dir.starts_with(path) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Cannot move a directory into itself",
+                ));
+            }
+            if path.parent() != Some(dir) {
+                let to = dir.join(name);
+                self.rename_path(path, &to)?;
+                ops.push(FileOp::Moved {
+                    from: path.clone(),
+                    to,
+                });
+            }
+            Ok(())
+        });
+        self.file_tree.record("Move", ops);
+        result
+    }
+
+    /// Copies `paths` into the directory `dir`, keeping both on name clashes.
+    pub fn copy_paths(&mut self, paths: &[PathBuf], dir: &Path) -> io::Result<()> {
+        let mut ops = Vec::new();
+        let result = paths.iter().try_for_each(|path| {
+            let Some(name) = path.file_name() else {
+                return Ok(());
+            };
+            let to = file_ops::unique_path(&dir.join(name));
+            file_ops::copy_recursive(path, &to)?;
+            ops.push(FileOp::Created(to));
+            Ok(())
+        });
+        self.file_tree.record("Copy", ops);
+        result
+    }
+
+    /// Moves `paths` to the trash and closes the files below them.
+    pub fn trash_paths(&mut self, paths: &[PathBuf]) -> io::Result<()> {
+        let mut ops = Vec::new();
+        let result = paths.iter().try_for_each(|path| {
+            ops.push(file_ops::trash(path)?);
+            self.close_files_below(path);
+            Ok(())
+        });
+        self.file_tree.record("Delete", ops);
+        result
+    }
+
+    /// Reverts the latest operation of the file tree.
+    pub fn undo_file_operation(&mut self) -> io::Result<()> {
+        let Some((title, mut ops)) = self.file_tree.take_undo() else {
+            return Ok(());
+        };
+        while let Some(op) = ops.pop() {
+            let result = match &op {
+                FileOp::Created(path) => {
+                    // trashed, it may have been edited since
+                    file_ops::trash(path).map(|_| self.close_files_below(path))
+                }
+                FileOp::Moved { from, to } => self.rename_path(to, from),
+                FileOp::Trashed(path, deleted_at) => file_ops::restore(path, *deleted_at),
+            };
+            // what is left can be undone again
+            if let Err(err) = result {
+                ops.push(op);
+                self.file_tree.record(title, ops);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    fn close_files_below(&mut self, path: &Path) {
+        self.files.retain(|file, _| !file.starts_with(path));
+        self.files_edited.retain(|file, _| !file.starts_with(path));
+        if self
+            .current_file
+            .as_ref()
+            .is_some_and(|current_file| current_file.starts_with(path))
+        {
+            self.current_file = None;
+        }
+    }
+
+    /// Opens the keymap file, creating it from the template if needed.
+    pub fn edit_keymap(&mut self) {
+        if let Some(path) = Keymap::path() {
+            if !path.exists() {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).unwrap_or_default();
+                }
+                fs::write(&path, keymap::TEMPLATE).unwrap_or_default();
+            }
+            self.open_file(path);
+        }
+    }
+
+    /// Rewrites the indentation of the current file with tabs or spaces and indents with them.
+    pub fn convert_indentation(&mut self, to_tabs: bool) {
+        let Some(file_path) = self.current_file.clone() else {
+            return;
+        };
+        let Some(content) = self.files.get_mut(&file_path) else {
+            return;
+        };
+        let width = content.indent().width();
+        let to = match to_tabs {
+            true => Indent::Tabs,
+            false => Indent::Spaces(width),
+        };
+        let converted = indent::convert(&content.text, width, to);
+        if converted != content.text {
+            let mut cursor = content.editor.state.cursor.char_range().unwrap_or_default();
+            format::apply(&mut content.text, &converted, &mut cursor);
+            content.editor.state.cursor.set_char_range(Some(cursor));
+            self.files_edited.insert(file_path, true);
+        }
+        content.indent_override = Some(to);
+    }
+
+    /// Opens the global snippets of the language of the current file.
+    pub fn edit_snippets(&mut self) {
+        let Some(path) = self.current_file.as_deref().and_then(Snippets::path) else {
+            return;
+        };
+        if !path.exists() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).unwrap_or_default();
+            }
+            fs::write(&path, snippets::TEMPLATE).unwrap_or_default();
+        }
+        self.open_file(path);
+    }
+
     fn open_project(&mut self) {
         if let Some(project_path) = &rfd::FileDialog::new().pick_folder() {
-            if Some(project_path) != self.project_path.as_ref() {
-                self.project_path = Some(project_path.to_path_buf());
-                self.current_file = None;
+            if Some(project_path) != self.project_path.as_ref()
+                || !self.workspace.folders.is_empty()
+            {
+                self.switch_project(vec![project_path.to_path_buf()], None);
+            }
+        }
+    }
+
+    pub fn open_workspace(&mut self) {
+        let Some(file) = rfd::FileDialog::new()
+            .add_filter("Workspace", &[workspace::EXTENSION])
+            .pick_file()
+        else {
+            return;
+        };
+        match workspace::load(&file) {
+            Ok(roots) => self.switch_project(roots, Some(file)),
+            Err(err) => self.file_tree.error = Some(err.to_string()),
+        }
+    }
+
+    /// Opens a project path or workspace file of the recent list.
+    pub fn open_recent(&mut self, path: &Path) {
+        let roots = if path
+            .extension()
+            .is_some_and(|ext| ext == workspace::EXTENSION)
+        {
+            workspace::load(path).map(|roots| (roots, Some(path.to_path_buf())))
+        } else if path.is_dir() {
+            Ok((vec![path.to_path_buf()], None))
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "The project no longer exists",
+            ))
+        };
+        match roots {
+            Ok((roots, file)) => self.switch_project(roots, file),
+            Err(err) => {
+                self.recent.retain(|recent| recent != path);
+                self.file_tree.error = Some(err.to_string());
             }
         }
     }
+
+    /// The workspace file or the project path, sessions are stored under it.
+    pub fn session_key(&self) -> Option<PathBuf> {
+        self.workspace.file.clone().or(self.project_path.clone())
+    }
+
+    /// Opens another project, putting the open one aside and restoring the session of the new one.
+    fn switch_project(&mut self, roots: Vec<PathBuf>, file: Option<PathBuf>) {
+        if let Some(key) = self.session_key() {
+            let session = Session {
+                current_file: self.current_file.take(),
+                files: mem::take(&mut self.files),
+                files_edited: mem::take(&mut self.files_edited),
+                expanded: self.file_tree.replace_expanded(HashSet::new()),
+                terminals: self.terminals.drain().map(|(root, _)| root).collect(),
+            };
+            self.sessions.insert(key.clone(), session);
+            self.add_recent(key);
+        }
+
+        let key = file.clone().or(roots.first().cloned());
+        let session = key
+            .as_ref()
+            .and_then(|key| self.sessions.remove(key))
+            .unwrap_or_default();
+        self.current_file = session.current_file;
+        self.files = session.files;
+        self.files_edited = session.files_edited;
+        self.file_tree.replace_expanded(session.expanded);
+        self.set_roots(roots, file);
+        for root in session.terminals {
+            if self.roots().contains(&root) {
+                self.open_terminal(&root);
+            }
+        }
+        self.terminal_root = None;
+
+        if let Some(key) = key {
+            self.add_recent(key);
+        }
+    }
+
+    fn add_recent(&mut self, path: PathBuf) {
+        self.recent.retain(|recent| *recent != path);
+        self.recent.insert(0, path);
+        self.recent.truncate(session::MAX_RECENT);
+    }
+
+    /// Adds a folder to the workspace.
+    pub fn add_folder(&mut self) {
+        let Some(folder) = rfd::FileDialog::new().pick_folder() else {
+            return;
+        };
+        let mut roots = self.roots();
+        if !roots.contains(&folder) {
+            roots.push(folder);
+            self.set_roots(roots, self.workspace.file.clone());
+            self.write_workspace();
+        }
+    }
+
+    pub fn remove_folder(&mut self, root: &Path) {
+        let mut roots = self.roots();
+        roots.retain(|other| other != root);
+        self.remove_terminal(&root.to_path_buf());
+        self.set_roots(roots, self.workspace.file.clone());
+        self.write_workspace();
+    }
+
+    /// Writes a change of the roots back to the workspace file.
+    fn write_workspace(&mut self) {
+        if let Some(file) = &self.workspace.file {
+            if let Err(err) = workspace::save(file, &self.roots()) {
+                self.file_tree.error = Some(err.to_string());
+            }
+        }
+    }
+
+    /// Saves the roots to the workspace file, asking for one if there is none yet.
+    pub fn save_workspace(&mut self) {
+        let file = self.workspace.file.clone().or_else(|| {
+            let name = self
+                .project_path
+                .as_ref()?
+                .file_name()?
+                .to_string_lossy()
+                .to_string();
+            rfd::FileDialog::new()
+                .add_filter("Workspace", &[workspace::EXTENSION])
+                .set_file_name(format!("{name}.{}", workspace::EXTENSION))
+                .save_file()
+        });
+        if let Some(file) = file {
+            match workspace::save(&file, &self.roots()) {
+                Ok(()) => self.workspace.file = Some(file),
+                Err(err) => self.file_tree.error = Some(err.to_string()),
+            }
+        }
+    }
+
+    /// Changes the roots, keeping the open files inside them.
+    fn set_roots(&mut self, roots: Vec<PathBuf>, file: Option<PathBuf>) {
+        let mut roots = roots.into_iter();
+        self.project_path = roots.next();
+        self.workspace.folders = roots.collect();
+        self.workspace.file = file;
+        if self
+            .current_file
+            .as_deref()
+            .is_some_and(|current_file| self.root_of(current_file).is_none())
+        {
+            self.current_file = None;
+        }
+        self.terminal_root = None;
+    }
 }
 
 impl eframe::App for App {
     fn save(&mut self, storage: &mut dyn Storage) {
+        if !self.persist {
+            return;
+        }
+        self.project.open_terminals = self.project.terminals.keys().cloned().collect();
         set_value(storage, "project", &self.project);
     }
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // commands
-        if ctx.input_mut(|i| {
-            self.project.is_current_file_edited()
-                && i.consume_key(egui::Modifiers::COMMAND, egui::Key::S)
-        }) {
-            if let Some(file_path) = &self.project.current_file {
-                if let Some(content) = &self.project.files.get(file_path) {
-                    fs::write(file_path, content.text.clone()).unwrap_or_default();
-                    self.project.files_edited.remove(file_path);
-                }
-            }
+        let focus = self.project.focus;
+        for action in ctx.input_mut(|i| self.project.keymap.dispatch(i, focus)) {
+            self.run_action(ctx, action);
         }
-        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::O)) {
-            self.project.open_project();
+
+        // settings
+        let project_path = self.project.project_path.clone();
+        if let Some(old) = self.project.config.sync(ctx, project_path.as_deref()) {
+            let problems = apply_fonts(ctx, &self.project.config.settings.font);
+            self.project.config.problems.extend(problems);
+            let settings = &self.project.config.settings;
+            apply_settings(ctx, settings, self.project.zoom_factor());
+            if old.window != settings.window {
+                ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(
+                    settings.window.width,
+                    settings.window.height,
+                )));
+            }
         }
 
         // panels
+        self.project.focus = Focus::None;
         egui::TopBottomPanel::bottom("bottom_panel")
             .min_height(30.0)
             .show(ctx, |ui| {
                 panels::bottom_panel::init(ui, &mut self.project);
             });
         egui::SidePanel::left("left_side_panel")
-            .default_width(200.0)
+            .default_width(self.project.config.settings.layout.side_panel_width)
             .min_width(150.0)
-            .max_width(250.0)
+            .max_width(
+                self.project
+                    .config
+                    .settings
+                    .layout
+                    .side_panel_width
+                    .max(250.0),
+            )
             .show(ctx, |ui| {
                 panels::left_side_panel::init(ui, &mut self.project);
             });
         egui::CentralPanel::default().show(ctx, |ui| {
             panels::main_panel::init(ui, &mut self.project);
         });
+
+        panels::quick_open::init(ctx, &mut self.project);
+        panels::settings::init(ctx, &mut self.project);
+        if let Some(action) = panels::command_palette::init(ctx, &mut self.project) {
+            self.run_action(ctx, action);
+        }
+        for action in self.project.keymap.take_app_queued() {
+            self.run_action(ctx, action);
+        }
+
+        self.project.poll_formatting();
+
+        for command in self.project.vim.take_commands() {
+            self.run_ex_command(ctx, command);
+        }
+
+        // launches forwarded to this instance
+        if let Some(server) = &mut self.server {
+            for args in server.take_requests() {
+                self.project.open_args(args);
+                ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+            }
+            server.release_closed(&self.project.files);
+        }
+
+        // `--wait` ends with the files
+        let wait_for = &self.project.wait_for;
+        if !wait_for.is_empty()
+            && !wait_for
+                .iter()
+                .any(|file| self.project.files.contains_key(file))
+        {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+    }
+}
+
+impl App {
+    fn run_action(&mut self, ctx: &egui::Context, action: Action) {
+        let project = &mut self.project;
+        match action {
+            Action::SaveFile => {
+                if project.is_current_file_edited() {
+                    project.save_current_file(ctx)
+                }
+            }
+            Action::FormatDocument => {
+                project.format_error = project.format_current_file(ctx, false, false).err()
+            }
+            Action::FormatSelection => {
+                project.format_error = project.format_current_file(ctx, true, false).err()
+            }
+            Action::CloseFile => {
+                if let Some(current_file) = project.current_file.clone() {
+                    if !project.is_file_edited(&current_file) {
+                        project.remove_file(&current_file);
+                    }
+                }
+            }
+            Action::OpenProject => project.open_project(),
+            Action::OpenWorkspace => project.open_workspace(),
+            Action::AddFolder => project.add_folder(),
+            Action::SaveWorkspace => project.save_workspace(),
+            Action::ClearCache => project.clear_cache(),
+            Action::ToggleTerminal => project.toggle_terminal(),
+            Action::ToggleVim => project.vim.toggle(),
+            Action::ReloadKeymap => project.keymap = Keymap::load(),
+            Action::EditKeymap => project.edit_keymap(),
+            Action::EditSnippets => project.edit_snippets(),
+            Action::ConvertIndentationToSpaces => project.convert_indentation(false),
+            Action::ConvertIndentationToTabs => project.convert_indentation(true),
+            Action::OpenSettings => project.settings_editor.toggle(&project.config.settings),
+            Action::ZoomIn | Action::ZoomOut | Action::ResetZoom => {
+                project.zoom = match action {
+                    Action::ZoomIn => (project.zoom + 1).min(12),
+                    Action::ZoomOut => (project.zoom - 1).max(-6),
+                    _ => 0,
+                };
+                apply_settings(ctx, &project.config.settings, project.zoom_factor());
+            }
+            Action::Quit => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+            Action::CommandPalette => project.palette.toggle(project.focus == Focus::Editor),
+            Action::QuickOpen => project.quick_open.toggle(
+                ctx,
+                &project.roots(),
+                project.file_tree.changes(),
+                project.focus == Focus::Editor,
+            ),
+            Action::GoToSymbol => {
+                if let Some(current_file) = &project.current_file {
+                    let text = project
+                        .get_current_file()
+                        .map(|file| file.text.as_str())
+                        .unwrap_or_default();
+                    project.palette.show_symbols(
+                        symbols::symbols(current_file, text),
+                        project.focus == Focus::Editor,
+                    );
+                }
+            }
+            Action::NewFile | Action::NewDirectory | Action::GoToLine => {
+                project
+                    .palette
+                    .prompt(action, String::new(), project.focus == Focus::Editor)
+            }
+            Action::RenameFile => {
+                if let Some(current_file) = &project.current_file {
+                    let name = current_file.file_name().unwrap_or_default();
+                    project.palette.prompt(
+                        action,
+                        name.to_string_lossy().to_string(),
+                        project.focus == Focus::Editor,
+                    )
+                }
+            }
+            Action::RevealActiveFile => {
+                if let Some(current_file) = &project.current_file {
+                    project.file_tree.reveal(current_file);
+                }
+            }
+            Action::UndoFileOperation => {
+                if let Err(err) = project.undo_file_operation() {
+                    project.file_tree.error = Some(err.to_string());
+                }
+            }
+            // everything else is run by the code editor
+            _ => project.keymap.queue(action),
+        }
+        ctx.request_repaint();
+    }
+
+    fn run_ex_command(&mut self, ctx: &egui::Context, command: ExCommand) {
+        let project = &mut self.project;
+        match command {
+            ExCommand::Write => {
+                if project.is_current_file_edited() {
+                    project.save_current_file(ctx);
+                }
+            }
+            ExCommand::Quit { force } => {
+                if let Some(current_file) = project.current_file.clone() {
+                    if project.is_file_edited(&current_file) && !force {
+                        project.vim.message =
+                            Some("No write since last change (add ! to override)".to_string());
+                    } else {
+                        project.remove_file(&current_file);
+                    }
+                }
+            }
+            ExCommand::QuitAll { force } => {
+                if !project.files_edited.is_empty() && !force {
+                    project.vim.message =
+                        Some("No write since last change (add ! to override)".to_string());
+                } else {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                }
+            }
+            ExCommand::Edit { path, force } => {
+                let Some(path) = path.or(project.current_file.clone()) else {
+                    return;
+                };
+                let path = match &project.project_path {
+                    Some(project_path) if path.is_relative() => project_path.join(path),
+                    _ => path,
+                };
+                if force {
+                    project.files_edited.remove(&path);
+                }
+                if project.is_file_edited(&path) {
+                    project.vim.message =
+                        Some("No write since last change (add ! to override)".to_string());
+                } else {
+                    project.open_file(path);
+                }
+            }
+        }
+        ctx.request_repaint();
+    }
+}
+
+/// Applies what takes effect through the egui style, the editor font is scaled by `zoom`.
+fn apply_settings(ctx: &egui::Context, settings: &Settings, zoom: f32) {
+    let font = &settings.font;
+    let mut style = (*ctx.style()).clone();
+    for (text_style, size) in [
+        (egui::TextStyle::Monospace, font.size * zoom),
+        (egui::TextStyle::Body, font.ui_size),
+        (egui::TextStyle::Button, font.ui_size),
+        (egui::TextStyle::Small, font.ui_size * 0.72),
+        (egui::TextStyle::Heading, font.ui_size * 1.44),
+    ] {
+        if let Some(font_id) = style.text_styles.get_mut(&text_style) {
+            font_id.size = size;
+        }
+    }
+    ctx.set_style(style);
+}
+
+/// Loads the font files of the settings in front of the built-in fonts.
+/// Returns why a file could not be loaded.
+fn apply_fonts(ctx: &egui::Context, font: &FontSettings) -> Vec<String> {
+    let mut fonts = egui::FontDefinitions::default();
+    let mut problems = Vec::new();
+    for (path, family) in [
+        (&font.path, egui::FontFamily::Monospace),
+        (&font.ui_path, egui::FontFamily::Proportional),
+    ] {
+        if path.is_empty() {
+            continue;
+        }
+        match fs::read(path) {
+            Ok(data) => {
+                fonts
+                    .font_data
+                    .insert(path.clone(), egui::FontData::from_owned(data));
+                fonts
+                    .families
+                    .entry(family)
+                    .or_default()
+                    .insert(0, path.clone());
+            }
+            Err(err) => problems.push(format!("settings: {path}: {err}")),
+        }
     }
+    ctx.set_fonts(fonts);
+    problems
 }
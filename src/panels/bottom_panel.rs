@@ -1,39 +1,226 @@
-use crate::{terminal::TermHandler, Project};
-use eframe::egui::{self, Ui};
-use portable_pty::CommandBuilder;
+use crate::{indent::Indent, keymap::Keymap, vim::Mode, workspace, Project};
+use eframe::egui::{self, RichText, Ui};
 
 pub fn init(ui: &mut Ui, project: &mut Project) {
     ui.vertical(|ui| {
         ui.add_space(5.0);
         ui.horizontal(|ui| {
             ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
-                let binding = project.project_path.clone().unwrap_or_default();
-                let header_text = binding.file_name().unwrap_or_default().to_string_lossy();
+                // the workspace file or the project
+                let binding = project
+                    .workspace
+                    .file
+                    .as_ref()
+                    .and_then(|file| file.file_stem())
+                    .or(project
+                        .project_path
+                        .as_ref()
+                        .and_then(|path| path.file_name()))
+                    .unwrap_or_default()
+                    .to_owned();
+                let header_text = binding.to_string_lossy();
                 egui::ComboBox::from_label(header_text)
                     .selected_text("Editor")
                     .show_ui(ui, |ui| {
                         if ui.selectable_label(false, "Open New Project...").clicked() {
                             project.open_project();
                         }
+                        if ui.selectable_label(false, "Open Workspace...").clicked() {
+                            project.open_workspace();
+                        }
+                        if ui
+                            .selectable_label(false, "Add Folder to Workspace...")
+                            .clicked()
+                        {
+                            project.add_folder();
+                        }
+                        if ui.selectable_label(false, "Save Workspace").clicked() {
+                            project.save_workspace();
+                        }
+                        let current = project.session_key();
+                        let recent = project
+                            .recent
+                            .iter()
+                            .filter(|path| Some(*path) != current.as_ref())
+                            .cloned()
+                            .collect::<Vec<_>>();
+                        if !recent.is_empty() {
+                            ui.separator();
+                            ui.label(RichText::new("Recent Projects").weak());
+                            for path in recent {
+                                let name = if path
+                                    .extension()
+                                    .is_some_and(|ext| ext == workspace::EXTENSION)
+                                {
+                                    path.file_stem()
+                                } else {
+                                    path.file_name()
+                                };
+                                if ui
+                                    .selectable_label(
+                                        false,
+                                        name.unwrap_or_default().to_string_lossy(),
+                                    )
+                                    .on_hover_text(path.display().to_string())
+                                    .clicked()
+                                {
+                                    project.open_recent(&path);
+                                }
+                            }
+                            ui.separator();
+                        }
                         if ui.selectable_label(false, "Clear Cache").clicked() {
-                            *project = Project::default();
+                            project.clear_cache();
+                        }
+                        if ui
+                            .selectable_label(project.vim.enabled, "Vim Mode")
+                            .clicked()
+                        {
+                            project.vim.toggle();
+                        }
+                        if ui
+                            .selectable_label(
+                                !project.file_tree.skip_delete_confirmation,
+                                "Confirm Delete",
+                            )
+                            .clicked()
+                        {
+                            project.file_tree.skip_delete_confirmation ^= true;
+                        }
+                        if ui.selectable_label(false, "Settings...").clicked() {
+                            project.settings_editor.toggle(&project.config.settings);
+                        }
+                        if ui.selectable_label(false, "Edit Keymap").clicked() {
+                            project.edit_keymap();
+                        }
+                        if ui.selectable_label(false, "Reload Keymap").clicked() {
+                            project.keymap = Keymap::load();
+                        }
+                        if ui.selectable_label(false, "Edit Snippets").clicked() {
+                            project.edit_snippets();
                         }
                     });
 
+                if !project.keymap.problems.is_empty() {
+                    ui.label(
+                        RichText::new(format!(
+                            "⚠ {} keymap problems",
+                            project.keymap.problems.len()
+                        ))
+                        .color(ui.visuals().warn_fg_color),
+                    )
+                    .on_hover_text(project.keymap.problems.join("\n"));
+                }
+
+                if !project.config.problems.is_empty() {
+                    ui.label(
+                        RichText::new(format!(
+                            "⚠ {} settings problems",
+                            project.config.problems.len()
+                        ))
+                        .color(ui.visuals().warn_fg_color),
+                    )
+                    .on_hover_text(project.config.problems.join("\n"));
+                }
+
+                if !project.snippets.problems.is_empty() {
+                    ui.label(
+                        RichText::new(format!(
+                            "⚠ {} snippet problems",
+                            project.snippets.problems.len()
+                        ))
+                        .color(ui.visuals().warn_fg_color),
+                    )
+                    .on_hover_text(project.snippets.problems.join("\n"));
+                }
+
+                if let Some(err) = &project.format_error {
+                    let label = ui
+                        .label(RichText::new("⚠ Format failed").color(ui.visuals().warn_fg_color))
+                        .on_hover_text(format!("{err}\n\nClick to dismiss"));
+                    if label.interact(egui::Sense::click()).clicked() {
+                        project.format_error = None;
+                    }
+                }
+
+                if project.vim.enabled {
+                    let vim = &project.vim;
+                    let status = if vim.mode == Mode::Command {
+                        format!(":{}", vim.command_line)
+                    } else if let Some(message) = &vim.message {
+                        message.clone()
+                    } else {
+                        format!("-- {} -- {}", vim.mode.label(), vim.pending_keys())
+                    };
+                    ui.label(RichText::new(status).monospace());
+                }
+
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     if ui
                         .selectable_label(project.get_current_terminal().is_some(), "Terminal")
                         .clicked()
                     {
-                        if project.get_current_terminal().is_some() {
-                            project
-                                .remove_terminal(&project.project_path.clone().unwrap_or_default());
-                        } else {
-                            let mut cmd = CommandBuilder::new_default_prog();
-                            let path = project.project_path.clone().unwrap_or_default();
-                            cmd.cwd(&path);
-                            let term = TermHandler::new(cmd);
-                            project.terminals.insert(path, term);
+                        project.toggle_terminal();
+                    }
+                    // every folder of a workspace has its own terminal
+                    let roots = project.roots();
+                    if roots.len() > 1 && project.get_current_terminal().is_some() {
+                        let current = project.current_terminal_root();
+                        let name = |root: &std::path::Path| {
+                            root.file_name()
+                                .unwrap_or_default()
+                                .to_string_lossy()
+                                .to_string()
+                        };
+                        egui::ComboBox::from_id_source("terminal_root")
+                            .selected_text(name(&current))
+                            .show_ui(ui, |ui| {
+                                for root in &roots {
+                                    if ui.selectable_label(*root == current, name(root)).clicked() {
+                                        project.open_terminal(root);
+                                    }
+                                }
+                            });
+                    }
+
+                    // indentation of the current file
+                    let current_file = project.current_file.clone().unwrap_or_default();
+                    if let Some(file) = project.files.get_mut(&current_file) {
+                        let indent = file.indent();
+                        let mut convert = None;
+                        egui::ComboBox::from_id_source("indentation")
+                            .selected_text(indent.label())
+                            .show_ui(ui, |ui| {
+                                for choice in [
+                                    Indent::Tabs,
+                                    Indent::Spaces(2),
+                                    Indent::Spaces(4),
+                                    Indent::Spaces(8),
+                                ] {
+                                    if ui
+                                        .selectable_label(indent == choice, choice.label())
+                                        .clicked()
+                                    {
+                                        file.indent_override = Some(choice);
+                                    }
+                                }
+                                if ui
+                                    .selectable_label(false, "Detect from Content")
+                                    .on_hover_text("Forget the picked indentation")
+                                    .clicked()
+                                {
+                                    file.indent_override = None;
+                                }
+                                ui.separator();
+                                if ui.selectable_label(false, "Convert to Spaces").clicked() {
+                                    convert = Some(false);
+                                }
+                                if ui.selectable_label(false, "Convert to Tabs").clicked() {
+                                    convert = Some(true);
+                                }
+                            });
+                        if let Some(to_tabs) = convert {
+                            project.convert_indentation(to_tabs);
                         }
                     }
                 });
@@ -0,0 +1,322 @@
+use std::cmp::Reverse;
+
+use eframe::egui::{
+    self, text::LayoutJob, Context, Key, Modifiers, TextEdit, TextFormat, TextStyle, Ui,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{actions::Action, fuzzy::fuzzy_match, symbols::Symbol, Project};
+
+const MAX_RECENT: usize = 10;
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct CommandPalette {
+    #[serde(skip)]
+    pub open: bool,
+    #[serde(skip)]
+    query: String,
+    #[serde(skip)]
+    selected: usize,
+    /// An action waiting for a name, e.g. `New File...`.
+    #[serde(skip)]
+    prompt: Option<Action>,
+    /// Lists these instead of the actions.
+    #[serde(skip)]
+    symbols: Option<Vec<Symbol>>,
+    #[serde(skip)]
+    error: Option<String>,
+    #[serde(skip)]
+    refocus_editor: bool,
+    /// Most recently used first.
+    recent: Vec<Action>,
+}
+
+impl CommandPalette {
+    pub fn toggle(&mut self, editor_focused: bool) {
+        if self.open {
+            self.close();
+        } else {
+            self.show(None, String::new());
+            self.refocus_editor = editor_focused;
+        }
+    }
+
+    /// Asks for the name `action` needs.
+    pub fn prompt(&mut self, action: Action, initial: String, editor_focused: bool) {
+        self.show(Some(action), initial);
+        self.refocus_editor = editor_focused;
+    }
+
+    /// Lists the symbols of a file to jump to.
+    pub fn show_symbols(&mut self, symbols: Vec<Symbol>, editor_focused: bool) {
+        self.show(None, String::new());
+        self.symbols = Some(symbols);
+        self.refocus_editor = editor_focused;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.prompt = None;
+        self.symbols = None;
+    }
+
+    /// Whether the editor should get the focus back after the palette closed.
+    pub fn take_refocus(&mut self) -> bool {
+        !self.open && std::mem::take(&mut self.refocus_editor)
+    }
+
+    fn show(&mut self, prompt: Option<Action>, query: String) {
+        self.open = true;
+        self.prompt = prompt;
+        self.symbols = None;
+        self.query = query;
+        self.selected = 0;
+        self.error = None;
+    }
+
+    fn used(&mut self, action: Action) {
+        self.recent.retain(|recent| *recent != action);
+        self.recent.insert(0, action);
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    /// Matching entries, best first.
+    fn entries(&self) -> Vec<Entry> {
+        let mut entries = match &self.symbols {
+            Some(symbols) => symbols
+                .iter()
+                .enumerate()
+                .filter_map(|(i, symbol)| {
+                    let found = fuzzy_match(&self.query, &symbol.name)?;
+                    // keep the order of the file without a query
+                    let score = if self.query.is_empty() {
+                        0
+                    } else {
+                        found.score
+                    };
+                    Some((Entry::Symbol(i, found.indices), score, i))
+                })
+                .collect::<Vec<_>>(),
+            None => Action::ALL
+                .iter()
+                .filter(|action| **action != Action::CommandPalette)
+                .filter_map(|action| {
+                    let found = fuzzy_match(&self.query, action.title())?;
+                    let score = if self.query.is_empty() {
+                        0
+                    } else {
+                        found.score
+                    };
+                    let recency = self.recent.iter().position(|recent| recent == action);
+                    Some((
+                        Entry::Action(*action, found.indices),
+                        score,
+                        recency.unwrap_or(usize::MAX),
+                    ))
+                })
+                .collect::<Vec<_>>(),
+        };
+        entries.sort_by_key(|(_, score, order)| (Reverse(*score), *order));
+        entries.into_iter().map(|(entry, ..)| entry).collect()
+    }
+}
+
+/// A row of the palette with the indices of the matched characters.
+enum Entry {
+    Action(Action, Vec<usize>),
+    /// Index into the listed symbols.
+    Symbol(usize, Vec<usize>),
+}
+
+/// Shows the palette, returns the chosen action.
+pub fn init(ctx: &Context, project: &mut Project) -> Option<Action> {
+    if !project.palette.open {
+        return None;
+    }
+
+    let line_count = project
+        .get_current_file()
+        .map(|file| file.text.split('\n').count())
+        .unwrap_or_default();
+    let entries = project.palette.entries();
+    let mut chosen = None;
+    let mut submitted = None;
+    let area = egui::Area::new(egui::Id::new("command_palette"))
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 40.0))
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.set_width(450.0);
+                let palette = &mut project.palette;
+
+                if ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Escape)) {
+                    palette.close();
+                    return;
+                }
+
+                if let Some(action) = palette.prompt {
+                    let hint = match action {
+                        Action::GoToLine => format!("Line[:Column], 1 - {line_count}"),
+                        _ => action.title().trim_end_matches("...").to_string(),
+                    };
+                    let input = ui.add(
+                        TextEdit::singleline(&mut palette.query)
+                            .hint_text(hint)
+                            .desired_width(f32::INFINITY),
+                    );
+                    input.request_focus();
+                    if ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Enter)) {
+                        submitted = Some((action, palette.query.clone()));
+                    }
+                    if let Some(error) = &palette.error {
+                        ui.colored_label(ui.visuals().error_fg_color, error);
+                    }
+                    return;
+                }
+
+                let mut moved = false;
+                ui.input_mut(|i| {
+                    if i.consume_key(Modifiers::NONE, Key::ArrowDown) {
+                        palette.selected += 1;
+                        moved = true;
+                    }
+                    if i.consume_key(Modifiers::NONE, Key::ArrowUp) {
+                        palette.selected = palette.selected.saturating_sub(1);
+                        moved = true;
+                    }
+                });
+                palette.selected = palette.selected.min(entries.len().saturating_sub(1));
+                let enter = ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Enter));
+
+                let input = ui.add(
+                    TextEdit::singleline(&mut palette.query)
+                        .hint_text(if palette.symbols.is_some() {
+                            "Go to symbol"
+                        } else {
+                            "Type a command"
+                        })
+                        .desired_width(f32::INFINITY),
+                );
+                input.request_focus();
+                if input.changed() {
+                    palette.selected = 0;
+                }
+
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        ui.with_layout(egui::Layout::top_down_justified(egui::Align::Min), |ui| {
+                            for (i, entry) in entries.iter().enumerate() {
+                                let (title, detail, indices) = match entry {
+                                    Entry::Action(action, indices) => (
+                                        action.title(),
+                                        project.keymap.shortcut_text(ui.ctx(), *action),
+                                        indices,
+                                    ),
+                                    Entry::Symbol(symbol, indices) => {
+                                        let symbol = &palette.symbols.as_ref().unwrap()[*symbol];
+                                        (
+                                            symbol.name.as_str(),
+                                            Some(format!("{} :{}", symbol.kind, symbol.line + 1)),
+                                            indices,
+                                        )
+                                    }
+                                };
+                                let row = ui.horizontal(|ui| {
+                                    let label = ui.selectable_label(
+                                        i == palette.selected,
+                                        highlighted(ui, title, indices),
+                                    );
+                                    if let Some(detail) = detail {
+                                        ui.with_layout(
+                                            egui::Layout::right_to_left(egui::Align::Center),
+                                            |ui| ui.weak(detail),
+                                        );
+                                    }
+                                    label
+                                });
+                                if row.inner.clicked() {
+                                    chosen = Some(i);
+                                }
+                                if moved && i == palette.selected {
+                                    row.inner.scroll_to_me(None);
+                                }
+                            }
+                            if entries.is_empty() {
+                                ui.weak(if palette.symbols.is_some() {
+                                    "No symbols"
+                                } else {
+                                    "No matching commands"
+                                });
+                            }
+                        });
+                    });
+
+                if enter && !entries.is_empty() {
+                    chosen = Some(palette.selected);
+                }
+            });
+        });
+    if area.response.clicked_elsewhere() {
+        project.palette.close();
+    }
+
+    if let Some((action, input)) = submitted {
+        let result = match action {
+            Action::NewFile => project.new_file(&input),
+            Action::NewDirectory => project.new_directory(&input),
+            Action::RenameFile => project.rename_current_file(&input),
+            Action::GoToLine => project.go_to_position(&input),
+            _ => Ok(()),
+        };
+        match result {
+            Ok(()) => project.palette.close(),
+            Err(err) => project.palette.error = Some(err.to_string()),
+        }
+    }
+
+    match chosen.and_then(|i| entries.into_iter().nth(i)) {
+        Some(Entry::Action(action, _)) => {
+            project.palette.used(action);
+            project.palette.close();
+            Some(action)
+        }
+        Some(Entry::Symbol(symbol, _)) => {
+            let symbols = project.palette.symbols.take().unwrap_or_default();
+            project.palette.close();
+            if let Some(symbol) = symbols.get(symbol) {
+                project.go_to(symbol.line, symbol.column);
+            }
+            None
+        }
+        None => None,
+    }
+}
+
+/// `text` with the characters at `indices` highlighted.
+pub fn highlighted(ui: &Ui, text: &str, indices: &[usize]) -> LayoutJob {
+    let font_id = TextStyle::Body.resolve(ui.style());
+    let mut job = LayoutJob::default();
+    for (i, c) in text.chars().enumerate() {
+        let color = if indices.contains(&i) {
+            ui.visuals().strong_text_color()
+        } else {
+            ui.visuals().text_color()
+        };
+        job.append(
+            c.encode_utf8(&mut [0; 4]),
+            0.0,
+            TextFormat {
+                font_id: font_id.clone(),
+                color,
+                underline: if indices.contains(&i) {
+                    ui.visuals().widgets.active.fg_stroke
+                } else {
+                    egui::Stroke::NONE
+                },
+                ..Default::default()
+            },
+        );
+    }
+    job
+}
@@ -0,0 +1,71 @@
+use eframe::egui::{self, Color32, RichText, Ui};
+use similar::ChangeTag;
+
+use crate::Project;
+
+pub fn init(ui: &mut Ui, project: &mut Project) {
+    let Some(diff) = &project.diff else {
+        return;
+    };
+    let mut close = false;
+    ui.vertical(|ui| {
+        ui.horizontal(|ui| {
+            let (inserted, deleted) = diff.stats();
+            ui.label(
+                RichText::new(format!(
+                    "{} ↔ {}",
+                    diff.left.display(),
+                    diff.right.display()
+                ))
+                .strong(),
+            );
+            ui.label(RichText::new(format!("+{inserted}")).color(Color32::GREEN));
+            ui.label(RichText::new(format!("-{deleted}")).color(Color32::RED));
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                close = ui.button("✖").on_hover_text("Close Diff").clicked();
+            });
+        });
+        ui.separator();
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let width = diff
+            .lines
+            .iter()
+            .filter_map(|line| line.left.max(line.right))
+            .max()
+            .unwrap_or_default()
+            .to_string()
+            .len();
+        egui::ScrollArea::both().auto_shrink(false).show_rows(
+            ui,
+            row_height,
+            diff.lines.len(),
+            |ui, range| {
+                for line in &diff.lines[range] {
+                    let number = |n: Option<usize>| {
+                        n.map(|n| format!("{n:>width$}"))
+                            .unwrap_or_else(|| " ".repeat(width))
+                    };
+                    let (sign, color) = match line.tag {
+                        ChangeTag::Equal => (' ', ui.visuals().text_color()),
+                        ChangeTag::Insert => ('+', Color32::GREEN),
+                        ChangeTag::Delete => ('-', Color32::RED),
+                    };
+                    ui.label(
+                        RichText::new(format!(
+                            "{} {} {sign} {}",
+                            number(line.left),
+                            number(line.right),
+                            line.text
+                        ))
+                        .monospace()
+                        .color(color),
+                    );
+                }
+            },
+        );
+    });
+    if close {
+        project.diff = None;
+    }
+}
@@ -1,15 +1,25 @@
 use std::{
-    ffi::OsStr,
-    fs, io,
+    fs,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
 use eframe::{
-    egui::{self, Key, Modifiers, Response, RichText, Sense, TextEdit, Ui},
+    egui::{
+        self,
+        text::{CCursor, CCursorRange, LayoutJob},
+        text_edit::TextEditState,
+        Key, Modifiers, Response, RichText, Sense, TextEdit, TextFormat, Ui,
+    },
     epaint::Color32,
 };
 
-use crate::{code_editor::FileData, Project};
+use crate::{
+    file_ops::{self, FileOp},
+    file_tree::{ClipboardMode, Conflict, EntryKind, Paste, Row, SortBy},
+    keymap::Focus,
+    Project,
+};
 
 #[derive(PartialEq)]
 enum EntryType {
@@ -18,148 +28,792 @@ enum EntryType {
     File,
 }
 
+const INDENT: f32 = 12.0;
+
+/// Paths dragged inside the tree.
+struct DraggedPaths(Vec<PathBuf>);
+
 pub fn init(ui: &mut Ui, project: &mut Project) {
     ui.add_space(5.0);
-    egui::ScrollArea::vertical()
-        .auto_shrink(true)
-        .max_height(ui.available_height() - 3.0)
-        .show(ui, |ui| {
-            ui.with_layout(egui::Layout::top_down_justified(egui::Align::Min), |ui| {
-                if let Some(path) = project.project_path.clone() {
-                    file_side_bar(ui, &path, project).unwrap_or_default();
-                    let response =
-                        ui.allocate_response(ui.available_size(), egui::Sense::click_and_drag());
-
-                    response.context_menu(|ui| {
-                        ctx_menu(
-                            ui,
-                            project,
-                            project.project_path.clone().unwrap_or_default(),
-                            &project
-                                .project_path
-                                .clone()
-                                .unwrap_or_default()
-                                .file_name()
-                                .unwrap_or_default()
-                                .to_string_lossy(),
-                            EntryType::Root,
-                            &response,
-                        )
-                    });
-                } else {
-                    ui.label("No Project Opened...");
+    let roots = project.roots();
+    let Some(root) = roots.first().cloned() else {
+        ui.label("No Project Opened...");
+        return;
+    };
+    project.file_tree.sync(ui.ctx(), &roots);
+
+    let mut filter = project.file_tree.filter();
+    ui.horizontal(|ui| {
+        ui.toggle_value(&mut filter.show_hidden, "Hidden")
+            .on_hover_text("Show hidden files");
+        ui.toggle_value(&mut filter.show_ignored, "Ignored")
+            .on_hover_text("Show ignored files dimmed");
+        let undo = project
+            .file_tree
+            .undo_title()
+            .map(|title| format!("Undo {title}"));
+        let undo_button = ui
+            .add_enabled(undo.is_some(), egui::Button::new("⟲"))
+            .on_hover_text(undo.unwrap_or_default());
+        if undo_button.clicked() {
+            if let Err(err) = project.undo_file_operation() {
+                project.file_tree.error = Some(err.to_string());
+            }
+        }
+        if ui
+            .add_enabled(project.current_file.is_some(), egui::Button::new("◎"))
+            .on_hover_text("Reveal Active File")
+            .clicked()
+        {
+            if let Some(current_file) = &project.current_file {
+                project.file_tree.reveal(current_file);
+            }
+        }
+        let mut view = project.file_tree.view();
+        ui.menu_button("Sort", |ui| {
+            ui.radio_value(&mut view.sort, SortBy::Name, "Name");
+            ui.radio_value(&mut view.sort, SortBy::Type, "Type");
+            ui.radio_value(&mut view.sort, SortBy::Modified, "Modified");
+            ui.radio_value(&mut view.sort, SortBy::Size, "Size");
+            ui.separator();
+            ui.checkbox(&mut view.details, "Details on Hover");
+            ui.checkbox(&mut view.nesting, "File Nesting");
+            ui.add_enabled_ui(view.nesting, |ui| {
+                ui.label("Nesting rules, `parent: child, child` per line");
+                let mut rules = view.nesting_rules.join("\n");
+                if ui
+                    .add(TextEdit::multiline(&mut rules).hint_text("*.ts: $(capture).js"))
+                    .changed()
+                {
+                    view.nesting_rules = rules.split('\n').map(str::to_string).collect();
                 }
             });
         });
-}
-
-fn file_side_bar(ui: &mut Ui, path: &PathBuf, project: &mut Project) -> io::Result<()> {
-    let mut entries = fs::read_dir(path)?
-        .map(|res| res.map(|e| e.path()))
-        .collect::<Result<Vec<_>, io::Error>>()?;
+        project.file_tree.set_view(view);
+        ui.menu_button("Exclude", |ui| {
+            ui.label("Gitignore patterns, one per line");
+            let mut patterns = filter.exclude.join("\n");
+            if ui
+                .add(TextEdit::multiline(&mut patterns).hint_text("target/\n*.log"))
+                .changed()
+            {
+                filter.exclude = patterns.split('\n').map(str::to_string).collect();
+            }
+        });
+    });
+    project.file_tree.set_filter(filter);
 
-    entries.sort_by(|a, b| {
-        let a_metadata = a.metadata().expect("Failed to get metadata for a");
-        let b_metadata = b.metadata().expect("Failed to get metadata for b");
+    if let Some(error) = project.file_tree.error.clone() {
+        ui.horizontal(|ui| {
+            ui.colored_label(ui.visuals().error_fg_color, format!("⚠ {error}"));
+            if ui.small_button("✖").clicked() {
+                project.file_tree.error = None;
+            }
+        });
+    }
 
-        let a_is_dir = a_metadata.is_dir();
-        let b_is_dir = b_metadata.is_dir();
+    // keeps the keyboard focus while the tree is shown
+    let tree = ui.interact(ui.max_rect(), tree_id(), Sense::focusable_noninteractive());
+    if tree.has_focus() {
+        project.focus = Focus::Tree;
+        ui.memory_mut(|m| {
+            m.set_focus_lock_filter(
+                tree.id,
+                egui::EventFilter {
+                    vertical_arrows: true,
+                    horizontal_arrows: true,
+                    ..Default::default()
+                },
+            )
+        });
+        keyboard(ui, project);
+    }
 
-        if a_is_dir && !b_is_dir {
-            std::cmp::Ordering::Less
-        } else if !a_is_dir && b_is_dir {
-            std::cmp::Ordering::Greater
-        } else {
-            a.file_name().unwrap().cmp(b.file_name().unwrap())
+    let row_height = ui.spacing().interact_size.y;
+    let row_count = project.file_tree.rows().len();
+    let visible_id = egui::Id::new("file_tree_visible");
+    let mut area = egui::ScrollArea::vertical()
+        .auto_shrink(true)
+        .max_height(ui.available_height() - 3.0);
+    if let Some(scroll_to) = project.file_tree.scroll_to.clone() {
+        match project
+            .file_tree
+            .rows()
+            .iter()
+            .position(|row| row.path == scroll_to)
+        {
+            Some(index) => {
+                let visible: std::ops::Range<usize> =
+                    ui.data(|d| d.get_temp(visible_id)).unwrap_or_default();
+                // rows out of view are not drawn and cannot scroll to themselves
+                if !visible.contains(&index) {
+                    let offset = index as f32 * (row_height + ui.spacing().item_spacing.y);
+                    area = area.vertical_scroll_offset(offset - ui.available_height() / 2.0);
+                }
+            }
+            None => project.file_tree.scroll_to = None,
         }
+    }
+    area.show_rows(ui, row_height, row_count, |ui, range| {
+        ui.data_mut(|d| d.insert_temp(visible_id, range.clone()));
+        ui.with_layout(egui::Layout::top_down_justified(egui::Align::Min), |ui| {
+            // only the visible rows are drawn
+            let rows = project.file_tree.rows()[range].to_vec();
+            for row in &rows {
+                entry_row(ui, project, row);
+            }
+        });
     });
 
-    for entry in entries {
-        let metadata = fs::metadata(&entry).expect("Unable to read metadata");
-        let file_name = &*entry.file_name().unwrap_or_default().to_string_lossy();
+    let response = ui.allocate_response(ui.available_size(), egui::Sense::click_and_drag());
+    drop_target(ui, project, &response, &root);
+    // files from the file manager dropped anywhere else on the panel
+    if ui.input(|i| !i.raw.dropped_files.is_empty()) && ui.rect_contains_pointer(ui.max_rect()) {
+        drop_files(ui, project, &root);
+    }
+    if let Some(dragged) = egui::DragAndDrop::payload::<DraggedPaths>(ui.ctx()) {
+        let names = dragged
+            .0
+            .iter()
+            .map(|path| path.file_name().unwrap_or_default().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(", ");
+        egui::show_tooltip_at_pointer(ui.ctx(), egui::Id::new("tree_drag"), |ui| ui.label(names));
+    }
 
-        if metadata.is_dir() {
-            let id = ui.make_persistent_id(&*entry);
-            let collapse = egui::collapsing_header::CollapsingState::load_with_default_open(
-                ui.ctx(),
-                id,
-                false,
-            );
-            let header = collapse.show_header(ui, |ui| {
-                ui.with_layout(egui::Layout::top_down_justified(egui::Align::Min), |ui| {
-                    let select = ui
-                        .selectable_label(false, file_name)
-                        .interact(Sense::click_and_drag());
-                    select.context_menu(|ui| {
-                        ctx_menu(
-                            ui,
-                            project,
-                            entry.to_path_buf(),
-                            file_name,
-                            EntryType::Directory,
-                            &select,
-                        )
-                    });
-                    if select.dragged() {
-                        println!("dragging");
-                    }
-                });
-            });
-            header.body(|ui| {
-                ui.with_layout(egui::Layout::top_down_justified(egui::Align::Min), |ui| {
-                    file_side_bar(ui, &entry, project)
+    conflict_dialog(ui.ctx(), project);
+    delete_dialog(ui.ctx(), project);
+
+    response.context_menu(|ui| {
+        ctx_menu(
+            ui,
+            project,
+            root.clone(),
+            &root.file_name().unwrap_or_default().to_string_lossy(),
+            EntryType::Root,
+            &response,
+        )
+    });
+}
+
+/// Arrow keys, enter, F2, delete and type-to-select while the tree is focused.
+fn keyboard(ui: &Ui, project: &mut Project) {
+    let (events, time) = ui.input(|i| (i.events.clone(), i.time));
+    for event in events {
+        match event {
+            egui::Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+                ..
+            } => {
+                let cursor = project.file_tree.cursor().map(Path::to_path_buf);
+                let row = cursor.as_ref().and_then(|cursor| {
+                    project
+                        .file_tree
+                        .rows()
+                        .iter()
+                        .find(|row| row.path == *cursor)
+                        .cloned()
                 });
-            });
+                match key {
+                    Key::ArrowUp => project.file_tree.move_cursor(-1, modifiers.shift),
+                    Key::ArrowDown => project.file_tree.move_cursor(1, modifiers.shift),
+                    Key::PageUp => project.file_tree.move_cursor(-20, modifiers.shift),
+                    Key::PageDown => project.file_tree.move_cursor(20, modifiers.shift),
+                    Key::Home => project.file_tree.move_cursor(isize::MIN, modifiers.shift),
+                    Key::End => project.file_tree.move_cursor(isize::MAX, modifiers.shift),
+                    Key::ArrowRight => match &row {
+                        Some(row)
+                            if row.is_expandable() && !project.file_tree.is_expanded(&row.path) =>
+                        {
+                            project.file_tree.toggle(&row.path)
+                        }
+                        Some(row) if row.is_expandable() => project.file_tree.move_cursor(1, false),
+                        _ => {}
+                    },
+                    Key::ArrowLeft => match &row {
+                        Some(row)
+                            if row.is_expandable() && project.file_tree.is_expanded(&row.path) =>
+                        {
+                            project.file_tree.toggle(&row.path)
+                        }
+                        Some(row) => {
+                            // the closest row above which is less indented
+                            let rows = project.file_tree.rows();
+                            let index = rows.iter().position(|other| other.path == row.path);
+                            let parent = rows[..index.unwrap_or_default()]
+                                .iter()
+                                .rev()
+                                .find(|other| other.depth < row.depth)
+                                .map(|parent| parent.path.clone());
+                            if let Some(parent) = parent {
+                                project.file_tree.select(&parent);
+                                project.file_tree.scroll_to = Some(parent);
+                            }
+                        }
+                        None => {}
+                    },
+                    Key::Enter => match &row {
+                        Some(row) if row.is_dir() => project.file_tree.toggle(&row.path),
+                        Some(row) if row.kind == EntryKind::File => {
+                            project.open_file(row.path.clone())
+                        }
+                        _ => {}
+                    },
+                    Key::F2 => match &row {
+                        Some(row) if !row.root => project.file_tree.start_rename(&row.path),
+                        _ => {}
+                    },
+                    Key::Delete => {
+                        let selection = project.file_tree.selection().to_vec();
+                        if !selection.is_empty() {
+                            delete(project, selection);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            egui::Event::Text(text) => project.file_tree.type_to_select(&text, time),
+            _ => {}
         }
+    }
+}
 
-        if metadata.is_file() {
-            let text = if project.is_file_edited(&entry) {
-                RichText::new(file_name.to_string() + " *").color(Color32::WHITE)
+/// The inline editor of an entry being renamed.
+fn rename_row(ui: &mut Ui, project: &mut Project) {
+    let Some(rename) = &mut project.file_tree.rename else {
+        return;
+    };
+    let id = ui.id().with("rename");
+    let input = ui.add(TextEdit::singleline(&mut rename.name).id(id));
+    if std::mem::take(&mut rename.focus) {
+        input.request_focus();
+        // select the name without the extension
+        let stem = Path::new(&rename.name)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .chars()
+            .count();
+        let mut state = TextEditState::load(ui.ctx(), id).unwrap_or_default();
+        state
+            .cursor
+            .set_char_range(Some(CCursorRange::two(CCursor::new(0), CCursor::new(stem))));
+        state.store(ui.ctx(), id);
+        return;
+    }
+
+    let checked = file_ops::check_name(&rename.path, &rename.name);
+    if let Err(err) = &checked {
+        egui::show_tooltip_for(ui.ctx(), id.with("error"), &input.rect, |ui| {
+            ui.colored_label(ui.visuals().error_fg_color, err)
+        });
+    }
+    if input.has_focus() {
+        return;
+    }
+    if ui.input(|i| i.key_pressed(Key::Enter)) {
+        if checked.is_err() {
+            input.request_focus();
+            return;
+        }
+        let from = rename.path.clone();
+        let to = from.with_file_name(&rename.name);
+        if from != to {
+            match project.rename_path(&from, &to) {
+                Ok(()) => project
+                    .file_tree
+                    .record("Rename", vec![FileOp::Moved { from, to }]),
+                Err(err) => project.file_tree.error = Some(err.to_string()),
+            }
+        }
+    }
+    // escape or clicking elsewhere cancels
+    project.file_tree.rename = None;
+    ui.memory_mut(|m| m.request_focus(tree_id()));
+}
+
+fn tree_id() -> egui::Id {
+    egui::Id::new("file_tree")
+}
+
+fn entry_row(ui: &mut Ui, project: &mut Project, row: &Row) {
+    ui.horizontal(|ui| {
+        ui.add_space(row.depth as f32 * INDENT);
+        ui.with_layout(egui::Layout::top_down_justified(egui::Align::Min), |ui| {
+            if project
+                .file_tree
+                .rename
+                .as_ref()
+                .is_some_and(|rename| rename.path == row.path)
+            {
+                rename_row(ui, project);
+                return;
+            }
+            let selected = project.file_tree.is_selected(&row.path);
+            let (select, entry_type) = match row.kind {
+                EntryKind::Error => {
+                    ui.colored_label(ui.visuals().error_fg_color, format!("⚠ {}", row.name));
+                    return;
+                }
+                EntryKind::Directory => {
+                    let icon = if project.file_tree.is_expanded(&row.path) {
+                        "⏷"
+                    } else {
+                        "⏵"
+                    };
+                    let text = RichText::new(format!("{icon} {}{}", row.name, link_marker(row)));
+                    let text = if row.ignored { text.weak() } else { text };
+                    if row.root {
+                        let select = ui
+                            .selectable_label(selected, text.strong())
+                            .interact(Sense::click())
+                            .on_hover_text(row.path.display().to_string());
+                        (select, EntryType::Root)
+                    } else {
+                        let select = ui
+                            .selectable_label(selected, text)
+                            .interact(Sense::click_and_drag());
+                        (select, EntryType::Directory)
+                    }
+                }
+                EntryKind::File => {
+                    let mut job = LayoutJob::default();
+                    let font_id = egui::TextStyle::Body.resolve(ui.style());
+                    let format = |color| TextFormat::simple(font_id.clone(), color);
+                    if row.nests {
+                        let arrow = if project.file_tree.is_expanded(&row.path) {
+                            "⏷ "
+                        } else {
+                            "⏵ "
+                        };
+                        job.append(arrow, 0.0, format(ui.visuals().text_color()));
+                    }
+                    let (icon, icon_color) = file_icon(&row.name);
+                    job.append(icon, 0.0, format(icon_color));
+                    let mut name = format!(" {}{}", row.name, link_marker(row));
+                    let mut color = ui.visuals().text_color();
+                    if project.is_file_edited(&row.path) {
+                        name += " *";
+                        color = Color32::WHITE;
+                    }
+                    if row.ignored {
+                        color = ui.visuals().weak_text_color();
+                    }
+                    job.append(&name, 0.0, format(color));
+                    let current = project.current_file.as_ref() == Some(&row.path);
+                    let select = ui
+                        .selectable_label(selected || current, job)
+                        .interact(Sense::click_and_drag());
+                    (select, EntryType::File)
+                }
+                EntryKind::Broken => {
+                    let text = RichText::new(row.name.clone() + link_marker(row))
+                        .color(ui.visuals().error_fg_color)
+                        .strikethrough();
+                    let select = ui
+                        .selectable_label(selected, text)
+                        .interact(Sense::click_and_drag());
+                    (select, EntryType::File)
+                }
+                EntryKind::Special => {
+                    let text = RichText::new(row.name.clone() + link_marker(row)).weak();
+                    let select = ui
+                        .selectable_label(selected, text)
+                        .interact(Sense::click_and_drag())
+                        .on_hover_text("Not a regular file");
+                    (select, EntryType::File)
+                }
+            };
+            if project.file_tree.scroll_to.as_ref() == Some(&row.path) {
+                select.scroll_to_me(None);
+                project.file_tree.scroll_to = None;
+            }
+            if select.clicked() || select.secondary_clicked() {
+                ui.memory_mut(|m| m.request_focus(tree_id()));
+            }
+            let select = if project.file_tree.view().details {
+                select.on_hover_text(details(row))
             } else {
-                RichText::new(file_name)
+                select
             };
-            let select = ui
-                .selectable_value(&mut project.current_file, Some(entry.to_path_buf()), text)
-                .interact(Sense::click_and_drag());
-            if !project.is_current_file_edited() && select.clicked() {
-                let def_edit = FileData::default();
-                let editor = &project
-                    .files
-                    .get(project.current_file.as_ref().unwrap_or(&PathBuf::default()))
-                    .unwrap_or(&def_edit)
-                    .editor;
-                project.files.insert(
-                    project
-                        .current_file
-                        .as_ref()
-                        .unwrap_or(&PathBuf::default())
-                        .to_path_buf(),
-                    FileData {
-                        text: fs::read_to_string(
-                            project.current_file.as_ref().unwrap_or(&PathBuf::new()),
-                        )
-                        .unwrap_or_default(),
-                        editor: editor.clone(),
-                    },
-                );
+            // the arrow of a file with nested files
+            let on_arrow = row.nests
+                && select
+                    .interact_pointer_pos()
+                    .is_some_and(|pos| pos.x < select.rect.left() + 2.0 * INDENT);
+            if select.clicked() && on_arrow {
+                project.file_tree.toggle(&row.path);
+            } else if select.clicked() {
+                let modifiers = ui.input(|i| i.modifiers);
+                if modifiers.command {
+                    project.file_tree.toggle_selected(&row.path);
+                } else if modifiers.shift {
+                    project.file_tree.select_range(&row.path);
+                } else {
+                    project.file_tree.select(&row.path);
+                    match row.kind {
+                        EntryKind::Directory => project.file_tree.toggle(&row.path),
+                        EntryKind::File => project.open_file(row.path.clone()),
+                        _ => {}
+                    }
+                }
+            }
+            if select.secondary_clicked() && !selected {
+                project.file_tree.select(&row.path);
             }
-            if select.dragged() {
-                println!("dragging");
+
+            let dragged = if selected {
+                project.file_tree.selection().to_vec()
+            } else {
+                vec![row.path.clone()]
+            };
+            if !row.root {
+                select.dnd_set_drag_payload(DraggedPaths(dragged));
             }
+            let target = match row.kind {
+                EntryKind::Directory => row.path.as_path(),
+                _ => row.path.parent().unwrap_or(&row.path),
+            };
+            drop_target(ui, project, &select, target);
+
+            let select = match &row.link {
+                Some(target) if row.kind == EntryKind::Broken => {
+                    select.on_hover_text(format!("Broken link to {}", target.display()))
+                }
+                Some(target) => select.on_hover_text(format!("Link to {}", target.display())),
+                None => select,
+            };
             select.context_menu(|ui| {
                 ctx_menu(
                     ui,
                     project,
-                    entry.to_path_buf(),
-                    file_name,
-                    EntryType::File,
+                    row.path.clone(),
+                    &row.name,
+                    entry_type,
                     &select,
                 )
             });
+        });
+    });
+}
+
+/// Moves paths dragged inside the tree and copies files from the file manager into `dir`
+/// when dropped on `response`.
+fn drop_target(ui: &Ui, project: &mut Project, response: &Response, dir: &Path) {
+    let hovered = response.dnd_hover_payload::<DraggedPaths>().is_some()
+        || (ui.input(|i| !i.raw.hovered_files.is_empty()) && response.contains_pointer());
+    if hovered {
+        ui.painter()
+            .rect_stroke(response.rect, 2.0, ui.visuals().selection.stroke);
+    }
+    if let Some(dragged) = response.dnd_release_payload::<DraggedPaths>() {
+        if let Err(err) = project.move_paths(&dragged.0, dir) {
+            project.file_tree.error = Some(err.to_string());
         }
     }
+    if ui.input(|i| !i.raw.dropped_files.is_empty()) && response.contains_pointer() {
+        drop_files(ui, project, dir);
+    }
+}
 
-    Ok(())
+fn drop_files(ui: &Ui, project: &mut Project, dir: &Path) {
+    let paths = ui
+        .ctx()
+        .input_mut(|i| std::mem::take(&mut i.raw.dropped_files))
+        .into_iter()
+        .filter_map(|file| file.path)
+        .collect::<Vec<_>>();
+    if let Err(err) = project.copy_paths(&paths, dir) {
+        project.file_tree.error = Some(err.to_string());
+    }
+}
+
+/// Pastes the pending entries until one needs a decision about an existing entry.
+fn continue_paste(project: &mut Project, mut decision: Option<Conflict>) {
+    let Some(mut paste) = project.file_tree.paste.take() else {
+        return;
+    };
+    while let Some(source) = paste.sources.front().cloned() {
+        let Some(name) = source.file_name() else {
+            paste.sources.pop_front();
+            continue;
+        };
+        let mut target = paste.dir.join(name);
+        if target == source {
+            // pasting a copy next to itself
+            if paste.mode == ClipboardMode::Copy {
+                target = file_ops::unique_path(&target);
+            } else {
+                paste.sources.pop_front();
+                continue;
+            }
+        } else if fs::symlink_metadata(&target).is_ok() {
+            match decision.take().or(paste.resolution) {
+                None => {
+                    // ask first
+                    project.file_tree.paste = Some(paste);
+                    return;
+                }
+                Some(Conflict::Skip) => {
+                    paste.sources.pop_front();
+                    continue;
+                }
+                Some(Conflict::KeepBoth) => target = file_ops::unique_path(&target),
+                Some(Conflict::Replace) => match project.trash_paths(&[target.clone()]) {
+                    Ok(()) => {
+                        // undone with the paste
+                        if let Some((_, ops)) = project.file_tree.take_undo() {
+                            paste.done.extend(ops);
+                        }
+                    }
+                    Err(err) => {
+                        project.file_tree.error = Some(err.to_string());
+                        paste.sources.pop_front();
+                        continue;
+                    }
+                },
+            }
+        }
+
+        paste.sources.pop_front();
+        let result = match paste.mode {
+            ClipboardMode::Copy => {
+                file_ops::copy_recursive(&source, &target).map(|()| FileOp::Created(target.clone()))
+            }
+            ClipboardMode::Cut => project
+                .rename_path(&source, &target)
+                .map(|()| FileOp::Moved {
+                    from: source,
+                    to: target.clone(),
+                }),
+        };
+        match result {
+            Ok(op) => paste.done.push(op),
+            Err(err) => project.file_tree.error = Some(err.to_string()),
+        }
+    }
+    project.file_tree.record("Paste", paste.done);
+    if paste.mode == ClipboardMode::Cut {
+        project.file_tree.clipboard = None;
+    }
+}
+
+/// Moves `paths` to the trash, asking first unless disabled.
+fn delete(project: &mut Project, mut paths: Vec<PathBuf>) {
+    // folders of the workspace are only removed from it
+    let roots = project.roots();
+    paths.retain(|path| !roots.contains(path));
+    if paths.is_empty() {
+        return;
+    }
+    if project.file_tree.skip_delete_confirmation {
+        if let Err(err) = project.trash_paths(&paths) {
+            project.file_tree.error = Some(err.to_string());
+        }
+    } else {
+        project.file_tree.delete = Some(paths);
+    }
+}
+
+/// Asks before moving entries to the trash.
+fn delete_dialog(ctx: &egui::Context, project: &mut Project) {
+    let Some(paths) = &project.file_tree.delete else {
+        return;
+    };
+    let question = match paths.as_slice() {
+        [path] => format!(
+            "Move \"{}\" to the trash?",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ),
+        paths => format!("Move {} items to the trash?", paths.len()),
+    };
+
+    let mut confirmed = false;
+    let mut cancel = ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Escape));
+    egui::Window::new("Delete")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label(question);
+            ui.checkbox(
+                &mut project.file_tree.skip_delete_confirmation,
+                "Don't ask again",
+            );
+            ui.horizontal(|ui| {
+                confirmed = ui.button("Move to Trash").clicked();
+                cancel |= ui.button("Cancel").clicked();
+            });
+        });
+
+    if confirmed {
+        let paths = project.file_tree.delete.take().unwrap_or_default();
+        if let Err(err) = project.trash_paths(&paths) {
+            project.file_tree.error = Some(err.to_string());
+        }
+    } else if cancel {
+        project.file_tree.delete = None;
+        project.file_tree.skip_delete_confirmation = false;
+    }
+}
+
+/// Asks what to do with a pasted entry which already exists.
+fn conflict_dialog(ctx: &egui::Context, project: &mut Project) {
+    let Some(paste) = &mut project.file_tree.paste else {
+        return;
+    };
+    let name = paste
+        .sources
+        .front()
+        .and_then(|source| source.file_name())
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+
+    let mut decision = None;
+    let mut cancel = false;
+    egui::Window::new("Entry Already Exists")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "\"{name}\" already exists in {}.",
+                paste.dir.display()
+            ));
+            if paste.sources.len() > 1 {
+                ui.checkbox(&mut paste.apply_to_all, "Apply to all");
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Keep Both").clicked() {
+                    decision = Some(Conflict::KeepBoth);
+                }
+                if ui.button("Replace").clicked() {
+                    decision = Some(Conflict::Replace);
+                }
+                if ui.button("Skip").clicked() {
+                    decision = Some(Conflict::Skip);
+                }
+                if ui.button("Cancel").clicked() {
+                    cancel = true;
+                }
+            });
+        });
+
+    if cancel {
+        if let Some(paste) = project.file_tree.paste.take() {
+            project.file_tree.record("Paste", paste.done);
+        }
+    } else if let Some(decision) = decision {
+        if paste.apply_to_all {
+            paste.resolution = Some(decision);
+        }
+        continue_paste(project, Some(decision));
+    }
+}
+
+/// Size and modification time of an entry.
+fn details(row: &Row) -> String {
+    let mut details = Vec::new();
+    if let Some(size) = row.size {
+        details.push(format_size(size));
+    }
+    if let Some(modified) = row.modified {
+        details.push(format!("Modified {}", format_time(modified)));
+    }
+    details.join("\n")
+}
+
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{size} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Relative for the last week, a date (UTC) before.
+fn format_time(time: SystemTime) -> String {
+    let Ok(elapsed) = SystemTime::now().duration_since(time) else {
+        return "just now".into();
+    };
+    let seconds = elapsed.as_secs();
+    match seconds {
+        0..=59 => "just now".into(),
+        60..=3599 => format!("{} min ago", seconds / 60),
+        3600..=86399 => format!("{} h ago", seconds / 3600),
+        86400..=604799 => format!("{} days ago", seconds / 86400),
+        _ => {
+            let days = time
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+                / 86400;
+            // days to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+            let z = days as i64 + 719468;
+            let era = z.div_euclid(146097);
+            let doe = z.rem_euclid(146097);
+            let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+            let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+            let mp = (5 * doy + 2) / 153;
+            let day = doy - (153 * mp + 2) / 5 + 1;
+            let month = if mp < 10 { mp + 3 } else { mp - 9 };
+            let year = yoe + era * 400 + i64::from(month <= 2);
+            format!("{year}-{month:02}-{day:02}")
+        }
+    }
+}
+
+/// Symbol and color of a file by its name.
+fn file_icon(name: &str) -> (&'static str, Color32) {
+    let extension = Path::new(name)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match (name, extension.as_str()) {
+        ("Cargo.toml" | "Cargo.lock", _) => ("📦", Color32::from_rgb(222, 165, 132)),
+        ("LICENSE" | "LICENSE-MIT" | "LICENSE-APACHE", _) => ("📜", Color32::GOLD),
+        (".gitignore" | ".gitattributes" | ".gitmodules", _) => {
+            ("🗋", Color32::from_rgb(240, 80, 50))
+        }
+        ("Makefile" | "Dockerfile", _) => ("⚙", Color32::from_rgb(110, 150, 200)),
+        (_, "rs") => ("🗋", Color32::from_rgb(222, 165, 132)),
+        (_, "py") => ("🗋", Color32::from_rgb(80, 140, 200)),
+        (_, "js" | "mjs" | "cjs") => ("🗋", Color32::from_rgb(240, 220, 80)),
+        (_, "ts" | "tsx" | "jsx") => ("🗋", Color32::from_rgb(50, 120, 200)),
+        (_, "c" | "h" | "cpp" | "hpp" | "cc") => ("🗋", Color32::from_rgb(90, 150, 210)),
+        (_, "go") => ("🗋", Color32::from_rgb(0, 173, 216)),
+        (_, "java" | "kt") => ("🗋", Color32::from_rgb(200, 120, 60)),
+        (_, "html" | "htm") => ("🗋", Color32::from_rgb(228, 77, 38)),
+        (_, "css" | "scss") => ("🗋", Color32::from_rgb(86, 61, 124)),
+        (_, "sh" | "bash" | "zsh" | "fish") => ("🗋", Color32::from_rgb(137, 224, 81)),
+        (_, "md" | "markdown" | "txt") => ("🖹", Color32::LIGHT_BLUE),
+        (_, "json" | "toml" | "yaml" | "yml" | "ini" | "conf") => ("⚙", Color32::GRAY),
+        (_, "lock") => ("🔒", Color32::GRAY),
+        (_, "png" | "jpg" | "jpeg" | "gif" | "svg" | "ico" | "webp" | "bmp") => {
+            ("🖼", Color32::from_rgb(160, 110, 200))
+        }
+        (_, "zip" | "tar" | "gz" | "xz" | "7z" | "rar") => ("📦", Color32::from_rgb(180, 140, 90)),
+        (_, "pdf") => ("🗋", Color32::from_rgb(220, 60, 60)),
+        _ => ("🗋", Color32::GRAY),
+    }
+}
+
+fn link_marker(row: &Row) -> &'static str {
+    if row.link.is_some() {
+        " ↪"
+    } else {
+        ""
+    }
 }
 
 fn ctx_menu(
@@ -170,16 +824,19 @@ fn ctx_menu(
     entry_type: EntryType,
     response: &Response,
 ) {
-    // state
+    // state, the last one is an error message
     let mut editable: (String, String, String) = ui
         .memory_mut(|w| w.data.get_persisted(ui.id()))
-        .unwrap_or((String::new(), String::new(), file_name.to_string()));
-    // update like current name for renaming data on changes
+        .unwrap_or_default();
+    // reset on reopening
     if response.clicked_by(egui::PointerButton::Secondary) {
-        editable = (String::new(), String::new(), file_name.to_string());
+        editable = Default::default();
     }
 
     ui.label(file_name);
+    if !editable.2.is_empty() {
+        ui.colored_label(ui.visuals().error_fg_color, &editable.2);
+    }
     if entry_type == EntryType::Root || entry_type == EntryType::Directory {
         ui.menu_button("Add Directory", |ui| {
             let input_field = ui.add(
@@ -191,9 +848,17 @@ fn ctx_menu(
                 || (input_field.lost_focus()
                     && ui.input_mut(|r| r.consume_key(Modifiers::NONE, Key::Enter)))
             {
-                fs::create_dir(path.join(&editable.0)).unwrap();
-                editable.0 = String::new();
-                ui.close_menu();
+                let new_path = path.join(&editable.0);
+                match fs::create_dir(&new_path) {
+                    Ok(()) => {
+                        project
+                            .file_tree
+                            .record("New Directory", vec![FileOp::Created(new_path)]);
+                        editable.0 = String::new();
+                        ui.close_menu();
+                    }
+                    Err(err) => editable.2 = err.to_string(),
+                }
             }
         });
         ui.menu_button("Add File", |ui| {
@@ -202,75 +867,138 @@ fn ctx_menu(
                 || (input_field.lost_focus()
                     && ui.input_mut(|r| r.consume_key(Modifiers::NONE, Key::Enter)))
             {
-                fs::write(path.join(&editable.1), "").unwrap();
-                editable.1 = String::new();
-                ui.close_menu();
-            }
-        });
-    }
-    if entry_type == EntryType::Directory || entry_type == EntryType::File {
-        ui.menu_button("Rename", |ui| {
-            let input_field = ui.add(TextEdit::singleline(&mut editable.2).hint_text("Name"));
-            if ui.button("Apply").clicked()
-                || (input_field.lost_focus()
-                    && ui.input_mut(|r| r.consume_key(Modifiers::NONE, Key::Enter)))
-            {
-                let new_path = rename(&path, &editable.2).unwrap_or(path.clone());
-                editable.2 = new_path
-                    .file_name()
-                    .unwrap_or(OsStr::new(file_name))
-                    .to_string_lossy()
-                    .to_string();
-                if project.current_file.clone().unwrap_or_default() == path {
-                    project.current_file = Some(new_path);
-                    project.files.insert(
+                let new_path = path.join(&editable.1);
+                // never empties a file that already exists
+                let created = fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&new_path);
+                match created {
+                    Ok(_) => {
                         project
-                            .current_file
-                            .as_ref()
-                            .unwrap_or(&PathBuf::default())
-                            .to_path_buf(),
-                        FileData {
-                            text: fs::read_to_string(
-                                project.current_file.as_ref().unwrap_or(&PathBuf::new()),
-                            )
-                            .unwrap_or_default(),
-                            ..Default::default()
-                        },
-                    );
+                            .file_tree
+                            .record("New File", vec![FileOp::Created(new_path)]);
+                        editable.1 = String::new();
+                        ui.close_menu();
+                    }
+                    Err(err) => editable.2 = err.to_string(),
                 }
-                ui.close_menu();
             }
         });
-        if ui.button("Delete").clicked() {
-            match entry_type {
-                EntryType::Directory => fs::remove_dir_all(&path).unwrap(),
-                EntryType::File => fs::remove_file(&path).unwrap(),
-                _ => {}
-            }
-            if &path == project.current_file.as_ref().unwrap_or(&PathBuf::new())
-                || project
-                    .current_file
-                    .as_ref()
-                    .unwrap_or(&PathBuf::new())
-                    .starts_with(path.clone())
-            {
-                project.current_file = None;
-                project.remove_file(&project.current_file.clone().unwrap_or_default())
+    }
+    // the whole selection if the entry is part of it
+    let targets = match entry_type {
+        EntryType::Root => Vec::new(),
+        _ if project.file_tree.is_selected(&path) => project.file_tree.selection().to_vec(),
+        _ => vec![path.clone()],
+    };
+    let dir = match entry_type {
+        EntryType::File => path.parent().unwrap_or(&path).to_path_buf(),
+        _ => path.clone(),
+    };
+
+    if targets.len() == 1 && ui.button("Rename").on_hover_text("F2").clicked() {
+        project.file_tree.start_rename(&path);
+        ui.close_menu();
+    }
+    ui.separator();
+    if !targets.is_empty() {
+        if ui.button("Cut").clicked() {
+            project.file_tree.clipboard = Some((targets.clone(), ClipboardMode::Cut));
+            ui.close_menu();
+        }
+        if ui.button("Copy").clicked() {
+            project.file_tree.clipboard = Some((targets.clone(), ClipboardMode::Copy));
+            ui.close_menu();
+        }
+    }
+    if let Some((sources, mode)) = project.file_tree.clipboard.clone() {
+        let label = match sources.len() {
+            1 => "Paste".to_string(),
+            n => format!("Paste {n} Items"),
+        };
+        if ui.button(label).clicked() {
+            project.file_tree.paste = Some(Paste {
+                sources: sources.into(),
+                dir: dir.clone(),
+                mode,
+                resolution: None,
+                apply_to_all: false,
+                done: Vec::new(),
+            });
+            continue_paste(project, None);
+            ui.close_menu();
+        }
+    }
+    if !targets.is_empty() && ui.button("Duplicate").clicked() {
+        let mut ops = Vec::new();
+        for target in &targets {
+            let copy = file_ops::unique_path(target);
+            match file_ops::copy_recursive(target, &copy) {
+                Ok(()) => ops.push(FileOp::Created(copy)),
+                Err(err) => editable.2 = err.to_string(),
             }
-            project.remove_file(&path);
+        }
+        project.file_tree.record("Duplicate", ops);
+        ui.close_menu();
+    }
+    ui.separator();
+    let paths = if targets.is_empty() {
+        vec![path.clone()]
+    } else {
+        targets.clone()
+    };
+    if ui.button("Copy Path").clicked() {
+        let text = paths
+            .iter()
+            .map(|path| path.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("\n");
+        ui.ctx().copy_text(text);
+        ui.close_menu();
+    }
+    if ui.button("Copy Relative Path").clicked() {
+        let text = paths
+            .iter()
+            .map(|path| {
+                let root = project.root_of(path).unwrap_or_default();
+                path.strip_prefix(&root)
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        ui.ctx().copy_text(text);
+        ui.close_menu();
+    }
+    if !targets.is_empty() {
+        ui.separator();
+        let label = match targets.len() {
+            1 => "Delete".to_string(),
+            n => format!("Delete {n} Items"),
+        };
+        if ui.button(label).clicked() {
+            delete(project, targets);
             ui.close_menu();
         }
     }
-    ui.memory_mut(|w| w.data.insert_persisted(ui.id(), editable));
-}
-
-fn rename(original_path: &Path, new_name: &str) -> std::io::Result<PathBuf> {
-    let new_path = original_path.with_file_name(new_name);
-
-    if !new_path.exists() {
-        fs::rename(original_path, &new_path)?;
-        return Ok(new_path);
+    if entry_type == EntryType::Root {
+        ui.separator();
+        if ui.button("Open Terminal").clicked() {
+            project.open_terminal(&path);
+            ui.close_menu();
+        }
+        if ui.button("Add Folder to Workspace...").clicked() {
+            project.add_folder();
+            ui.close_menu();
+        }
+        if !project.workspace.folders.is_empty()
+            && ui.button("Remove Folder from Workspace").clicked()
+        {
+            project.remove_folder(&path);
+            ui.close_menu();
+        }
     }
-
-    Err(std::io::Error::from_raw_os_error(0))
+    ui.memory_mut(|w| w.data.insert_persisted(ui.id(), editable));
 }
@@ -1,25 +1,59 @@
 use ecolor::{Color32, HexColor};
-use eframe::egui::{self, Ui};
+use eframe::egui::{self, FontId, Ui};
 
 use crate::{
     code_editor::ExtendedCodeEditorSpawner,
+    keymap::Focus,
+    panels::diff_view,
+    snippets::SnippetScope,
     terminal::{prelude::TerminalSpawner, render::CursorType},
     Project,
 };
 
 pub fn init(ui: &mut Ui, project: &mut Project) {
-    let terminal_heigth: f32 = ui.available_height() * 0.25;
+    let settings = &project.config.settings;
+    let terminal_heigth: f32 = ui.available_height() * settings.layout.terminal_height;
+    let font_size = settings.font.terminal_size * project.zoom_factor();
     ui.vertical(|ui| {
         // text input window
         ui.with_layout(egui::Layout::left_to_right(egui::Align::Min), |ui| {
-            if let Some(current_file) = project.current_file.clone() {
-                // update height on shown terminal
-                if project.get_current_terminal().is_some() {
-                    ui.set_height(ui.available_height() - (terminal_heigth + 2.5));
-                }
+            // update height on shown terminal
+            if project.get_current_terminal().is_some() {
+                ui.set_height(ui.available_height() - (terminal_heigth + 2.5));
+            }
+            if project.diff.is_some() {
+                diff_view::init(ui, project);
+            } else if let Some(current_file) = project.current_file.clone() {
+                project.completion.sync(
+                    ui.ctx(),
+                    &project.config.settings.completion,
+                    &project.roots(),
+                    &project.files,
+                    &current_file,
+                );
+                let root = project.root_of(&current_file);
+                let scope = SnippetScope {
+                    snippets: project.snippets.for_path(&current_file, root.as_deref()),
+                    path: &current_file,
+                    root: root.as_deref(),
+                };
                 let contents = project.files.get_mut(&current_file);
                 if let Some(contents) = contents {
-                    let text_edit = ui.ext_code_ui(contents);
+                    contents.resolve(&current_file);
+                    let text_edit = ui.ext_code_ui(
+                        contents,
+                        &mut project.vim,
+                        &mut project.keymap,
+                        &scope,
+                        &project.completion,
+                        &project.config.settings.editor,
+                    );
+                    if project.palette.take_refocus() | project.quick_open.take_refocus() {
+                        text_edit.request_focus();
+                    }
+                    if text_edit.has_focus() {
+                        project.focus = Focus::Editor;
+                    }
                     if text_edit.changed() {
                         project.files_edited.insert(current_file, true);
                     }
@@ -28,6 +62,7 @@ pub fn init(ui: &mut Ui, project: &mut Project) {
         });
 
         // terminal
+        let mut terminal_focused = false;
         if let Some(terminal) = &mut project.get_mut_current_terminal() {
             egui::panel::TopBottomPanel::bottom("terminal_panel")
                 .min_height(terminal_heigth)
@@ -37,9 +72,15 @@ pub fn init(ui: &mut Ui, project: &mut Project) {
                     terminal.style.default_focus_cursor =
                         CursorType::Beam(HexColor::Hex8(Color32::WHITE));
                     terminal.style.default_unfocus_cursor = CursorType::None;
+                    terminal.style.font = FontId::monospace(font_size);
 
-                    ui.terminal_sized(terminal, egui::vec2(ui.available_width(), terminal_heigth));
+                    terminal_focused = ui
+                        .terminal_sized(terminal, egui::vec2(ui.available_width(), terminal_heigth))
+                        .has_focus();
                 });
         }
+        if terminal_focused {
+            project.focus = Focus::Terminal;
+        }
     });
 }
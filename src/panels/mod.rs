@@ -1,3 +1,7 @@
 pub mod bottom_panel;
+pub mod command_palette;
+pub mod diff_view;
 pub mod left_side_panel;
 pub mod main_panel;
+pub mod quick_open;
+pub mod settings;
@@ -0,0 +1,290 @@
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use eframe::egui::{self, Context, Key, Modifiers, RichText, TextEdit};
+
+use crate::{
+    file_index::{FileIndex, IndexedFile},
+    fuzzy::fuzzy_match,
+    Project,
+};
+
+use super::command_palette::highlighted;
+
+const MAX_RESULTS: usize = 200;
+const PREVIEW_BYTES: u64 = 16 * 1024;
+
+#[derive(Default)]
+pub struct QuickOpen {
+    pub open: bool,
+    query: String,
+    selected: usize,
+    refocus_editor: bool,
+    pub index: FileIndex,
+    results: Vec<(IndexedFile, Vec<usize>)>,
+    /// Query and index generation `results` were computed for.
+    results_for: Option<(String, usize)>,
+    preview: Option<(PathBuf, String)>,
+}
+
+impl QuickOpen {
+    pub fn toggle(
+        &mut self,
+        ctx: &Context,
+        roots: &[PathBuf],
+        tree_changes: usize,
+        editor_focused: bool,
+    ) {
+        if self.open {
+            self.open = false;
+            return;
+        }
+        if roots.is_empty() {
+            return;
+        }
+        self.index.refresh(ctx, roots, tree_changes);
+        self.open = true;
+        self.query.clear();
+        self.selected = 0;
+        self.refocus_editor = editor_focused;
+    }
+
+    /// Whether the editor should get the focus back after quick open closed.
+    pub fn take_refocus(&mut self) -> bool {
+        !self.open && std::mem::take(&mut self.refocus_editor)
+    }
+
+    fn update_results(&mut self) {
+        let (query, _, _) = parse_query(&self.query);
+        let key = (query.to_string(), self.index.generation);
+        if self.results_for.as_ref() == Some(&key) {
+            return;
+        }
+
+        let mut results = self
+            .index
+            .files()
+            .iter()
+            .filter_map(|file| {
+                let path = &file.relative;
+                let text = path.to_string_lossy();
+                let in_path = fuzzy_match(query, &text);
+                // matches in the file name count more
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let offset = text.chars().count() - name.chars().count();
+                let in_name = fuzzy_match(query, &name).map(|mut found| {
+                    found.score += 10;
+                    found.indices.iter_mut().for_each(|i| *i += offset);
+                    found
+                });
+                let found = match (in_path, in_name) {
+                    (Some(a), Some(b)) if a.score > b.score => a,
+                    (_, Some(b)) => b,
+                    (a, None) => a?,
+                };
+                Some((found.score, file, found.indices))
+            })
+            .collect::<Vec<_>>();
+        results.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.relative.cmp(&b.1.relative)));
+        results.truncate(MAX_RESULTS);
+
+        self.results = results
+            .into_iter()
+            .map(|(_, file, indices)| (file.clone(), indices))
+            .collect();
+        self.results_for = Some(key);
+        self.selected = 0;
+    }
+}
+
+/// Shows quick open and opens the chosen file.
+pub fn init(ctx: &Context, project: &mut Project) {
+    let quick_open = &mut project.quick_open;
+    quick_open.index.poll();
+    if !quick_open.open {
+        return;
+    }
+    quick_open.update_results();
+
+    let mut chosen = None;
+    let area = egui::Area::new(egui::Id::new("quick_open"))
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 40.0))
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.set_width(800.0);
+
+                let mut moved = false;
+                let (escape, enter) = ui.input_mut(|i| {
+                    if i.consume_key(Modifiers::NONE, Key::ArrowDown) {
+                        quick_open.selected += 1;
+                        moved = true;
+                    }
+                    if i.consume_key(Modifiers::NONE, Key::ArrowUp) {
+                        quick_open.selected = quick_open.selected.saturating_sub(1);
+                        moved = true;
+                    }
+                    (
+                        i.consume_key(Modifiers::NONE, Key::Escape),
+                        i.consume_key(Modifiers::NONE, Key::Enter),
+                    )
+                });
+                if escape {
+                    quick_open.open = false;
+                    return;
+                }
+                quick_open.selected = quick_open
+                    .selected
+                    .min(quick_open.results.len().saturating_sub(1));
+
+                ui.add(
+                    TextEdit::singleline(&mut quick_open.query)
+                        .hint_text("Search files by name (append :line:column to jump)")
+                        .desired_width(f32::INFINITY),
+                )
+                .request_focus();
+
+                ui.horizontal_top(|ui| {
+                    ui.vertical(|ui| {
+                        ui.set_width(350.0);
+                        egui::ScrollArea::vertical()
+                            .id_source("quick_open_results")
+                            .max_height(400.0)
+                            .show(ui, |ui| {
+                                ui.with_layout(
+                                    egui::Layout::top_down_justified(egui::Align::Min),
+                                    |ui| {
+                                        for (i, (file, indices)) in
+                                            quick_open.results.iter().enumerate()
+                                        {
+                                            let label = ui.selectable_label(
+                                                i == quick_open.selected,
+                                                highlighted(
+                                                    ui,
+                                                    &file.relative.to_string_lossy(),
+                                                    indices,
+                                                ),
+                                            );
+                                            if label.clicked() {
+                                                chosen = Some(file.path.clone());
+                                            }
+                                            if moved && i == quick_open.selected {
+                                                label.scroll_to_me(None);
+                                            }
+                                        }
+                                        if quick_open.results.is_empty() {
+                                            ui.weak(if quick_open.index.is_building() {
+                                                "Indexing..."
+                                            } else {
+                                                "No matching files"
+                                            });
+                                        }
+                                    },
+                                );
+                            });
+                    });
+                    ui.separator();
+
+                    // preview
+                    let selected = quick_open
+                        .results
+                        .get(quick_open.selected)
+                        .map(|(file, _)| file.path.clone());
+                    if let Some(selected) = selected {
+                        if quick_open.preview.as_ref().map(|(path, _)| path) != Some(&selected) {
+                            let text = preview(&selected);
+                            quick_open.preview = Some((selected, text));
+                        }
+                    }
+                    if let Some((_, text)) = &quick_open.preview {
+                        egui::ScrollArea::both()
+                            .id_source("quick_open_preview")
+                            .max_height(400.0)
+                            .show(ui, |ui| {
+                                ui.label(RichText::new(text).monospace().small());
+                            });
+                    }
+                });
+
+                if enter {
+                    chosen = quick_open
+                        .results
+                        .get(quick_open.selected)
+                        .map(|(file, _)| file.path.clone());
+                }
+            });
+        });
+    if area.response.clicked_elsewhere() {
+        quick_open.open = false;
+    }
+
+    if let Some(path) = chosen {
+        let (_, line, column) = parse_query(&quick_open.query);
+        quick_open.open = false;
+        quick_open.refocus_editor = true;
+        project.open_file(path);
+        if let Some(line) = line {
+            project.go_to(
+                line.saturating_sub(1),
+                column.unwrap_or(1).saturating_sub(1),
+            );
+        }
+    }
+}
+
+/// Splits `main.rs:10:5` into the file query and the 1-based line and column.
+fn parse_query(query: &str) -> (&str, Option<usize>, Option<usize>) {
+    let number = |s: &str| s.trim().parse::<usize>().ok();
+    match query.rsplit_once(':') {
+        Some((rest, last)) if number(last).is_some() => match rest.rsplit_once(':') {
+            Some((path, line)) if number(line).is_some() => (path, number(line), number(last)),
+            _ => (rest, number(last), None),
+        },
+        // `main.rs:` and `main.rs:10:` while typing the line or column
+        Some((rest, "")) => {
+            let (path, line, _) = parse_query(rest);
+            (path, line, None)
+        }
+        _ => (query, None, None),
+    }
+}
+
+/// The beginning of the file at `path`.
+fn preview(path: &Path) -> String {
+    let mut bytes = Vec::new();
+    let read =
+        fs::File::open(path).and_then(|file| file.take(PREVIEW_BYTES).read_to_end(&mut bytes));
+    match read {
+        Err(err) => err.to_string(),
+        Ok(_) if bytes.contains(&0) => "Binary file".to_string(),
+        Ok(_) => String::from_utf8_lossy(&bytes).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_positions() {
+        assert_eq!(parse_query("main.rs"), ("main.rs", None, None));
+        assert_eq!(parse_query("main.rs:42"), ("main.rs", Some(42), None));
+        assert_eq!(parse_query("main.rs:42:7"), ("main.rs", Some(42), Some(7)));
+        assert_eq!(parse_query("main.rs: 42"), ("main.rs", Some(42), None));
+        assert_eq!(parse_query("a:b:3:4:5"), ("a:b:3", Some(4), Some(5)));
+        assert_eq!(parse_query("main.rs:x"), ("main.rs:x", None, None));
+        assert_eq!(parse_query(":3"), ("", Some(3), None));
+        assert_eq!(parse_query(""), ("", None, None));
+    }
+
+    #[test]
+    fn parses_unfinished_positions() {
+        assert_eq!(parse_query("main.rs:"), ("main.rs", None, None));
+        assert_eq!(parse_query("main.rs:42:"), ("main.rs", Some(42), None));
+        assert_eq!(parse_query("main.rs::"), ("main.rs", None, None));
+        assert_eq!(parse_query(":"), ("", None, None));
+    }
+}
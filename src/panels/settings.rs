@@ -0,0 +1,205 @@
+use eframe::egui::{self, Context, DragValue, RichText, Slider};
+
+use crate::{
+    settings::{Scope, Settings},
+    Project,
+};
+
+#[derive(Default)]
+pub struct SettingsEditor {
+    pub open: bool,
+    scope: Scope,
+    /// The values being edited, saved on request.
+    draft: Settings,
+    error: Option<String>,
+}
+
+impl SettingsEditor {
+    pub fn toggle(&mut self, settings: &Settings) {
+        self.open = !self.open;
+        self.draft = settings.clone();
+        self.error = None;
+    }
+}
+
+pub fn init(ctx: &Context, project: &mut Project) {
+    let editor = &mut project.settings_editor;
+    if !editor.open {
+        return;
+    }
+    let config = &project.config;
+
+    let mut open = true;
+    let mut open_file = None;
+    egui::Window::new("Settings")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Save to");
+                ui.selectable_value(&mut editor.scope, Scope::Global, "Global");
+                ui.add_enabled_ui(config.path(Scope::Project).is_some(), |ui| {
+                    ui.selectable_value(&mut editor.scope, Scope::Project, "Project")
+                        .on_hover_text("Overrides the global settings in this project");
+                });
+            });
+            ui.separator();
+
+            let draft = &mut editor.draft;
+            egui::Grid::new("settings_grid")
+                .num_columns(2)
+                .spacing([20.0, 6.0])
+                .show(ui, |ui| {
+                    ui.label(RichText::new("Window").strong());
+                    ui.end_row();
+                    ui.label("Width");
+                    ui.add(DragValue::new(&mut draft.window.width).clamp_range(600.0..=10000.0));
+                    ui.end_row();
+                    ui.label("Height");
+                    ui.add(DragValue::new(&mut draft.window.height).clamp_range(400.0..=10000.0));
+                    ui.end_row();
+
+                    ui.label(RichText::new("Font").strong());
+                    ui.end_row();
+                    ui.label("Editor size");
+                    ui.add(Slider::new(&mut draft.font.size, 6.0..=72.0));
+                    ui.end_row();
+                    ui.label("Terminal size");
+                    ui.add(Slider::new(&mut draft.font.terminal_size, 6.0..=72.0));
+                    ui.end_row();
+                    ui.label("Interface size");
+                    ui.add(Slider::new(&mut draft.font.ui_size, 6.0..=72.0));
+                    ui.end_row();
+                    ui.label("Monospace font").on_hover_text(
+                        "Ligatures are not supported, every character is drawn on its own",
+                    );
+                    font_file(ui, &mut draft.font.path);
+                    ui.end_row();
+                    ui.label("Interface font");
+                    font_file(ui, &mut draft.font.ui_path);
+                    ui.end_row();
+
+                    ui.label(RichText::new("Layout").strong());
+                    ui.end_row();
+                    ui.label("Terminal height");
+                    ui.add(Slider::new(&mut draft.layout.terminal_height, 0.1..=0.9));
+                    ui.end_row();
+                    ui.label("Side panel width");
+                    ui.add(
+                        DragValue::new(&mut draft.layout.side_panel_width)
+                            .clamp_range(150.0..=1000.0),
+                    );
+                    ui.end_row();
+
+                    ui.label(RichText::new("Editor").strong());
+                    ui.end_row();
+                    ui.label("Render whitespace");
+                    ui.checkbox(&mut draft.editor.render_whitespace, "");
+                    ui.end_row();
+                    ui.label("Indentation guides");
+                    ui.checkbox(&mut draft.editor.indent_guides, "");
+                    ui.end_row();
+                    ui.label("Trailing whitespace");
+                    ui.checkbox(&mut draft.editor.highlight_trailing_whitespace, "")
+                        .on_hover_text("Highlight whitespace at the end of lines");
+                    ui.end_row();
+                    ui.label("Rulers");
+                    ui.horizontal(|ui| {
+                        let mut removed = None;
+                        for (i, column) in draft.editor.rulers.iter_mut().enumerate() {
+                            ui.add(DragValue::new(column).clamp_range(1..=1000));
+                            if ui.small_button("✖").clicked() {
+                                removed = Some(i);
+                            }
+                        }
+                        if let Some(i) = removed {
+                            draft.editor.rulers.remove(i);
+                        }
+                        if ui.small_button("+").on_hover_text("Add a ruler").clicked() {
+                            draft.editor.rulers.push(80);
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label(RichText::new("Format").strong());
+                    ui.end_row();
+                    ui.label("On save");
+                    ui.checkbox(&mut draft.format.on_save, "");
+                    ui.end_row();
+                    ui.label("Timeout");
+                    ui.add(Slider::new(&mut draft.format.timeout, 0.1..=60.0).suffix("s"));
+                    ui.end_row();
+                    for (extension, command) in &mut draft.format.commands {
+                        ui.label(format!("*.{extension}"));
+                        ui.text_edit_singleline(command);
+                        ui.end_row();
+                    }
+
+                    ui.label(RichText::new("Completion").strong());
+                    ui.end_row();
+                    ui.label("While typing");
+                    ui.checkbox(&mut draft.completion.auto, "");
+                    ui.end_row();
+                    ui.label("After characters");
+                    ui.add(Slider::new(&mut draft.completion.min_chars, 1..=10));
+                    ui.end_row();
+                    ui.label("Project words");
+                    ui.checkbox(&mut draft.completion.project_words, "")
+                        .on_hover_text("Complete words of all project files");
+                    ui.end_row();
+                });
+
+            for problem in config.problems.iter().chain(&editor.error) {
+                ui.label(RichText::new(problem).color(ui.visuals().warn_fg_color));
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    editor.error = config
+                        .save(editor.scope, &editor.draft)
+                        .err()
+                        .map(|err| err.to_string());
+                }
+                if ui
+                    .button("Revert")
+                    .on_hover_text("Back to the settings in effect")
+                    .clicked()
+                {
+                    editor.draft = config.settings.clone();
+                }
+                if ui.button("Open File").clicked() {
+                    open_file = config.path(editor.scope);
+                }
+            });
+        });
+    editor.open &= open;
+
+    if let Some(path) = open_file {
+        if !path.exists() {
+            if let Err(err) = project.config.save(
+                project.settings_editor.scope,
+                &project.settings_editor.draft,
+            ) {
+                project.settings_editor.error = Some(err.to_string());
+                return;
+            }
+        }
+        project.open_file(path);
+    }
+}
+
+/// A font file path, empty for the built-in font.
+fn font_file(ui: &mut egui::Ui, path: &mut String) {
+    ui.horizontal(|ui| {
+        ui.add(egui::TextEdit::singleline(path).hint_text("Built-in"));
+        if ui.small_button("Browse...").clicked() {
+            if let Some(file) = rfd::FileDialog::new()
+                .add_filter("Font", &["ttf", "otf"])
+                .pick_file()
+            {
+                *path = file.to_string_lossy().to_string();
+            }
+        }
+    });
+}
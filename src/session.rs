@@ -0,0 +1,22 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::code_editor::FileData;
+
+pub const MAX_RECENT: usize = 10;
+
+/// State of a project that is not open, restored when it is opened again.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Session {
+    pub current_file: Option<PathBuf>,
+    /// Open buffers with their cursors and scroll offsets.
+    pub files: HashMap<PathBuf, FileData>,
+    pub files_edited: HashMap<PathBuf, bool>,
+    pub expanded: HashSet<PathBuf>,
+    /// Roots whose terminal was running.
+    pub terminals: Vec<PathBuf>,
+}
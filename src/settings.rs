@@ -0,0 +1,430 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use crossbeam_channel::Receiver;
+use eframe::egui::Context;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use toml::Table;
+
+/// Everything configurable, read from the global and the project settings file.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub window: WindowSettings,
+    pub font: FontSettings,
+    pub layout: LayoutSettings,
+    pub editor: EditorSettings,
+    pub format: FormatSettings,
+    pub completion: CompletionSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            window: WindowSettings {
+                width: 1000.0,
+                height: 700.0,
+            },
+            font: FontSettings {
+                path: String::new(),
+                ui_path: String::new(),
+                size: 12.0,
+                terminal_size: 12.0,
+                ui_size: 12.5,
+            },
+            layout: LayoutSettings {
+                terminal_height: 0.25,
+                side_panel_width: 200.0,
+            },
+            editor: EditorSettings {
+                render_whitespace: false,
+                indent_guides: true,
+                rulers: Vec::new(),
+                highlight_trailing_whitespace: true,
+            },
+            format: FormatSettings {
+                on_save: false,
+                timeout: 2.0,
+                commands: [
+                    ("rs", "rustfmt --edition 2021"),
+                    ("toml", "taplo fmt -"),
+                    ("py", "black -q -"),
+                    ("go", "gofmt"),
+                    ("c", "clang-format --assume-filename={path}"),
+                    ("cpp", "clang-format --assume-filename={path}"),
+                    ("h", "clang-format --assume-filename={path}"),
+                    ("js", "prettier --stdin-filepath {path}"),
+                    ("ts", "prettier --stdin-filepath {path}"),
+                    ("jsx", "prettier --stdin-filepath {path}"),
+                    ("tsx", "prettier --stdin-filepath {path}"),
+                    ("json", "prettier --stdin-filepath {path}"),
+                    ("css", "prettier --stdin-filepath {path}"),
+                    ("html", "prettier --stdin-filepath {path}"),
+                    ("md", "prettier --stdin-filepath {path}"),
+                    ("yaml", "prettier --stdin-filepath {path}"),
+                ]
+                .into_iter()
+                .map(|(extension, command)| (extension.to_string(), command.to_string()))
+                .collect(),
+            },
+            completion: CompletionSettings {
+                auto: true,
+                min_chars: 3,
+                project_words: true,
+            },
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowSettings {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Settings::default().window
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FontSettings {
+    /// TTF or OTF file of the monospace font of the editor and the terminal, built-in if empty.
+    /// egui does not shape text, so ligatures are not drawn.
+    pub path: String,
+    /// Font file of the rest of the interface, built-in if empty.
+    pub ui_path: String,
+    /// Size of the editor font.
+    pub size: f32,
+    pub terminal_size: f32,
+    pub ui_size: f32,
+}
+
+impl Default for FontSettings {
+    fn default() -> Self {
+        Settings::default().font
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutSettings {
+    /// Part of the main panel taken by the terminal.
+    pub terminal_height: f32,
+    pub side_panel_width: f32,
+}
+
+impl Default for LayoutSettings {
+    fn default() -> Self {
+        Settings::default().layout
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EditorSettings {
+    /// Show spaces as dots and tabs as arrows.
+    pub render_whitespace: bool,
+    pub indent_guides: bool,
+    /// Columns a vertical line is drawn at.
+    pub rulers: Vec<usize>,
+    pub highlight_trailing_whitespace: bool,
+}
+
+impl Default for EditorSettings {
+    fn default() -> Self {
+        Settings::default().editor
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FormatSettings {
+    pub on_save: bool,
+    /// Seconds a formatter may take.
+    pub timeout: f32,
+    /// Formatter commands by file extension, reading stdin and writing stdout. Arguments with
+    /// spaces are quoted like in a shell.
+    pub commands: BTreeMap<String, String>,
+}
+
+impl Default for FormatSettings {
+    fn default() -> Self {
+        Settings::default().format
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompletionSettings {
+    /// Show completions while typing, not only on request.
+    pub auto: bool,
+    /// Characters of a word typed before completions show up.
+    pub min_chars: usize,
+    /// Complete words of all project files, not only of the open ones.
+    pub project_words: bool,
+}
+
+impl Default for CompletionSettings {
+    fn default() -> Self {
+        Settings::default().completion
+    }
+}
+
+impl Settings {
+    /// Resets values out of their range to the default.
+    fn validate(&mut self, problems: &mut Vec<String>) {
+        let default = Settings::default();
+        let mut check = |name: &str, value: &mut f32, range: RangeInclusive<f32>, default: f32| {
+            if !range.contains(value) {
+                problems.push(format!(
+                    "settings: {name} must be between {} and {}",
+                    range.start(),
+                    range.end()
+                ));
+                *value = default;
+            }
+        };
+        check(
+            "window.width",
+            &mut self.window.width,
+            600.0..=10000.0,
+            default.window.width,
+        );
+        check(
+            "window.height",
+            &mut self.window.height,
+            400.0..=10000.0,
+            default.window.height,
+        );
+        check(
+            "font.size",
+            &mut self.font.size,
+            6.0..=72.0,
+            default.font.size,
+        );
+        check(
+            "font.terminal_size",
+            &mut self.font.terminal_size,
+            6.0..=72.0,
+            default.font.terminal_size,
+        );
+        check(
+            "font.ui_size",
+            &mut self.font.ui_size,
+            6.0..=72.0,
+            default.font.ui_size,
+        );
+        check(
+            "layout.terminal_height",
+            &mut self.layout.terminal_height,
+            0.1..=0.9,
+            default.layout.terminal_height,
+        );
+        check(
+            "layout.side_panel_width",
+            &mut self.layout.side_panel_width,
+            150.0..=1000.0,
+            default.layout.side_panel_width,
+        );
+        check(
+            "format.timeout",
+            &mut self.format.timeout,
+            0.1..=60.0,
+            default.format.timeout,
+        );
+        if self
+            .editor
+            .rulers
+            .iter()
+            .any(|column| !(1..=1000).contains(column))
+        {
+            problems.push("settings: editor.rulers must be between 1 and 1000".to_string());
+            self.editor
+                .rulers
+                .retain(|column| (1..=1000).contains(column));
+        }
+        if !(1..=10).contains(&self.completion.min_chars) {
+            problems.push("settings: completion.min_chars must be between 1 and 10".to_string());
+            self.completion.min_chars = default.completion.min_chars;
+        }
+    }
+}
+
+/// Which file settings are saved to.
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum Scope {
+    #[default]
+    Global,
+    Project,
+}
+
+/// The settings in effect, reloaded when one of their files changes.
+#[derive(Default)]
+pub struct Config {
+    pub settings: Settings,
+    /// Syntax, schema and range errors of the files.
+    pub problems: Vec<String>,
+    project: Option<PathBuf>,
+    loaded: bool,
+    watcher: Option<RecommendedWatcher>,
+    events: Option<Receiver<notify::Result<notify::Event>>>,
+}
+
+impl Config {
+    pub fn global_path() -> Option<PathBuf> {
+        eframe::storage_dir("eta").map(|dir| dir.join("settings.toml"))
+    }
+
+    /// `.eta/settings.toml` in the project, it overrides the global file.
+    pub fn project_path(project: &Path) -> PathBuf {
+        project.join(".eta").join("settings.toml")
+    }
+
+    pub fn path(&self, scope: Scope) -> Option<PathBuf> {
+        match scope {
+            Scope::Global => Self::global_path(),
+            Scope::Project => self.project.as_deref().map(Self::project_path),
+        }
+    }
+
+    /// Reads the global file and the one of `project`.
+    pub fn load(project: Option<&Path>) -> (Settings, Vec<String>) {
+        let mut problems = Vec::new();
+        let mut merged = Table::new();
+        let files = Self::global_path()
+            .into_iter()
+            .chain(project.map(Self::project_path));
+        for path in files {
+            if let Some(table) = read_table(&path, &mut problems) {
+                merge(&mut merged, table);
+            }
+        }
+        let mut settings = toml::Value::Table(merged)
+            .try_into::<Settings>()
+            .unwrap_or_default();
+        settings.validate(&mut problems);
+        (settings, problems)
+    }
+
+    /// Catches up with `project` and the changes of the files, call once per frame.
+    /// Returns the previous settings if they changed.
+    pub fn sync(&mut self, ctx: &Context, project: Option<&Path>) -> Option<Settings> {
+        let mut reload = !self.loaded || self.project.as_deref() != project;
+        self.project = project.map(Path::to_path_buf);
+        if let Some(events) = &self.events {
+            for event in events.try_iter().flatten() {
+                reload |= event.paths.iter().any(|path| {
+                    let name = path.file_name().unwrap_or_default();
+                    name == "settings.toml" || name == ".eta"
+                });
+            }
+        }
+        if !reload {
+            return None;
+        }
+
+        // watching again picks up a new `.eta`
+        self.watch(ctx);
+        self.loaded = true;
+        let (settings, problems) = Self::load(self.project.as_deref());
+        self.problems = problems;
+        (settings != self.settings).then(|| std::mem::replace(&mut self.settings, settings))
+    }
+
+    fn watch(&mut self, ctx: &Context) {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let ctx = ctx.clone();
+        self.watcher = notify::recommended_watcher(move |event| {
+            sender.send(event).unwrap_or_default();
+            ctx.request_repaint();
+        })
+        .ok();
+        self.events = Some(receiver);
+
+        let global_dir = Self::global_path().and_then(|path| Some(path.parent()?.to_path_buf()));
+        let project_dir = self.project.as_ref().map(|project| {
+            let dir = project.join(".eta");
+            if dir.is_dir() {
+                dir
+            } else {
+                project.clone()
+            }
+        });
+        if let Some(watcher) = &mut self.watcher {
+            for dir in global_dir.iter().chain(&project_dir) {
+                watcher.watch(dir, RecursiveMode::NonRecursive).ok();
+            }
+        }
+    }
+
+    /// Writes `settings` to the file of `scope`, the project file only keeps what differs from
+    /// the global settings.
+    pub fn save(&self, scope: Scope, settings: &Settings) -> io::Result<()> {
+        let Some(path) = self.path(scope) else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "No project opened"));
+        };
+        let mut table = Table::try_from(settings).map_err(io::Error::other)?;
+        if scope == Scope::Project {
+            let (global, _) = Self::load(None);
+            let global = Table::try_from(global).map_err(io::Error::other)?;
+            remove_equal(&mut table, &global);
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(
+            path,
+            toml::to_string_pretty(&table).map_err(io::Error::other)?,
+        )
+    }
+}
+
+/// The file as a table if it exists and matches the schema.
+fn read_table(path: &Path, problems: &mut Vec<String>) -> Option<Table> {
+    let content = fs::read_to_string(path).ok()?;
+    let name = path.display();
+    let table = match content.parse::<Table>() {
+        Ok(table) => table,
+        Err(err) => {
+            problems.push(format!("{name}: {}", err.message()));
+            return None;
+        }
+    };
+    if let Err(err) = toml::Value::Table(table.clone()).try_into::<Settings>() {
+        problems.push(format!("{name}: {}", err.message()));
+        return None;
+    }
+    Some(table)
+}
+
+/// Overrides the values of `base` with the ones of `other`, tables are merged key by key.
+fn merge(base: &mut Table, other: Table) {
+    for (key, value) in other {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(other)) => merge(base, other),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Removes the values of `table` which are the same in `other`.
+fn remove_equal(table: &mut Table, other: &Table) {
+    table.retain(|key, value| match (value, other.get(key)) {
+        (toml::Value::Table(table), Some(toml::Value::Table(other))) => {
+            remove_equal(table, other);
+            !table.is_empty()
+        }
+        (value, other) => Some(&*value) != other,
+    });
+}
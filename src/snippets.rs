@@ -0,0 +1,803 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, iter,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use eframe::egui::{
+    text::CCursor,
+    text_selection::{CCursorRange, CursorRange},
+    TextBuffer,
+};
+use serde::Deserialize;
+
+use crate::indent::Indent;
+
+pub const TEMPLATE: &str = r#"{
+	// Snippets in the VS Code format, expanded with Tab after typing the prefix.
+	// "Print to console": {
+	// 	"prefix": "log",
+	// 	"body": ["console.log('$1');", "$0"],
+	// 	"description": "Log output to console"
+	// }
+}
+"#;
+
+pub struct Snippet {
+    pub name: String,
+    pub prefixes: Vec<String>,
+    pub body: String,
+    pub description: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            OneOrMany::One(one) => vec![one],
+            OneOrMany::Many(many) => many,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SnippetEntry {
+    prefix: Option<OneOrMany>,
+    body: OneOrMany,
+    #[serde(default)]
+    description: Option<OneOrMany>,
+}
+
+/// Snippets by language, read when a file of the language is opened.
+#[derive(Default)]
+pub struct Snippets {
+    loaded: HashMap<(String, Option<PathBuf>), Vec<Snippet>>,
+    pub problems: Vec<String>,
+}
+
+impl Snippets {
+    pub fn dir() -> Option<PathBuf> {
+        eframe::storage_dir("eta").map(|dir| dir.join("snippets"))
+    }
+
+    /// The global snippet file of the language of `path`.
+    pub fn path(path: &Path) -> Option<PathBuf> {
+        Some(Self::dir()?.join(format!("{}.json", language(path)?)))
+    }
+
+    /// The global snippets and the ones in `.eta/snippets` of `root` for the language of `path`.
+    pub fn for_path(&mut self, path: &Path, root: Option<&Path>) -> &[Snippet] {
+        let Some(language) = language(path) else {
+            return &[];
+        };
+        let key = (language.to_string(), root.map(Path::to_path_buf));
+        let problems = &mut self.problems;
+        self.loaded.entry(key).or_insert_with(|| {
+            let file = format!("{language}.json");
+            let files = Self::dir()
+                .into_iter()
+                .chain(root.map(|root| root.join(".eta").join("snippets")))
+                .map(|dir| dir.join(&file));
+            let mut snippets = Vec::new();
+            for file in files {
+                match load(&file) {
+                    Ok(loaded) => snippets.extend(loaded),
+                    Err(err) => problems.push(format!("{}: {err}", file.display())),
+                }
+            }
+            snippets
+        })
+    }
+
+    /// Forgets what was read, after a snippet file changed.
+    pub fn clear(&mut self) {
+        self.loaded.clear();
+        self.problems.clear();
+    }
+}
+
+/// The VS Code language id of a file.
+fn language(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?;
+    Some(match extension {
+        "rs" => "rust",
+        "py" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "javascriptreact",
+        "ts" => "typescript",
+        "tsx" => "typescriptreact",
+        "c" | "h" => "c",
+        "cpp" | "cc" | "hpp" => "cpp",
+        "go" => "go",
+        "java" => "java",
+        "md" => "markdown",
+        "json" => "json",
+        "toml" => "toml",
+        "yaml" | "yml" => "yaml",
+        "html" => "html",
+        "css" => "css",
+        "sh" | "bash" => "shellscript",
+        _ => return None,
+    })
+}
+
+fn load(file: &Path) -> Result<Vec<Snippet>, String> {
+    let Ok(content) = fs::read_to_string(file) else {
+        return Ok(Vec::new());
+    };
+    let entries = serde_json::from_str::<BTreeMap<String, SnippetEntry>>(&strip_jsonc(&content))
+        .map_err(|err| err.to_string())?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|(name, entry)| {
+            Some(Snippet {
+                name,
+                prefixes: entry.prefix?.into_vec(),
+                body: entry.body.into_vec().join("\n"),
+                description: entry
+                    .description
+                    .map(|description| description.into_vec().join("\n"))
+                    .unwrap_or_default(),
+            })
+        })
+        .collect())
+}
+
+/// Removes the comments and trailing commas VS Code allows in JSON.
+fn strip_jsonc(content: &str) -> String {
+    let chars = content.chars().collect::<Vec<_>>();
+    let mut stripped = String::new();
+    let mut i = 0;
+    let mut in_string = false;
+    while i < chars.len() {
+        let c = chars[i];
+        if in_string {
+            stripped.push(c);
+            if c == '\\' {
+                stripped.extend(chars.get(i + 1));
+                i += 1;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if let Some(end) = comment_end(&chars, i) {
+            i = end;
+            continue;
+        } else if c == ',' {
+            // comments may follow a trailing comma as well
+            let mut next = i + 1;
+            while next < chars.len() {
+                match comment_end(&chars, next) {
+                    Some(end) => next = end,
+                    None if chars[next].is_whitespace() => next += 1,
+                    None => break,
+                }
+            }
+            if !matches!(chars.get(next), Some('}' | ']')) {
+                stripped.push(c);
+            }
+        } else {
+            in_string = c == '"';
+            stripped.push(c);
+        }
+        i += 1;
+    }
+    stripped
+}
+
+/// The index after the comment starting at `i`, if one does.
+fn comment_end(chars: &[char], i: usize) -> Option<usize> {
+    match chars.get(i..i + 2)? {
+        ['/', '/'] => Some(
+            (i..chars.len())
+                .find(|&end| chars[end] == '\n')
+                .unwrap_or(chars.len()),
+        ),
+        ['/', '*'] => Some(
+            (i + 2..chars.len())
+                .find(|&end| chars[end] == '*' && chars.get(end + 1) == Some(&'/'))
+                .map_or(chars.len(), |end| end + 2),
+        ),
+        _ => None,
+    }
+}
+
+/// The snippets of a file and what their variables refer to.
+pub struct SnippetScope<'a> {
+    pub snippets: &'a [Snippet],
+    pub path: &'a Path,
+    pub root: Option<&'a Path>,
+}
+
+/// A snippet prefix ending at `cursor`, the longest one wins.
+pub fn find_prefix<'a>(
+    snippets: &'a [Snippet],
+    text: &str,
+    cursor: usize,
+) -> Option<(&'a Snippet, usize)> {
+    let before = text.chars().take(cursor).collect::<Vec<_>>();
+    snippets
+        .iter()
+        .flat_map(|snippet| snippet.prefixes.iter().map(move |prefix| (snippet, prefix)))
+        .filter(|(_, prefix)| {
+            let prefix = prefix.chars().collect::<Vec<_>>();
+            let Some(start) = before.len().checked_sub(prefix.len()) else {
+                return false;
+            };
+            if prefix.is_empty() || before[start..] != prefix[..] {
+                return false;
+            }
+            // not in the middle of a word
+            start == 0
+                || !(before[start - 1].is_alphanumeric() || before[start - 1] == '_')
+                || !(prefix[0].is_alphanumeric() || prefix[0] == '_')
+        })
+        .max_by_key(|(_, prefix)| prefix.chars().count())
+        .map(|(snippet, prefix)| (snippet, prefix.chars().count()))
+}
+
+/// A tab stop, `$0` is the last one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stop {
+    pub index: u32,
+    /// The placeholder and its mirrors as char ranges.
+    pub ranges: Vec<Range<usize>>,
+    pub choices: Vec<String>,
+}
+
+/// The text of a snippet body and its tab stops in navigation order.
+#[derive(Debug, PartialEq)]
+pub struct Expansion {
+    pub text: String,
+    pub stops: Vec<Stop>,
+}
+
+/// Expands `body` with the `variables`, continuation lines get the `line_indent`.
+pub fn expand(
+    body: &str,
+    variables: &dyn Fn(&str) -> Option<String>,
+    line_indent: &str,
+    indent: Indent,
+) -> Expansion {
+    // mirrors show the text of their placeholder
+    let mut first = Parser::new(body, variables, line_indent, indent, HashMap::new());
+    first.parse_until(None);
+    let defaults = first
+        .stops
+        .values()
+        .filter_map(|stop| {
+            let range = stop.ranges.iter().find(|range| !range.is_empty())?;
+            Some((stop.index, first.out[range.clone()].iter().collect()))
+        })
+        .collect();
+
+    let mut parser = Parser::new(body, variables, line_indent, indent, defaults);
+    parser.parse_until(None);
+    let end = parser.out.len();
+    let mut stops = parser.stops.into_values().collect::<Vec<_>>();
+    let last = match stops.first().map(|stop| stop.index) {
+        Some(0) => stops.remove(0),
+        _ => Stop {
+            index: 0,
+            ranges: iter::once(end..end).collect(),
+            choices: Vec::new(),
+        },
+    };
+    stops.push(last);
+    Expansion {
+        text: parser.out.into_iter().collect(),
+        stops,
+    }
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    out: Vec<char>,
+    stops: BTreeMap<u32, Stop>,
+    variables: &'a dyn Fn(&str) -> Option<String>,
+    line_indent: &'a str,
+    indent: Indent,
+    defaults: HashMap<u32, String>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(
+        body: &str,
+        variables: &'a dyn Fn(&str) -> Option<String>,
+        line_indent: &'a str,
+        indent: Indent,
+        defaults: HashMap<u32, String>,
+    ) -> Self {
+        Self {
+            chars: body.chars().collect(),
+            pos: 0,
+            out: Vec::new(),
+            stops: BTreeMap::new(),
+            variables,
+            line_indent,
+            indent,
+            defaults,
+        }
+    }
+
+    fn push(&mut self, c: char) {
+        match c {
+            '\n' => {
+                self.out.push('\n');
+                self.out.extend(self.line_indent.chars());
+            }
+            '\t' => self.out.extend(self.indent.unit().chars()),
+            c => self.out.push(c),
+        }
+    }
+
+    fn push_str(&mut self, text: &str) {
+        text.chars().for_each(|c| self.push(c));
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        self.pos += usize::from(found);
+        found
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .ok()
+    }
+
+    fn name(&mut self) -> Option<String> {
+        let start = self.pos;
+        if !self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        {
+            return None;
+        }
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            self.pos += 1;
+        }
+        Some(self.chars[start..self.pos].iter().collect())
+    }
+
+    fn stop(&mut self, index: u32, range: Range<usize>, choices: Vec<String>) {
+        let stop = self.stops.entry(index).or_insert_with(|| Stop {
+            index,
+            ranges: Vec::new(),
+            choices: Vec::new(),
+        });
+        stop.ranges.push(range);
+        if stop.choices.is_empty() {
+            stop.choices = choices;
+        }
+    }
+
+    /// Parses up to the unescaped `end`, consuming it.
+    fn parse_until(&mut self, end: Option<char>) {
+        while let Some(c) = self.peek() {
+            if Some(c) == end {
+                self.pos += 1;
+                return;
+            }
+            self.pos += 1;
+            match c {
+                '\\' => match self.peek() {
+                    Some(escaped @ ('$' | '}' | '\\')) => {
+                        self.pos += 1;
+                        self.push(escaped);
+                    }
+                    _ => self.push('\\'),
+                },
+                '$' => self.dollar(),
+                c => self.push(c),
+            }
+        }
+    }
+
+    fn dollar(&mut self) {
+        let start = self.pos;
+        if let Some(index) = self.number() {
+            let at = self.out.len();
+            let default = self.defaults.get(&index).cloned().unwrap_or_default();
+            self.push_str(&default);
+            self.stop(index, at..self.out.len(), Vec::new());
+            return;
+        }
+        if let Some(name) = self.name() {
+            let value = (self.variables)(&name).unwrap_or(name);
+            self.push_str(&value);
+            return;
+        }
+        if !self.eat('{') {
+            self.push('$');
+            return;
+        }
+
+        if let Some(index) = self.number() {
+            let at = self.out.len();
+            if self.eat('}') {
+                let default = self.defaults.get(&index).cloned().unwrap_or_default();
+                self.push_str(&default);
+                self.stop(index, at..self.out.len(), Vec::new());
+            } else if self.eat(':') {
+                self.parse_until(Some('}'));
+                self.stop(index, at..self.out.len(), Vec::new());
+            } else if self.eat('|') {
+                let choices = self.choices();
+                self.push_str(choices.first().map(String::as_str).unwrap_or_default());
+                self.stop(index, at..self.out.len(), choices);
+            } else {
+                self.pos = start;
+                self.push('$');
+            }
+            return;
+        }
+        if let Some(name) = self.name() {
+            let value = (self.variables)(&name);
+            if self.eat('}') {
+                self.push_str(&value.unwrap_or(name));
+            } else if self.eat(':') {
+                // the default may hold tab stops, they are dropped with it
+                let at = self.out.len();
+                let stops = self.stops.clone();
+                self.parse_until(Some('}'));
+                if let Some(value) = value {
+                    self.out.truncate(at);
+                    self.stops = stops;
+                    self.push_str(&value);
+                }
+            } else {
+                // transforms are not supported, the value is kept as it is
+                while self.peek().is_some_and(|c| c != '}') {
+                    self.pos += 1 + usize::from(self.peek() == Some('\\'));
+                }
+                self.pos += 1;
+                self.push_str(&value.unwrap_or_default());
+            }
+            return;
+        }
+        self.pos = start;
+        self.push('$');
+    }
+
+    /// `a,b,c|}` of `${1|a,b,c|}`.
+    fn choices(&mut self) -> Vec<String> {
+        let mut choices = vec![String::new()];
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '\\' => {
+                    if let Some(escaped) = self.peek() {
+                        self.pos += 1;
+                        choices.last_mut().unwrap().push(escaped);
+                    }
+                }
+                ',' => choices.push(String::new()),
+                '|' if self.eat('}') => break,
+                c => choices.last_mut().unwrap().push(c),
+            }
+        }
+        choices
+    }
+}
+
+/// The variables of a snippet inserted at `cursor` of `text` in the file `path`.
+pub fn variables<'a>(
+    scope: &'a SnippetScope,
+    text: &'a str,
+    cursor: &'a CursorRange,
+) -> impl Fn(&str) -> Option<String> + 'a {
+    move |name| {
+        let path = scope.path;
+        let line = cursor.primary.rcursor.row;
+        let current_line = text.split('\n').nth(line).unwrap_or_default();
+        Some(match name {
+            "TM_FILENAME" => path.file_name()?.to_string_lossy().to_string(),
+            "TM_FILENAME_BASE" => path.file_stem()?.to_string_lossy().to_string(),
+            "TM_DIRECTORY" => path.parent()?.to_string_lossy().to_string(),
+            "TM_FILEPATH" => path.to_string_lossy().to_string(),
+            "RELATIVE_FILEPATH" => path
+                .strip_prefix(scope.root?)
+                .ok()?
+                .to_string_lossy()
+                .to_string(),
+            "WORKSPACE_NAME" => scope.root?.file_name()?.to_string_lossy().to_string(),
+            "WORKSPACE_FOLDER" => scope.root?.to_string_lossy().to_string(),
+            "TM_LINE_INDEX" => line.to_string(),
+            "TM_LINE_NUMBER" => (line + 1).to_string(),
+            "TM_CURRENT_LINE" => current_line.to_string(),
+            "TM_CURRENT_WORD" => {
+                let column = cursor.primary.rcursor.column;
+                let chars = current_line.chars().collect::<Vec<_>>();
+                let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+                let start = chars[..column.min(chars.len())]
+                    .iter()
+                    .rposition(|c| !is_word(c))
+                    .map_or(0, |i| i + 1);
+                let end = chars[start..]
+                    .iter()
+                    .position(|c| !is_word(c))
+                    .map_or(chars.len(), |i| start + i);
+                chars[start..end].iter().collect()
+            }
+            "TM_SELECTED_TEXT" => cursor.slice_str(text).to_string(),
+            "CURRENT_SECONDS_UNIX" => SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .ok()?
+                .as_secs()
+                .to_string(),
+            _ => return None,
+        })
+    }
+}
+
+/// An expanded snippet whose tab stops are visited with Tab and Shift+Tab.
+#[derive(Clone, Debug)]
+pub struct SnippetSession {
+    pub stops: Vec<Stop>,
+    pub current: usize,
+    /// The text the ranges refer to.
+    known: String,
+}
+
+impl SnippetSession {
+    /// `expansion` was inserted at the char index `at` of `text`.
+    pub fn new(expansion: Expansion, at: usize, text: &str) -> Self {
+        let stops = expansion
+            .stops
+            .into_iter()
+            .map(|mut stop| {
+                for range in &mut stop.ranges {
+                    *range = range.start + at..range.end + at;
+                }
+                stop
+            })
+            .collect();
+        Self {
+            stops,
+            current: 0,
+            known: text.to_string(),
+        }
+    }
+
+    /// Selects the placeholder of the current stop.
+    pub fn selection(&self) -> CCursorRange {
+        let range = self.stops[self.current]
+            .ranges
+            .first()
+            .cloned()
+            .unwrap_or_default();
+        CCursorRange::two(CCursor::new(range.start), CCursor::new(range.end))
+    }
+
+    /// Whether the current stop is `$0`, which ends the snippet.
+    pub fn is_done(&self) -> bool {
+        self.current + 1 >= self.stops.len()
+    }
+
+    pub fn next_stop(&mut self) -> CCursorRange {
+        self.current = (self.current + 1).min(self.stops.len() - 1);
+        self.selection()
+    }
+
+    pub fn previous_stop(&mut self) -> CCursorRange {
+        self.current = self.current.saturating_sub(1);
+        self.selection()
+    }
+
+    pub fn choices(&self) -> &[String] {
+        &self.stops[self.current].choices
+    }
+
+    /// Follows the edits made to `text` since the last call, filling the mirrors of the current
+    /// stop and moving the `cursor` along. Returns false if the edit left the snippet.
+    pub fn track(&mut self, text: &mut dyn TextBuffer, cursor: &mut CCursorRange) -> bool {
+        if text.as_str() == self.known {
+            return true;
+        }
+        let old = self.known.chars().collect::<Vec<_>>();
+        let new = text.as_str().chars().collect::<Vec<_>>();
+        let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let (start, end) = (prefix, old.len() - suffix);
+        let inserted = new.len() - suffix - prefix;
+
+        let Some(owner) = self.stops[self.current]
+            .ranges
+            .iter()
+            .position(|range| range.start <= start && end <= range.end)
+        else {
+            return false;
+        };
+        if !self.shift(start..end, inserted, (self.current, owner)) {
+            return false;
+        }
+
+        // from the back, so the ranges before stay valid
+        let range = self.stops[self.current].ranges[owner].clone();
+        let content = new[range].iter().collect::<String>();
+        for mirror in (0..self.stops[self.current].ranges.len()).rev() {
+            let range = self.stops[self.current].ranges[mirror].clone();
+            if mirror == owner || text.char_range(range.clone()) == content {
+                continue;
+            }
+            let inserted = content.chars().count();
+            text.delete_char_range(range.clone());
+            text.insert_text(&content, range.start);
+            for index in [&mut cursor.primary.index, &mut cursor.secondary.index] {
+                if *index >= range.end {
+                    *index = *index + inserted - range.len();
+                }
+            }
+            if !self.shift(range, inserted, (self.current, mirror)) {
+                return false;
+            }
+        }
+        self.known = text.as_str().to_string();
+        true
+    }
+
+    /// Moves the ranges after `edit` and grows the `owner` range.
+    fn shift(&mut self, edit: Range<usize>, inserted: usize, owner: (usize, usize)) -> bool {
+        let moved = |index: usize| index + inserted - edit.len();
+        for (s, stop) in self.stops.iter_mut().enumerate() {
+            for (r, range) in stop.ranges.iter_mut().enumerate() {
+                if (s, r) == owner {
+                    range.end = moved(range.end);
+                } else if range.start >= edit.end {
+                    *range = moved(range.start)..moved(range.end);
+                } else if range.end <= edit.start {
+                } else if range.start < edit.start && edit.end < range.end {
+                    range.end = moved(range.end);
+                } else if edit.start <= range.start && range.end <= edit.end {
+                    *range = edit.start..edit.start;
+                } else {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables(name: &str) -> Option<String> {
+        (name == "TM_FILENAME").then(|| "main.rs".to_string())
+    }
+
+    fn expand_plain(body: &str) -> Expansion {
+        expand(body, &variables, "", Indent::Tabs)
+    }
+
+    #[test]
+    fn expands_tab_stops() {
+        let expansion = expand(
+            "fn ${1:name}($2) {\n\t$0\n}",
+            &variables,
+            "  ",
+            Indent::Spaces(4),
+        );
+        assert_eq!(expansion.text, "fn name() {\n      \n  }");
+        let indices = expansion
+            .stops
+            .iter()
+            .map(|stop| stop.index)
+            .collect::<Vec<_>>();
+        assert_eq!(indices, vec![1, 2, 0]);
+        assert_eq!(expansion.stops[0].ranges, vec![3..7]);
+        assert_eq!(expansion.stops[1].ranges, vec![8..8]);
+        assert_eq!(expansion.stops[2].ranges, vec![18..18]);
+
+        // without `$0` the snippet ends after the text
+        let expansion = expand_plain("a$1b");
+        assert_eq!(expansion.stops[1].index, 0);
+        assert_eq!(expansion.stops[1].ranges, vec![2..2]);
+    }
+
+    #[test]
+    fn expands_nested_placeholders_and_variables() {
+        let expansion = expand_plain("${1:x ${2:y}} $TM_FILENAME ${NOPE:z} ${TM_FILENAME/x/}");
+        assert_eq!(expansion.text, "x y main.rs z main.rs");
+        assert_eq!(expansion.stops[0].ranges, vec![0..3]);
+        assert_eq!(expansion.stops[1].ranges, vec![2..3]);
+    }
+
+    #[test]
+    fn mirrors_placeholders() {
+        let expansion = expand_plain("$1 ${1:name} ${1}");
+        assert_eq!(expansion.text, "name name name");
+        assert_eq!(expansion.stops[0].ranges, vec![0..4, 5..9, 10..14]);
+
+        let expansion = expand_plain("${1:a}-$1-$2");
+        let mut session = SnippetSession::new(expansion, 1, "xa-a-");
+        let mut text = "xab-a-".to_string();
+        let mut cursor = CCursorRange::one(CCursor::new(3));
+        assert!(session.track(&mut text, &mut cursor));
+        assert_eq!(text, "xab-ab-");
+        assert_eq!(session.stops[1].ranges, vec![7..7]);
+        // an edit outside of the stops ends the snippet
+        text.insert(0, 'q');
+        assert!(!session.track(&mut text, &mut cursor));
+    }
+
+    #[test]
+    fn expands_choices() {
+        let expansion = expand_plain("${1|one,t\\,wo,th\\|ree|} $1");
+        assert_eq!(expansion.text, "one one");
+        assert_eq!(expansion.stops[0].choices, vec!["one", "t,wo", "th|ree"]);
+        let session = SnippetSession::new(expansion, 0, "one one");
+        assert_eq!(session.choices(), ["one", "t,wo", "th|ree"]);
+    }
+
+    #[test]
+    fn keeps_escaped_characters() {
+        assert_eq!(expand_plain("\\$1 \\\\ \\a").text, "$1 \\ \\a");
+        assert_eq!(expand_plain("${1:a\\}b}").text, "a}b");
+        assert_eq!(expand_plain("$ ${ $unknown").text, "$ ${ unknown");
+        assert_eq!(expand_plain("a $").text, "a $");
+    }
+
+    #[test]
+    fn finds_prefixes() {
+        let snippets = vec![Snippet {
+            name: "function".to_string(),
+            prefixes: vec!["fn".to_string(), "f".to_string()],
+            body: String::new(),
+            description: String::new(),
+        }];
+        assert_eq!(
+            find_prefix(&snippets, "a fn", 4).map(|found| found.1),
+            Some(2)
+        );
+        assert_eq!(find_prefix(&snippets, "f", 1).map(|found| found.1), Some(1));
+        assert!(find_prefix(&snippets, "afn", 3).is_none());
+        assert!(find_prefix(&snippets, "fn a", 4).is_none());
+    }
+
+    #[test]
+    fn strips_jsonc() {
+        assert_eq!(
+            strip_jsonc("{\"a//\": 1, // c\n /* x */ \"b\": [1,],\n}"),
+            "{\"a//\": 1, \n  \"b\": [1]\n}"
+        );
+        assert_eq!(
+            strip_jsonc("{\"x\": {\"a\": 1}, // note\n/* end */ }"),
+            "{\"x\": {\"a\": 1} \n }"
+        );
+        assert_eq!(strip_jsonc("[1, /* 2 */ 3 /* open"), "[1,  3 ");
+        assert!(serde_json::from_str::<serde_json::Value>(&strip_jsonc(
+            "{\n\t\"x\": {\"body\": \"a\"}, // note\n}\n"
+        ))
+        .is_ok());
+    }
+}
@@ -0,0 +1,160 @@
+use std::path::Path;
+
+/// A definition or heading found in a file.
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: &'static str,
+    /// 0-based
+    pub line: usize,
+    /// 0-based
+    pub column: usize,
+}
+
+const KEYWORDS: &[(&str, &str)] = &[
+    ("fn", "function"),
+    ("struct", "struct"),
+    ("enum", "enum"),
+    ("union", "union"),
+    ("trait", "trait"),
+    ("impl", "impl"),
+    ("mod", "module"),
+    ("type", "type"),
+    ("const", "constant"),
+    ("static", "static"),
+    ("macro_rules!", "macro"),
+    ("class", "class"),
+    ("interface", "interface"),
+    ("def", "function"),
+    ("function", "function"),
+    ("func", "function"),
+];
+
+const MODIFIERS: &[&str] = &[
+    "pub", "async", "unsafe", "extern", "export", "default", "abstract", "final",
+];
+
+/// Finds the symbols of `text` by looking at the start of every line.
+pub fn symbols(path: &Path, text: &str) -> Vec<Symbol> {
+    let markdown = path
+        .extension()
+        .is_some_and(|ext| ext == "md" || ext == "markdown");
+    if markdown {
+        headings(text)
+    } else {
+        definitions(text)
+    }
+}
+
+fn headings(text: &str) -> Vec<Symbol> {
+    let mut in_code = false;
+    let mut symbols = Vec::new();
+    for (line, content) in text.lines().enumerate() {
+        if content.trim_start().starts_with("```") {
+            in_code = !in_code;
+        }
+        let level = content.chars().take_while(|c| *c == '#').count();
+        if in_code || level == 0 || !content[level..].starts_with(' ') {
+            continue;
+        }
+        symbols.push(Symbol {
+            name: format!("{} {}", "#".repeat(level), content[level..].trim()),
+            kind: "heading",
+            line,
+            column: 0,
+        });
+    }
+    symbols
+}
+
+fn definitions(text: &str) -> Vec<Symbol> {
+    let mut symbols = Vec::new();
+    for (line, content) in text.lines().enumerate() {
+        let trimmed = content.trim_start();
+        let column = content.chars().count() - trimmed.chars().count();
+        let mut rest = trimmed;
+
+        // visibility and other modifiers
+        loop {
+            let word = first_word(rest);
+            if !MODIFIERS.contains(&word) {
+                break;
+            }
+            rest = rest[word.len()..].trim_start();
+            if rest.starts_with('(') {
+                // pub(crate)
+                rest = rest.split_once(')').map(|(_, r)| r).unwrap_or_default();
+            } else if rest.starts_with('"') {
+                // extern "C"
+                rest = rest[1..]
+                    .split_once('"')
+                    .map(|(_, r)| r)
+                    .unwrap_or_default();
+            }
+            rest = rest.trim_start();
+        }
+        // `const fn` is a function
+        if first_word(rest) == "const" && first_word(rest[5..].trim_start()) == "fn" {
+            rest = rest[5..].trim_start();
+        }
+
+        let word = first_word(rest);
+        let Some((_, kind)) = KEYWORDS.iter().find(|(keyword, _)| *keyword == word) else {
+            continue;
+        };
+        let after = rest[word.len()..].trim_start();
+        let name = if word == "impl" {
+            // `impl<T> Trait for Type`
+            let after = skip_generics(after);
+            after
+                .split(['{', ';'])
+                .next()
+                .unwrap_or_default()
+                .split(" where")
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_string()
+        } else {
+            after
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '$')
+                .collect()
+        };
+        if name.is_empty() {
+            continue;
+        }
+        symbols.push(Symbol {
+            name,
+            kind,
+            line,
+            column,
+        });
+    }
+    symbols
+}
+
+fn first_word(text: &str) -> &str {
+    let end = text
+        .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '!'))
+        .unwrap_or(text.len());
+    &text[..end]
+}
+
+fn skip_generics(text: &str) -> &str {
+    if !text.starts_with('<') {
+        return text;
+    }
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            return text[i + 1..].trim_start();
+        }
+    }
+    text
+}
@@ -18,7 +18,7 @@ use crossbeam_channel::{unbounded, Receiver};
 use termwiz::cellcluster::CellCluster;
 use wezterm_term::{Terminal as WezTerm, TerminalSize};
 
-use egui::{Color32, Event, FontId, InputState, Modifiers, Response, TextFormat, Ui, Vec2};
+use egui::{Color32, Event, InputState, Modifiers, Response, TextFormat, Ui, Vec2};
 
 use super::config::definitions::TermResult;
 use super::config::term_config::{Config, Style};
@@ -189,8 +189,8 @@ impl TermHandler {
         modifiers: Modifiers,
     ) -> TermResult {
         let relative_pos = *pos - response.rect.min;
-        let char_x = (relative_pos.x / 12.0) as usize;
-        let char_y = (relative_pos.y / 12.0) as i64;
+        let char_x = (relative_pos.x / self.text_width) as usize;
+        let char_y = (relative_pos.y / self.text_height) as i64;
         self.terminal.mouse_event(wezterm_term::MouseEvent {
             kind: wezterm_term::MouseEventKind::Move,
             x: char_x,
@@ -333,7 +333,7 @@ impl TermHandler {
         let size = self.size;
 
         let mono_format = TextFormat {
-            font_id: FontId::monospace(12.0),
+            font_id: self.style.font.clone(),
             ..Default::default()
         };
         let total_height = self.terminal.screen().scrollback_rows() as f32;
@@ -0,0 +1,1960 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use eframe::egui::{
+    text::CCursor, text_edit::TextEditState, text_selection::CCursorRange, Event, Key, TextBuffer,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::indent::Indent;
+
+/// The modes of the vim layer.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    #[default]
+    Normal,
+    Insert,
+    Visual,
+    VisualLine,
+    Command,
+}
+
+impl Mode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Mode::Normal => "NORMAL",
+            Mode::Insert => "INSERT",
+            Mode::Visual => "VISUAL",
+            Mode::VisualLine => "VISUAL LINE",
+            Mode::Command => "COMMAND",
+        }
+    }
+
+    fn is_visual(&self) -> bool {
+        matches!(self, Mode::Visual | Mode::VisualLine)
+    }
+}
+
+/// An ex command (`:w`, `:q`, `:e`, ...) which has to be executed by the app.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ExCommand {
+    Write,
+    Quit { force: bool },
+    QuitAll { force: bool },
+    Edit { path: Option<PathBuf>, force: bool },
+}
+
+/// What the editor should do after the vim layer saw an event.
+pub enum VimResponse {
+    /// The event is not handled by vim, the editor should process it.
+    Pass,
+    /// The event was swallowed without any effect on text or cursor.
+    Consumed,
+    /// Only the cursor moved.
+    Cursor(CCursorRange),
+    /// The text was changed.
+    Changed(CCursorRange),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum VimKey {
+    Char(char),
+    Ctrl(char),
+    Esc,
+    Enter,
+    Backspace,
+}
+
+#[derive(Clone, Default)]
+struct Register {
+    text: String,
+    linewise: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordForward(bool),
+    WordBackward(bool),
+    WordEnd(bool),
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    FileStart,
+    FileEnd,
+    ParagraphForward,
+    ParagraphBackward,
+    MatchPair,
+    Find {
+        forward: bool,
+        till: bool,
+        char: char,
+    },
+    RepeatFind {
+        reverse: bool,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum TextObject {
+    Word {
+        inner: bool,
+        big: bool,
+    },
+    Quote {
+        inner: bool,
+        quote: char,
+    },
+    Pair {
+        inner: bool,
+        open: char,
+        close: char,
+    },
+    Paragraph {
+        inner: bool,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Outdent,
+    ToggleCase,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Target {
+    Motion(Motion),
+    Object(TextObject),
+    Line,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Action {
+    Insert,
+    Append,
+    InsertLineStart,
+    AppendLineEnd,
+    OpenBelow,
+    OpenAbove,
+    PasteAfter,
+    PasteBefore,
+    Undo,
+    Redo,
+    JoinLines,
+    Replace(char),
+    ToggleCase,
+    Repeat,
+    Visual,
+    VisualLine,
+    Command,
+    SwapAnchor,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum CommandKind {
+    Move(Motion),
+    Operate(Operator, Target),
+    /// An operator applied on the visual selection.
+    OperateSelection(Operator),
+    /// Selects a text object in visual mode.
+    Select(TextObject),
+    Action(Action),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Command {
+    register: Option<char>,
+    count: Option<usize>,
+    kind: CommandKind,
+}
+
+impl Command {
+    /// Whether this command can be repeated with `.`.
+    fn is_change(&self) -> bool {
+        match self.kind {
+            CommandKind::Operate(op, _) => op != Operator::Yank,
+            CommandKind::Action(action) => !matches!(
+                action,
+                Action::Undo
+                    | Action::Redo
+                    | Action::Repeat
+                    | Action::Visual
+                    | Action::VisualLine
+                    | Action::Command
+                    | Action::SwapAnchor
+            ),
+            _ => false,
+        }
+    }
+}
+
+enum Parse {
+    Incomplete,
+    Invalid,
+    Done(Command),
+}
+
+/// A range of characters an operator acts on.
+#[derive(Clone, Copy, Debug)]
+struct Span {
+    start: usize,
+    end: usize,
+    linewise: bool,
+}
+
+/// Optional modal editing layer for the code editor.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Vim {
+    pub enabled: bool,
+    /// Inserted by `>`, the editor sets it for the current file.
+    #[serde(skip)]
+    pub indent: Indent,
+    #[serde(skip)]
+    pub mode: Mode,
+    #[serde(skip)]
+    pub command_line: String,
+    #[serde(skip)]
+    pub message: Option<String>,
+    #[serde(skip)]
+    pending: Vec<VimKey>,
+    #[serde(skip)]
+    registers: HashMap<char, Register>,
+    #[serde(skip)]
+    last_change: Vec<VimKey>,
+    #[serde(skip)]
+    recording: Option<Vec<VimKey>>,
+    #[serde(skip)]
+    last_find: Option<Motion>,
+    #[serde(skip)]
+    anchor: usize,
+    #[serde(skip)]
+    head: usize,
+    #[serde(skip)]
+    replaying: bool,
+    #[serde(skip)]
+    commands: Vec<ExCommand>,
+    #[serde(skip)]
+    clipboard: Option<String>,
+}
+
+impl Vim {
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.mode = Mode::Normal;
+        self.pending.clear();
+        self.command_line.clear();
+        self.message = None;
+    }
+
+    /// Whether the cursor should be drawn as a block.
+    pub fn block_cursor(&self) -> bool {
+        self.enabled && self.mode != Mode::Insert
+    }
+
+    /// The keys typed so far for an incomplete command, e.g. `2d`.
+    pub fn pending_keys(&self) -> String {
+        self.pending
+            .iter()
+            .map(|key| match key {
+                VimKey::Char(c) => c.to_string(),
+                VimKey::Ctrl(c) => format!("^{}", c.to_ascii_uppercase()),
+                VimKey::Esc => "<Esc>".to_string(),
+                VimKey::Enter => "<CR>".to_string(),
+                VimKey::Backspace => "<BS>".to_string(),
+            })
+            .collect()
+    }
+
+    /// Ex commands the app has to execute.
+    pub fn take_commands(&mut self) -> Vec<ExCommand> {
+        std::mem::take(&mut self.commands)
+    }
+
+    /// Text which was yanked into the `+`/`*` register.
+    pub fn take_clipboard(&mut self) -> Option<String> {
+        self.clipboard.take()
+    }
+
+    pub fn on_event(
+        &mut self,
+        event: &Event,
+        text: &mut dyn TextBuffer,
+        state: &mut TextEditState,
+        cursor: CCursorRange,
+    ) -> VimResponse {
+        if !self.enabled {
+            return VimResponse::Pass;
+        }
+
+        if self.mode == Mode::Insert {
+            return match event {
+                Event::Key {
+                    key: Key::Escape,
+                    pressed: true,
+                    ..
+                } => self.feed(VimKey::Esc, text, state, cursor),
+                Event::Text(t) | Event::Paste(t) => {
+                    t.chars().for_each(|c| self.record(VimKey::Char(c)));
+                    VimResponse::Pass
+                }
+                Event::Key {
+                    key: Key::Enter,
+                    pressed: true,
+                    ..
+                } => {
+                    self.record(VimKey::Enter);
+                    VimResponse::Pass
+                }
+                Event::Key {
+                    key: Key::Backspace,
+                    pressed: true,
+                    ..
+                } => {
+                    self.record(VimKey::Backspace);
+                    VimResponse::Pass
+                }
+                Event::Key {
+                    key: Key::Tab,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } if !modifiers.shift => {
+                    self.record(VimKey::Char('\t'));
+                    VimResponse::Pass
+                }
+                _ => VimResponse::Pass,
+            };
+        }
+
+        let keys = match event {
+            Event::Text(t) => t.chars().map(VimKey::Char).collect(),
+            Event::Paste(t) if self.mode == Mode::Command => t.chars().map(VimKey::Char).collect(),
+            Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+                ..
+            } => match key {
+                Key::Escape => vec![VimKey::Esc],
+                Key::Enter => vec![VimKey::Enter],
+                Key::Backspace => vec![VimKey::Backspace],
+                Key::ArrowLeft => vec![VimKey::Char('h')],
+                Key::ArrowRight => vec![VimKey::Char('l')],
+                Key::ArrowUp => vec![VimKey::Char('k')],
+                Key::ArrowDown => vec![VimKey::Char('j')],
+                Key::Home => vec![VimKey::Char('0')],
+                Key::End => vec![VimKey::Char('$')],
+                Key::PageUp | Key::PageDown if !self.mode.is_visual() => return VimResponse::Pass,
+                Key::R if modifiers.ctrl => vec![VimKey::Ctrl('r')],
+                _ => return VimResponse::Consumed,
+            },
+            Event::Copy => return VimResponse::Pass,
+            Event::Cut | Event::Paste(_) => return VimResponse::Consumed,
+            _ => return VimResponse::Pass,
+        };
+
+        let mut cursor = cursor;
+        let mut changed = false;
+        for key in keys {
+            match self.feed(key, text, state, cursor) {
+                VimResponse::Cursor(c) => cursor = c,
+                VimResponse::Changed(c) => {
+                    cursor = c;
+                    changed = true;
+                }
+                _ => {}
+            }
+        }
+        if changed {
+            VimResponse::Changed(cursor)
+        } else {
+            VimResponse::Cursor(cursor)
+        }
+    }
+
+    fn record(&mut self, key: VimKey) {
+        if self.replaying {
+            return;
+        }
+        if let Some(recording) = &mut self.recording {
+            recording.push(key);
+        }
+    }
+
+    fn feed(
+        &mut self,
+        key: VimKey,
+        text: &mut dyn TextBuffer,
+        state: &mut TextEditState,
+        cursor: CCursorRange,
+    ) -> VimResponse {
+        match self.mode {
+            Mode::Insert => self.feed_insert(key, text, cursor),
+            Mode::Command => self.feed_command(key, text, cursor),
+            Mode::Normal | Mode::Visual | Mode::VisualLine => {
+                if key == VimKey::Esc {
+                    let was_pending = !self.pending.is_empty();
+                    self.pending.clear();
+                    if self.mode.is_visual() && !was_pending {
+                        self.mode = Mode::Normal;
+                        let chars = chars(text);
+                        return VimResponse::Cursor(one(clamp_normal(&chars, self.head)));
+                    }
+                    return VimResponse::Consumed;
+                }
+
+                self.message = None;
+                self.pending.push(key);
+                match parse(&self.pending, self.mode.is_visual()) {
+                    Parse::Incomplete => VimResponse::Consumed,
+                    Parse::Invalid => {
+                        self.pending.clear();
+                        VimResponse::Consumed
+                    }
+                    Parse::Done(command) => {
+                        let keys = std::mem::take(&mut self.pending);
+                        self.execute(command, keys, text, state, cursor)
+                    }
+                }
+            }
+        }
+    }
+
+    fn feed_insert(
+        &mut self,
+        key: VimKey,
+        text: &mut dyn TextBuffer,
+        cursor: CCursorRange,
+    ) -> VimResponse {
+        let cur = cursor.primary.index;
+        match key {
+            VimKey::Esc => {
+                self.mode = Mode::Normal;
+                if let Some(mut recording) = self.recording.take() {
+                    if !self.replaying {
+                        recording.push(VimKey::Esc);
+                        self.last_change = recording;
+                    }
+                }
+                let chars = chars(text);
+                let cur = if cur > line_start(&chars, cur) {
+                    cur - 1
+                } else {
+                    cur
+                };
+                VimResponse::Cursor(one(clamp_normal(&chars, cur)))
+            }
+            VimKey::Char(c) => {
+                let inserted = text.insert_text(&c.to_string(), cur);
+                VimResponse::Changed(one(cur + inserted))
+            }
+            VimKey::Enter => {
+                text.insert_text("\n", cur);
+                VimResponse::Changed(one(cur + 1))
+            }
+            VimKey::Backspace if cur > 0 => {
+                text.delete_char_range(cur - 1..cur);
+                VimResponse::Changed(one(cur - 1))
+            }
+            _ => VimResponse::Consumed,
+        }
+    }
+
+    fn feed_command(
+        &mut self,
+        key: VimKey,
+        text: &mut dyn TextBuffer,
+        cursor: CCursorRange,
+    ) -> VimResponse {
+        match key {
+            VimKey::Char(c) => {
+                self.command_line.push(c);
+                VimResponse::Consumed
+            }
+            VimKey::Backspace => {
+                if self.command_line.pop().is_none() {
+                    self.mode = Mode::Normal;
+                }
+                VimResponse::Consumed
+            }
+            VimKey::Esc => {
+                self.command_line.clear();
+                self.mode = Mode::Normal;
+                VimResponse::Consumed
+            }
+            VimKey::Enter => {
+                self.mode = Mode::Normal;
+                let line = std::mem::take(&mut self.command_line);
+                self.execute_ex(line.trim(), text, cursor)
+            }
+            VimKey::Ctrl(_) => VimResponse::Consumed,
+        }
+    }
+
+    fn execute_ex(
+        &mut self,
+        line: &str,
+        text: &mut dyn TextBuffer,
+        cursor: CCursorRange,
+    ) -> VimResponse {
+        let (name, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let arg = arg.trim();
+        let (name, force) = match name.strip_suffix('!') {
+            Some(name) => (name, true),
+            None => (name, false),
+        };
+
+        match name {
+            "" => {}
+            "w" | "write" => self.commands.push(ExCommand::Write),
+            "q" | "quit" | "close" => self.commands.push(ExCommand::Quit { force }),
+            "wq" | "x" | "exit" => {
+                self.commands.push(ExCommand::Write);
+                self.commands.push(ExCommand::Quit { force });
+            }
+            "qa" | "qall" | "quitall" => self.commands.push(ExCommand::QuitAll { force }),
+            "wqa" | "xa" => {
+                self.commands.push(ExCommand::Write);
+                self.commands.push(ExCommand::QuitAll { force });
+            }
+            "e" | "edit" => self.commands.push(ExCommand::Edit {
+                path: (!arg.is_empty()).then(|| PathBuf::from(arg)),
+                force,
+            }),
+            _ => {
+                if let Ok(line) = name.parse::<usize>() {
+                    let chars = chars(text);
+                    let index = line_index(&chars, line.saturating_sub(1));
+                    return VimResponse::Cursor(one(first_non_blank(&chars, index)));
+                }
+                self.message = Some(format!("Not an editor command: {line}"));
+            }
+        }
+        VimResponse::Cursor(cursor)
+    }
+
+    fn execute(
+        &mut self,
+        command: Command,
+        keys: Vec<VimKey>,
+        text: &mut dyn TextBuffer,
+        state: &mut TextEditState,
+        cursor: CCursorRange,
+    ) -> VimResponse {
+        let chars = chars(text);
+        let cur = if self.mode.is_visual() {
+            self.head
+        } else {
+            cursor.primary.index.min(chars.len())
+        };
+        let count = command.count.unwrap_or(1);
+
+        if command.is_change() && !self.replaying && !self.mode.is_visual() {
+            self.recording = Some(keys);
+        }
+
+        let response = match command.kind {
+            CommandKind::Move(motion) => {
+                let (target, _) = self.motion(&chars, cur, motion, command.count);
+                if self.mode.is_visual() {
+                    self.head = clamp_normal(&chars, target);
+                    VimResponse::Cursor(self.visual_range(&chars))
+                } else {
+                    VimResponse::Cursor(one(clamp_normal(&chars, target)))
+                }
+            }
+            CommandKind::Operate(op, target) => {
+                match self.span(&chars, cur, op, target, command.count) {
+                    Some(span) => self.operate(op, command.register, span, cur, text, &chars),
+                    None => VimResponse::Consumed,
+                }
+            }
+            CommandKind::OperateSelection(op) => {
+                let [min, max] = sorted(self.anchor, self.head);
+                let linewise = self.mode == Mode::VisualLine;
+                let span = Span {
+                    start: min,
+                    end: (max + 1).min(chars.len()),
+                    linewise,
+                };
+                self.mode = Mode::Normal;
+                self.operate(op, command.register, span, min, text, &chars)
+            }
+            CommandKind::Select(object) => match text_object(&chars, self.head, object) {
+                Some(span) => {
+                    if self.anchor == self.head || self.anchor > span.start {
+                        self.anchor = span.start;
+                    }
+                    self.head = span.end.saturating_sub(1).max(span.start);
+                    if span.linewise {
+                        self.mode = Mode::VisualLine;
+                    }
+                    VimResponse::Cursor(self.visual_range(&chars))
+                }
+                None => VimResponse::Consumed,
+            },
+            CommandKind::Action(action) => self.action(
+                action,
+                command.register,
+                count,
+                cur,
+                cursor,
+                text,
+                state,
+                &chars,
+            ),
+        };
+
+        if self.mode != Mode::Insert {
+            if let Some(recording) = self.recording.take() {
+                if !self.replaying {
+                    self.last_change = recording;
+                }
+            }
+        }
+
+        response
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn action(
+        &mut self,
+        action: Action,
+        register: Option<char>,
+        count: usize,
+        cur: usize,
+        cursor: CCursorRange,
+        text: &mut dyn TextBuffer,
+        state: &mut TextEditState,
+        chars: &[char],
+    ) -> VimResponse {
+        match action {
+            Action::Insert => self.insert_at(cur),
+            Action::Append => {
+                let end = line_end(chars, cur);
+                self.insert_at((cur + 1).min(end))
+            }
+            Action::InsertLineStart => self.insert_at(first_non_blank(chars, cur)),
+            Action::AppendLineEnd => self.insert_at(line_end(chars, cur)),
+            Action::OpenBelow => {
+                let end = line_end(chars, cur);
+                let indent = indentation(chars, cur);
+                text.insert_text(&format!("\n{indent}"), end);
+                self.mode = Mode::Insert;
+                VimResponse::Changed(one(end + 1 + indent.chars().count()))
+            }
+            Action::OpenAbove => {
+                let start = line_start(chars, cur);
+                let indent = indentation(chars, cur);
+                text.insert_text(&format!("{indent}\n"), start);
+                self.mode = Mode::Insert;
+                VimResponse::Changed(one(start + indent.chars().count()))
+            }
+            Action::PasteAfter | Action::PasteBefore => {
+                let name = register.unwrap_or('"').to_ascii_lowercase();
+                let Some(reg) = self.registers.get(&name).cloned() else {
+                    return VimResponse::Consumed;
+                };
+                let after = action == Action::PasteAfter;
+                if self.mode.is_visual() {
+                    // replace the selection with the register content
+                    let [min, max] = sorted(self.anchor, self.head);
+                    let linewise = self.mode == Mode::VisualLine;
+                    let span = line_span(chars, min, (max + 1).min(chars.len()), linewise);
+                    self.mode = Mode::Normal;
+                    text.delete_char_range(span.start..span.end);
+                    let content = reg.text.repeat(count);
+                    let inserted = text.insert_text(&content, span.start);
+                    return VimResponse::Changed(one(span.start + inserted.saturating_sub(1)));
+                }
+                if reg.linewise {
+                    let mut content = reg.text.repeat(count);
+                    let at = if after {
+                        let end = line_end(chars, cur);
+                        if end == chars.len() {
+                            content = format!("\n{}", content.trim_end_matches('\n'));
+                            end
+                        } else {
+                            end + 1
+                        }
+                    } else {
+                        line_start(chars, cur)
+                    };
+                    text.insert_text(&content, at);
+                    let chars = self::chars(text);
+                    let line = if content.starts_with('\n') {
+                        at + 1
+                    } else {
+                        at
+                    };
+                    VimResponse::Changed(one(first_non_blank(&chars, line)))
+                } else {
+                    let at = if after && cur < line_end(chars, cur) {
+                        cur + 1
+                    } else {
+                        cur
+                    };
+                    let inserted = text.insert_text(&reg.text.repeat(count), at);
+                    VimResponse::Changed(one(at + inserted.saturating_sub(1)))
+                }
+            }
+            Action::Undo | Action::Redo => {
+                let mut cursor = cursor;
+                let mut changed = false;
+                for _ in 0..count {
+                    let mut undoer = state.undoer();
+                    let current = (cursor, text.as_str().to_owned());
+                    let restored = if action == Action::Undo {
+                        undoer.undo(&current).cloned()
+                    } else {
+                        undoer.redo(&current).cloned()
+                    };
+                    state.set_undoer(undoer);
+                    let Some((range, content)) = restored else {
+                        break;
+                    };
+                    text.replace_with(&content);
+                    cursor = CCursorRange::one(range.primary);
+                    changed = true;
+                }
+                if changed {
+                    let chars = self::chars(text);
+                    VimResponse::Changed(one(clamp_normal(&chars, cursor.primary.index)))
+                } else {
+                    self.message = Some(match action {
+                        Action::Undo => "Already at oldest change".to_string(),
+                        _ => "Already at newest change".to_string(),
+                    });
+                    VimResponse::Consumed
+                }
+            }
+            Action::JoinLines => {
+                let mut chars = chars.to_vec();
+                let mut at = cur;
+                for _ in 0..count.max(2) - 1 {
+                    let end = line_end(&chars, at);
+                    if end >= chars.len() {
+                        break;
+                    }
+                    let mut next = end + 1;
+                    while next < chars.len() && (chars[next] == ' ' || chars[next] == '\t') {
+                        next += 1;
+                    }
+                    let separator = match chars.get(next) {
+                        None | Some('\n') | Some(')') => "",
+                        _ if end > 0 && chars[end - 1] == ' ' => "",
+                        _ => " ",
+                    };
+                    text.delete_char_range(end..next);
+                    text.insert_text(separator, end);
+                    chars = self::chars(text);
+                    at = end;
+                }
+                VimResponse::Changed(one(at))
+            }
+            Action::Replace(c) => {
+                let end = line_end(chars, cur);
+                if cur + count > end {
+                    return VimResponse::Consumed;
+                }
+                text.delete_char_range(cur..cur + count);
+                text.insert_text(&c.to_string().repeat(count), cur);
+                VimResponse::Changed(one(cur + count - 1))
+            }
+            Action::ToggleCase => {
+                let end = (cur + count).min(line_end(chars, cur));
+                if end <= cur {
+                    return VimResponse::Consumed;
+                }
+                let toggled: String = chars[cur..end].iter().map(|c| toggle_case(*c)).collect();
+                text.delete_char_range(cur..end);
+                text.insert_text(&toggled, cur);
+                let chars = self::chars(text);
+                VimResponse::Changed(one(clamp_normal(&chars, end)))
+            }
+            Action::Repeat => {
+                let keys = self.last_change.clone();
+                if keys.is_empty() {
+                    return VimResponse::Consumed;
+                }
+                self.replaying = true;
+                let mut cursor = cursor;
+                let mut changed = false;
+                for _ in 0..count {
+                    for key in &keys {
+                        match self.feed(*key, text, state, cursor) {
+                            VimResponse::Cursor(c) => cursor = c,
+                            VimResponse::Changed(c) => {
+                                cursor = c;
+                                changed = true;
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                self.replaying = false;
+                if changed {
+                    VimResponse::Changed(cursor)
+                } else {
+                    VimResponse::Cursor(cursor)
+                }
+            }
+            Action::Visual | Action::VisualLine => {
+                let mode = if action == Action::Visual {
+                    Mode::Visual
+                } else {
+                    Mode::VisualLine
+                };
+                if self.mode == mode {
+                    self.mode = Mode::Normal;
+                    return VimResponse::Cursor(one(self.head));
+                }
+                if !self.mode.is_visual() {
+                    self.anchor = cur;
+                    self.head = cur;
+                }
+                self.mode = mode;
+                VimResponse::Cursor(self.visual_range(chars))
+            }
+            Action::SwapAnchor => {
+                std::mem::swap(&mut self.anchor, &mut self.head);
+                VimResponse::Cursor(self.visual_range(chars))
+            }
+            Action::Command => {
+                self.mode = Mode::Command;
+                self.command_line.clear();
+                VimResponse::Cursor(one(cur))
+            }
+        }
+    }
+
+    fn insert_at(&mut self, index: usize) -> VimResponse {
+        self.mode = Mode::Insert;
+        VimResponse::Cursor(one(index))
+    }
+
+    /// The selection shown while in visual mode.
+    fn visual_range(&self, chars: &[char]) -> CCursorRange {
+        let [min, max] = sorted(self.anchor, self.head);
+        let (min, max) = if self.mode == Mode::VisualLine {
+            (line_start(chars, min), line_end(chars, max))
+        } else {
+            (min, (max + 1).min(line_end(chars, max).max(max)))
+        };
+        if self.head >= self.anchor {
+            CCursorRange::two(CCursor::new(min), CCursor::new(max))
+        } else {
+            CCursorRange::two(CCursor::new(max), CCursor::new(min))
+        }
+    }
+
+    /// Returns the target of `motion` and whether it is linewise.
+    fn motion(
+        &mut self,
+        chars: &[char],
+        cur: usize,
+        motion: Motion,
+        count: Option<usize>,
+    ) -> (usize, bool) {
+        let mut pos = cur;
+        match motion {
+            Motion::FileStart | Motion::FileEnd => {
+                let line = match (motion, count) {
+                    (_, Some(count)) => count.saturating_sub(1),
+                    (Motion::FileStart, None) => 0,
+                    _ => usize::MAX,
+                };
+                return (first_non_blank(chars, line_index(chars, line)), true);
+            }
+            Motion::RepeatFind { reverse } => {
+                let Some(Motion::Find {
+                    forward,
+                    till,
+                    char,
+                }) = self.last_find
+                else {
+                    return (cur, false);
+                };
+                let find = Motion::Find {
+                    forward: forward != reverse,
+                    till,
+                    char,
+                };
+                for _ in 0..count.unwrap_or(1) {
+                    // `;` after a `t` must not get stuck in front of the same character
+                    let from = match (till, forward != reverse) {
+                        (true, true) if pos + 1 < chars.len() => pos + 1,
+                        (true, false) if pos > 0 => pos - 1,
+                        _ => pos,
+                    };
+                    match find_in_line(chars, from, find) {
+                        Some(p) => pos = p,
+                        None => break,
+                    }
+                }
+                return (pos, false);
+            }
+            Motion::Find { .. } => self.last_find = Some(motion),
+            _ => {}
+        }
+
+        for _ in 0..count.unwrap_or(1) {
+            pos = match motion {
+                Motion::Left => pos.max(line_start(chars, pos) + 1) - 1,
+                Motion::Right => (pos + 1).min(line_end(chars, pos)),
+                Motion::Up | Motion::Down => {
+                    let line = line_of(chars, pos);
+                    let line = if motion == Motion::Up {
+                        match line.checked_sub(1) {
+                            Some(line) => line,
+                            None => break,
+                        }
+                    } else {
+                        line + 1
+                    };
+                    if line > line_of(chars, chars.len()) {
+                        break;
+                    }
+                    let column = pos - line_start(chars, pos);
+                    let start = line_index(chars, line);
+                    (start + column).min(line_end(chars, start))
+                }
+                Motion::WordForward(big) => word_forward(chars, pos, big),
+                Motion::WordBackward(big) => word_backward(chars, pos, big),
+                Motion::WordEnd(big) => word_end(chars, pos, big),
+                Motion::LineStart => line_start(chars, pos),
+                Motion::FirstNonBlank => first_non_blank(chars, pos),
+                Motion::LineEnd => line_end(chars, pos),
+                Motion::ParagraphForward => paragraph(chars, pos, true),
+                Motion::ParagraphBackward => paragraph(chars, pos, false),
+                Motion::MatchPair => match_pair(chars, pos).unwrap_or(pos),
+                Motion::Find { .. } => match find_in_line(chars, pos, motion) {
+                    Some(p) => p,
+                    None => break,
+                },
+                Motion::FileStart | Motion::FileEnd | Motion::RepeatFind { .. } => unreachable!(),
+            };
+        }
+        (pos, matches!(motion, Motion::Up | Motion::Down))
+    }
+
+    /// Resolves the range an operator acts on.
+    fn span(
+        &mut self,
+        chars: &[char],
+        cur: usize,
+        op: Operator,
+        target: Target,
+        count: Option<usize>,
+    ) -> Option<Span> {
+        let motion = match target {
+            Target::Line => {
+                let last = line_index(chars, line_of(chars, cur) + count.unwrap_or(1) - 1);
+                return Some(Span {
+                    start: line_start(chars, cur),
+                    end: line_end(chars, last),
+                    linewise: true,
+                });
+            }
+            Target::Object(object) => return text_object(chars, cur, object),
+            Target::Motion(motion) => motion,
+        };
+
+        // `cw` behaves like `ce` as long as the cursor is on a word
+        if let Motion::WordForward(big) = motion {
+            if op == Operator::Change && class(chars, cur, big) != 0 {
+                let mut end = cur;
+                for i in 0..count.unwrap_or(1) {
+                    if i > 0 || class(chars, end + 1, big) == class(chars, end, big) {
+                        end = word_end(chars, end, big);
+                    }
+                }
+                return Some(Span {
+                    start: cur,
+                    end: (end + 1).min(chars.len()),
+                    linewise: false,
+                });
+            }
+        }
+
+        let (target, linewise) = self.motion(chars, cur, motion, count);
+        let linewise = linewise || matches!(motion, Motion::FileStart | Motion::FileEnd);
+        let inclusive = matches!(
+            motion,
+            Motion::WordEnd(_)
+                | Motion::MatchPair
+                | Motion::Find { .. }
+                | Motion::RepeatFind { .. }
+        );
+        let [start, end] = sorted(cur, target);
+        let mut span = Span {
+            start,
+            end: if inclusive {
+                (end + 1).min(chars.len())
+            } else {
+                end
+            },
+            linewise,
+        };
+        // an exclusive motion ending at the start of a line excludes that line break
+        if !linewise
+            && !inclusive
+            && span.end > start
+            && chars[span.end - 1] == '\n'
+            && line_of(chars, start) < line_of(chars, span.end)
+        {
+            span.end -= 1;
+            span.linewise = start <= first_non_blank(chars, start)
+                && line_of(chars, start) < line_of(chars, span.end);
+        }
+        Some(span)
+    }
+
+    fn operate(
+        &mut self,
+        op: Operator,
+        register: Option<char>,
+        span: Span,
+        cur: usize,
+        text: &mut dyn TextBuffer,
+        chars: &[char],
+    ) -> VimResponse {
+        let span = line_span(chars, span.start, span.end, span.linewise);
+        let mut content: String = chars[span.start..span.end].iter().collect();
+        if span.linewise && !content.ends_with('\n') {
+            content.push('\n');
+        }
+
+        match op {
+            Operator::Yank => {
+                self.store(register, content, span.linewise, true);
+                let cursor = if span.linewise {
+                    cur.max(span.start).min(span.end)
+                } else {
+                    span.start
+                };
+                VimResponse::Cursor(one(clamp_normal(chars, cursor)))
+            }
+            Operator::Delete => {
+                self.store(register, content, span.linewise, false);
+                // deleting the last lines also removes the line break in front of them
+                let start = if span.linewise
+                    && span.end == chars.len()
+                    && span.start > 0
+                    && chars.last() != Some(&'\n')
+                {
+                    span.start - 1
+                } else {
+                    span.start
+                };
+                text.delete_char_range(start..span.end);
+                let chars = self::chars(text);
+                let cursor = if span.linewise {
+                    first_non_blank(&chars, start.min(chars.len()))
+                } else {
+                    clamp_normal(&chars, start)
+                };
+                VimResponse::Changed(one(cursor))
+            }
+            Operator::Change => {
+                self.store(register, content, span.linewise, false);
+                let (start, end) = if span.linewise {
+                    (
+                        first_non_blank(chars, span.start),
+                        line_end(chars, span.end.saturating_sub(1).max(span.start)),
+                    )
+                } else {
+                    (span.start, span.end)
+                };
+                text.delete_char_range(start..end.max(start));
+                self.mode = Mode::Insert;
+                VimResponse::Changed(one(start))
+            }
+            Operator::Indent | Operator::Outdent => {
+                let first = line_of(chars, span.start);
+                let last = line_of(chars, span.end.saturating_sub(1).max(span.start));
+                for line in (first..=last).rev() {
+                    let start = line_index(chars, line);
+                    if op == Operator::Indent {
+                        if line_end(chars, start) > start {
+                            text.insert_text(&self.indent.unit(), start);
+                        }
+                    } else {
+                        let width = self.indent.outdent_width(chars[start..].iter().copied());
+                        text.delete_char_range(start..start + width);
+                    }
+                }
+                let chars = self::chars(text);
+                VimResponse::Changed(one(first_non_blank(&chars, line_index(&chars, first))))
+            }
+            Operator::ToggleCase => {
+                let toggled: String = content
+                    .chars()
+                    .take(span.end - span.start)
+                    .map(toggle_case)
+                    .collect();
+                text.delete_char_range(span.start..span.end);
+                text.insert_text(&toggled, span.start);
+                VimResponse::Changed(one(span.start))
+            }
+        }
+    }
+
+    fn store(&mut self, register: Option<char>, text: String, linewise: bool, yank: bool) {
+        let content = Register { text, linewise };
+        match register {
+            Some('_') => return,
+            Some(name) if name.is_ascii_uppercase() => {
+                let reg = self.registers.entry(name.to_ascii_lowercase()).or_default();
+                reg.text.push_str(&content.text);
+                reg.linewise |= content.linewise;
+            }
+            Some(name @ ('+' | '*')) => {
+                self.clipboard = Some(content.text.clone());
+                self.registers.insert(name, content.clone());
+            }
+            Some(name) => {
+                self.registers.insert(name, content.clone());
+            }
+            None => {}
+        }
+        if yank {
+            self.registers.insert('0', content.clone());
+        }
+        self.registers.insert('"', content);
+    }
+}
+
+fn parse(keys: &[VimKey], visual: bool) -> Parse {
+    let mut keys = Keys { keys, pos: 0 };
+    match parse_command(&mut keys, visual) {
+        None => Parse::Incomplete,
+        Some(Some(command)) => Parse::Done(command),
+        Some(None) => Parse::Invalid,
+    }
+}
+
+struct Keys<'a> {
+    keys: &'a [VimKey],
+    pos: usize,
+}
+
+impl Keys<'_> {
+    fn next(&mut self) -> Option<VimKey> {
+        let key = self.keys.get(self.pos).copied();
+        self.pos += 1;
+        key
+    }
+
+    fn next_char(&mut self) -> Option<Option<char>> {
+        match self.next()? {
+            VimKey::Char(c) => Some(Some(c)),
+            _ => Some(None),
+        }
+    }
+
+    fn count(&mut self) -> Option<Option<usize>> {
+        let mut count: Option<usize> = None;
+        loop {
+            match self.keys.get(self.pos) {
+                Some(VimKey::Char('1'..='9')) => {}
+                Some(VimKey::Char('0')) if count.is_some() => {}
+                Some(_) => return Some(count),
+                None => return None,
+            };
+            if let Some(VimKey::Char(c)) = self.next() {
+                let digit = c.to_digit(10).unwrap_or_default() as usize;
+                count = Some(count.unwrap_or_default().saturating_mul(10) + digit);
+            }
+        }
+    }
+}
+
+/// `None` means more keys are needed, `Some(None)` means the keys are invalid.
+fn parse_command(keys: &mut Keys, visual: bool) -> Option<Option<Command>> {
+    let mut register = None;
+    if keys.keys.first() == Some(&VimKey::Char('"')) {
+        keys.next();
+        match keys.next_char()? {
+            Some(c) => register = Some(c),
+            None => return Some(None),
+        }
+    }
+    let count = keys.count()?;
+    let command = |kind| {
+        Some(Some(Command {
+            register,
+            count,
+            kind,
+        }))
+    };
+
+    let key = keys.next()?;
+    let operator = match key {
+        VimKey::Char('d') => Some(Operator::Delete),
+        VimKey::Char('c') => Some(Operator::Change),
+        VimKey::Char('y') => Some(Operator::Yank),
+        VimKey::Char('>') => Some(Operator::Indent),
+        VimKey::Char('<') => Some(Operator::Outdent),
+        _ => None,
+    };
+
+    if visual {
+        if let Some(op) = operator {
+            return command(CommandKind::OperateSelection(op));
+        }
+        let op = match key {
+            VimKey::Char('x') => Some(Operator::Delete),
+            VimKey::Char('s') => Some(Operator::Change),
+            VimKey::Char('~') => Some(Operator::ToggleCase),
+            _ => None,
+        };
+        if let Some(op) = op {
+            return command(CommandKind::OperateSelection(op));
+        }
+        if let VimKey::Char(c @ ('i' | 'a')) = key {
+            return match parse_object(c == 'i', keys.next_char()?) {
+                Some(object) => command(CommandKind::Select(object)),
+                None => Some(None),
+            };
+        }
+        if key == VimKey::Char('o') {
+            return command(CommandKind::Action(Action::SwapAnchor));
+        }
+    } else if let Some(op) = operator {
+        let motion_count = keys.count()?;
+        let count = match (count, motion_count) {
+            (None, None) => None,
+            (a, b) => Some(a.unwrap_or(1) * b.unwrap_or(1)),
+        };
+        let target = match keys.next()? {
+            VimKey::Char(c) if c == op_char(op) => Target::Line,
+            VimKey::Char(c @ ('i' | 'a')) => match parse_object(c == 'i', keys.next_char()?) {
+                Some(object) => Target::Object(object),
+                None => return Some(None),
+            },
+            key => match parse_motion(key, keys)? {
+                Some(motion) => Target::Motion(motion),
+                None => return Some(None),
+            },
+        };
+        return Some(Some(Command {
+            register,
+            count,
+            kind: CommandKind::Operate(op, target),
+        }));
+    }
+
+    let action = match key {
+        VimKey::Char('i') => Action::Insert,
+        VimKey::Char('a') => Action::Append,
+        VimKey::Char('I') => Action::InsertLineStart,
+        VimKey::Char('A') => Action::AppendLineEnd,
+        VimKey::Char('o') => Action::OpenBelow,
+        VimKey::Char('O') => Action::OpenAbove,
+        VimKey::Char('p') => Action::PasteAfter,
+        VimKey::Char('P') => Action::PasteBefore,
+        VimKey::Char('u') => Action::Undo,
+        VimKey::Ctrl('r') => Action::Redo,
+        VimKey::Char('J') => Action::JoinLines,
+        VimKey::Char('~') => Action::ToggleCase,
+        VimKey::Char('.') => Action::Repeat,
+        VimKey::Char('v') => Action::Visual,
+        VimKey::Char('V') => Action::VisualLine,
+        VimKey::Char(':') => Action::Command,
+        VimKey::Char('r') => match keys.next_char()? {
+            Some(c) => Action::Replace(c),
+            None => return Some(None),
+        },
+        VimKey::Char(c @ ('x' | 'X' | 's' | 'S' | 'C' | 'D' | 'Y')) => {
+            let (op, target) = match c {
+                'x' => (Operator::Delete, Target::Motion(Motion::Right)),
+                'X' => (Operator::Delete, Target::Motion(Motion::Left)),
+                's' => (Operator::Change, Target::Motion(Motion::Right)),
+                'S' => (Operator::Change, Target::Line),
+                'C' => (Operator::Change, Target::Motion(Motion::LineEnd)),
+                'D' => (Operator::Delete, Target::Motion(Motion::LineEnd)),
+                _ => (Operator::Yank, Target::Line),
+            };
+            return command(CommandKind::Operate(op, target));
+        }
+        key => {
+            return match parse_motion(key, keys)? {
+                Some(motion) => command(CommandKind::Move(motion)),
+                None => Some(None),
+            }
+        }
+    };
+    if visual
+        && !matches!(
+            action,
+            Action::PasteAfter
+                | Action::PasteBefore
+                | Action::Visual
+                | Action::VisualLine
+                | Action::Command
+        )
+    {
+        return Some(None);
+    }
+    command(CommandKind::Action(action))
+}
+
+fn op_char(op: Operator) -> char {
+    match op {
+        Operator::Delete => 'd',
+        Operator::Change => 'c',
+        Operator::Yank => 'y',
+        Operator::Indent => '>',
+        Operator::Outdent => '<',
+        Operator::ToggleCase => '~',
+    }
+}
+
+fn parse_motion(key: VimKey, keys: &mut Keys) -> Option<Option<Motion>> {
+    let motion = match key {
+        VimKey::Char('h') | VimKey::Backspace => Motion::Left,
+        VimKey::Char('l') | VimKey::Char(' ') => Motion::Right,
+        VimKey::Char('k') => Motion::Up,
+        VimKey::Char('j') | VimKey::Enter => Motion::Down,
+        VimKey::Char('w') => Motion::WordForward(false),
+        VimKey::Char('W') => Motion::WordForward(true),
+        VimKey::Char('b') => Motion::WordBackward(false),
+        VimKey::Char('B') => Motion::WordBackward(true),
+        VimKey::Char('e') => Motion::WordEnd(false),
+        VimKey::Char('E') => Motion::WordEnd(true),
+        VimKey::Char('0') => Motion::LineStart,
+        VimKey::Char('^') => Motion::FirstNonBlank,
+        VimKey::Char('$') => Motion::LineEnd,
+        VimKey::Char('G') => Motion::FileEnd,
+        VimKey::Char('}') => Motion::ParagraphForward,
+        VimKey::Char('{') => Motion::ParagraphBackward,
+        VimKey::Char('%') => Motion::MatchPair,
+        VimKey::Char(';') => Motion::RepeatFind { reverse: false },
+        VimKey::Char(',') => Motion::RepeatFind { reverse: true },
+        VimKey::Char('g') => match keys.next_char()? {
+            Some('g') => Motion::FileStart,
+            _ => return Some(None),
+        },
+        VimKey::Char(c @ ('f' | 'F' | 't' | 'T')) => match keys.next_char()? {
+            Some(char) => Motion::Find {
+                forward: c.is_lowercase(),
+                till: c == 't' || c == 'T',
+                char,
+            },
+            None => return Some(None),
+        },
+        _ => return Some(None),
+    };
+    Some(Some(motion))
+}
+
+fn parse_object(inner: bool, key: Option<char>) -> Option<TextObject> {
+    Some(match key? {
+        'w' => TextObject::Word { inner, big: false },
+        'W' => TextObject::Word { inner, big: true },
+        'p' => TextObject::Paragraph { inner },
+        quote @ ('"' | '\'' | '`') => TextObject::Quote { inner, quote },
+        '(' | ')' | 'b' => TextObject::Pair {
+            inner,
+            open: '(',
+            close: ')',
+        },
+        '{' | '}' | 'B' => TextObject::Pair {
+            inner,
+            open: '{',
+            close: '}',
+        },
+        '[' | ']' => TextObject::Pair {
+            inner,
+            open: '[',
+            close: ']',
+        },
+        '<' | '>' => TextObject::Pair {
+            inner,
+            open: '<',
+            close: '>',
+        },
+        _ => return None,
+    })
+}
+
+fn text_object(chars: &[char], cur: usize, object: TextObject) -> Option<Span> {
+    match object {
+        TextObject::Word { inner, big } => {
+            if cur >= chars.len() || chars[cur] == '\n' {
+                return None;
+            }
+            let kind = class(chars, cur, big);
+            let mut start = cur;
+            while start > 0 && chars[start - 1] != '\n' && class(chars, start - 1, big) == kind {
+                start -= 1;
+            }
+            let mut end = cur + 1;
+            while end < chars.len() && chars[end] != '\n' && class(chars, end, big) == kind {
+                end += 1;
+            }
+            if !inner {
+                let trailing = end;
+                while end < chars.len() && (chars[end] == ' ' || chars[end] == '\t') {
+                    end += 1;
+                }
+                if end == trailing {
+                    while start > line_start(chars, start)
+                        && (chars[start - 1] == ' ' || chars[start - 1] == '\t')
+                    {
+                        start -= 1;
+                    }
+                }
+            }
+            Some(Span {
+                start,
+                end,
+                linewise: false,
+            })
+        }
+        TextObject::Quote { inner, quote } => {
+            let (start, end) = (line_start(chars, cur), line_end(chars, cur));
+            let quotes: Vec<usize> = (start..end).filter(|i| chars[*i] == quote).collect();
+            let (open, close) = quotes
+                .chunks_exact(2)
+                .map(|pair| (pair[0], pair[1]))
+                .find(|(_, close)| cur <= *close)?;
+            Some(if inner {
+                Span {
+                    start: open + 1,
+                    end: close,
+                    linewise: false,
+                }
+            } else {
+                Span {
+                    start: open,
+                    end: close + 1,
+                    linewise: false,
+                }
+            })
+        }
+        TextObject::Pair { inner, open, close } => {
+            let mut depth = 0;
+            let mut start = None;
+            for i in (0..=cur.min(chars.len().saturating_sub(1))).rev() {
+                if chars[i] == close && i != cur {
+                    depth += 1;
+                } else if chars[i] == open {
+                    if depth == 0 {
+                        start = Some(i);
+                        break;
+                    }
+                    depth -= 1;
+                }
+            }
+            let start = start?;
+            let end = match_pair(chars, start)?;
+            Some(if inner {
+                Span {
+                    start: start + 1,
+                    end,
+                    linewise: false,
+                }
+            } else {
+                Span {
+                    start,
+                    end: end + 1,
+                    linewise: false,
+                }
+            })
+        }
+        TextObject::Paragraph { inner } => {
+            let blank = |line: usize| empty_line(chars, line);
+            let current = line_of(chars, cur);
+            let kind = blank(current);
+            let last_line = line_of(chars, chars.len());
+            let mut first = current;
+            while first > 0 && blank(first - 1) == kind {
+                first -= 1;
+            }
+            let mut last = current;
+            while last < last_line && blank(last + 1) == kind {
+                last += 1;
+            }
+            if !inner {
+                while last < last_line && blank(last + 1) != kind {
+                    last += 1;
+                }
+            }
+            Some(Span {
+                start: line_index(chars, first),
+                end: line_end(chars, line_index(chars, last)),
+                linewise: true,
+            })
+        }
+    }
+}
+
+/// Extends a linewise span to whole lines including the final line break.
+fn line_span(chars: &[char], start: usize, end: usize, linewise: bool) -> Span {
+    if !linewise {
+        return Span {
+            start,
+            end,
+            linewise,
+        };
+    }
+    let last = line_end(chars, end.saturating_sub(1).max(start));
+    Span {
+        start: line_start(chars, start),
+        end: (last + 1).min(chars.len()),
+        linewise,
+    }
+}
+
+fn chars(text: &dyn TextBuffer) -> Vec<char> {
+    text.as_str().chars().collect()
+}
+
+fn one(index: usize) -> CCursorRange {
+    CCursorRange::one(CCursor::new(index))
+}
+
+fn sorted(a: usize, b: usize) -> [usize; 2] {
+    [a.min(b), a.max(b)]
+}
+
+fn toggle_case(c: char) -> char {
+    if c.is_uppercase() {
+        c.to_lowercase().next().unwrap_or(c)
+    } else {
+        c.to_uppercase().next().unwrap_or(c)
+    }
+}
+
+fn line_start(chars: &[char], index: usize) -> usize {
+    let mut i = index.min(chars.len());
+    while i > 0 && chars[i - 1] != '\n' {
+        i -= 1;
+    }
+    i
+}
+
+/// The index of the line break ending the line, or the text length.
+fn line_end(chars: &[char], index: usize) -> usize {
+    let mut i = index.min(chars.len());
+    while i < chars.len() && chars[i] != '\n' {
+        i += 1;
+    }
+    i
+}
+
+fn first_non_blank(chars: &[char], index: usize) -> usize {
+    let mut i = line_start(chars, index);
+    let end = line_end(chars, index);
+    while i < end && (chars[i] == ' ' || chars[i] == '\t') {
+        i += 1;
+    }
+    i
+}
+
+fn indentation(chars: &[char], index: usize) -> String {
+    chars[line_start(chars, index)..first_non_blank(chars, index)]
+        .iter()
+        .collect()
+}
+
+fn line_of(chars: &[char], index: usize) -> usize {
+    chars[..index.min(chars.len())]
+        .iter()
+        .filter(|c| **c == '\n')
+        .count()
+}
+
+/// The start index of the given line, clamped to the last line.
+fn line_index(chars: &[char], line: usize) -> usize {
+    let mut start = 0;
+    for _ in 0..line {
+        let end = line_end(chars, start);
+        if end >= chars.len() {
+            break;
+        }
+        start = end + 1;
+    }
+    start
+}
+
+/// In normal mode the cursor sits on a character, never behind the last one of a line.
+fn clamp_normal(chars: &[char], index: usize) -> usize {
+    let index = index.min(chars.len());
+    let (start, end) = (line_start(chars, index), line_end(chars, index));
+    if index >= end && end > start {
+        end - 1
+    } else {
+        index
+    }
+}
+
+/// 0: whitespace, 1: word characters, 2: punctuation
+fn class(chars: &[char], index: usize, big: bool) -> u8 {
+    match chars.get(index) {
+        None => 0,
+        Some(c) if c.is_whitespace() => 0,
+        Some(_) if big => 1,
+        Some(c) if c.is_alphanumeric() || *c == '_' => 1,
+        Some(_) => 2,
+    }
+}
+
+fn word_forward(chars: &[char], index: usize, big: bool) -> usize {
+    let kind = class(chars, index, big);
+    let mut i = index;
+    if kind != 0 {
+        while i < chars.len() && class(chars, i, big) == kind {
+            i += 1;
+        }
+    }
+    while i < chars.len() && class(chars, i, big) == 0 {
+        // empty lines are words as well
+        if i > index && chars[i] == '\n' && chars[i - 1] == '\n' {
+            return i;
+        }
+        i += 1;
+    }
+    i
+}
+
+fn word_backward(chars: &[char], index: usize, big: bool) -> usize {
+    let mut i = index;
+    while i > 0 && class(chars, i - 1, big) == 0 {
+        i -= 1;
+        if chars[i] == '\n' && i > 0 && chars[i - 1] == '\n' {
+            return i;
+        }
+    }
+    if i == 0 {
+        return 0;
+    }
+    let kind = class(chars, i - 1, big);
+    while i > 0 && class(chars, i - 1, big) == kind {
+        i -= 1;
+    }
+    i
+}
+
+fn word_end(chars: &[char], index: usize, big: bool) -> usize {
+    let mut i = index + 1;
+    while i < chars.len() && class(chars, i, big) == 0 {
+        i += 1;
+    }
+    if i >= chars.len() {
+        return chars.len().saturating_sub(1).max(index);
+    }
+    let kind = class(chars, i, big);
+    while i + 1 < chars.len() && class(chars, i + 1, big) == kind {
+        i += 1;
+    }
+    i
+}
+
+fn paragraph(chars: &[char], index: usize, forward: bool) -> usize {
+    let last = line_of(chars, chars.len());
+    let mut line = line_of(chars, index);
+    if forward {
+        while line < last && empty_line(chars, line) {
+            line += 1;
+        }
+        while line < last && !empty_line(chars, line) {
+            line += 1;
+        }
+        if empty_line(chars, line) {
+            line_index(chars, line)
+        } else {
+            chars.len()
+        }
+    } else {
+        while line > 0 && empty_line(chars, line) {
+            line -= 1;
+        }
+        while line > 0 && !empty_line(chars, line) {
+            line -= 1;
+        }
+        line_index(chars, line)
+    }
+}
+
+fn empty_line(chars: &[char], line: usize) -> bool {
+    let start = line_index(chars, line);
+    line_end(chars, start) == start
+}
+
+fn match_pair(chars: &[char], index: usize) -> Option<usize> {
+    const PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+    // the first bracket on the line starting at the cursor
+    let start = (index..line_end(chars, index)).find(|i| {
+        PAIRS
+            .iter()
+            .any(|(o, c)| chars[*i] == *o || chars[*i] == *c)
+    })?;
+    let (open, close) = *PAIRS
+        .iter()
+        .find(|(o, c)| chars[start] == *o || chars[start] == *c)?;
+    let mut depth = 0;
+    if chars[start] == open {
+        for (i, c) in chars.iter().enumerate().skip(start) {
+            if *c == open {
+                depth += 1;
+            } else if *c == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+        }
+    } else {
+        for i in (0..=start).rev() {
+            if chars[i] == close {
+                depth += 1;
+            } else if chars[i] == open {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+        }
+    }
+    None
+}
+
+fn find_in_line(chars: &[char], index: usize, motion: Motion) -> Option<usize> {
+    let Motion::Find {
+        forward,
+        till,
+        char,
+    } = motion
+    else {
+        return None;
+    };
+    if forward {
+        let end = line_end(chars, index);
+        let found = (index + 1..end).find(|i| chars[*i] == char)?;
+        Some(if till { found - 1 } else { found })
+    } else {
+        let start = line_start(chars, index);
+        let found = (start..index).rev().find(|i| chars[*i] == char)?;
+        Some(if till { found + 1 } else { found })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::egui::Modifiers;
+
+    use super::*;
+
+    /// The register, count and kind of a command.
+    type Parsed = (Option<char>, Option<usize>, CommandKind);
+
+    /// `None` while incomplete, `Some(None)` for invalid keys.
+    fn parsed(keys: &str, visual: bool) -> Option<Option<Parsed>> {
+        let keys = keys.chars().map(VimKey::Char).collect::<Vec<_>>();
+        match parse(&keys, visual) {
+            Parse::Incomplete => None,
+            Parse::Invalid => Some(None),
+            Parse::Done(command) => Some(Some((command.register, command.count, command.kind))),
+        }
+    }
+
+    fn operate(op: Operator, target: Target) -> CommandKind {
+        CommandKind::Operate(op, target)
+    }
+
+    /// Types `keys` into vim, `\x1b` is Escape, and returns the text and the cursor.
+    fn run(text: &str, cursor: usize, keys: &str) -> (String, usize, Mode) {
+        let mut vim = Vim {
+            enabled: true,
+            ..Default::default()
+        };
+        let mut text = text.to_string();
+        let mut state = TextEditState::default();
+        let mut cursor = one(cursor);
+        for c in keys.chars() {
+            let key = |key| Event::Key {
+                key,
+                physical_key: None,
+                pressed: true,
+                repeat: false,
+                modifiers: Modifiers::NONE,
+            };
+            let event = match c {
+                '\x1b' => key(Key::Escape),
+                '\n' if vim.mode != Mode::Insert => key(Key::Enter),
+                c => Event::Text(c.to_string()),
+            };
+            match vim.on_event(&event, &mut text, &mut state, cursor) {
+                VimResponse::Cursor(moved) | VimResponse::Changed(moved) => cursor = moved,
+                // typed in insert mode, the editor inserts it
+                VimResponse::Pass => {
+                    if let Event::Text(typed) = event {
+                        text.insert_text(&typed, cursor.primary.index);
+                        cursor = one(cursor.primary.index + typed.chars().count());
+                    }
+                }
+                VimResponse::Consumed => {}
+            }
+        }
+        (text, cursor.primary.index, vim.mode)
+    }
+
+    #[test]
+    fn parses_motions() {
+        let motion = |keys| parsed(keys, false).flatten().map(|(_, _, kind)| kind);
+        assert_eq!(
+            motion("w"),
+            Some(CommandKind::Move(Motion::WordForward(false)))
+        );
+        assert_eq!(motion("E"), Some(CommandKind::Move(Motion::WordEnd(true))));
+        assert_eq!(motion("gg"), Some(CommandKind::Move(Motion::FileStart)));
+        assert_eq!(
+            motion("Tx"),
+            Some(CommandKind::Move(Motion::Find {
+                forward: false,
+                till: true,
+                char: 'x'
+            }))
+        );
+        assert_eq!(parsed("g", false), None);
+        assert_eq!(parsed("f", false), None);
+        assert_eq!(parsed("gx", false), Some(None));
+        assert_eq!(parsed("z", false), Some(None));
+    }
+
+    #[test]
+    fn parses_counts_and_registers() {
+        assert_eq!(
+            parsed("10j", false),
+            Some(Some((None, Some(10), CommandKind::Move(Motion::Down))))
+        );
+        // `0` is a motion unless it continues a count
+        assert_eq!(
+            parsed("0", false),
+            Some(Some((None, None, CommandKind::Move(Motion::LineStart))))
+        );
+        // the counts of the operator and the motion multiply
+        assert_eq!(
+            parsed("2d3w", false),
+            Some(Some((
+                None,
+                Some(6),
+                operate(Operator::Delete, Target::Motion(Motion::WordForward(false)))
+            )))
+        );
+        assert_eq!(
+            parsed("\"a2yy", false),
+            Some(Some((
+                Some('a'),
+                Some(2),
+                operate(Operator::Yank, Target::Line)
+            )))
+        );
+        assert_eq!(parsed("\"", false), None);
+        assert_eq!(parsed("3", false), None);
+    }
+
+    #[test]
+    fn parses_operators() {
+        let kind = |keys| parsed(keys, false).flatten().map(|(_, _, kind)| kind);
+        assert_eq!(kind("dd"), Some(operate(Operator::Delete, Target::Line)));
+        assert_eq!(kind(">>"), Some(operate(Operator::Indent, Target::Line)));
+        assert_eq!(
+            kind("ci\""),
+            Some(operate(
+                Operator::Change,
+                Target::Object(TextObject::Quote {
+                    inner: true,
+                    quote: '"'
+                })
+            ))
+        );
+        assert_eq!(
+            kind("dab"),
+            Some(operate(
+                Operator::Delete,
+                Target::Object(TextObject::Pair {
+                    inner: false,
+                    open: '(',
+                    close: ')'
+                })
+            ))
+        );
+        assert_eq!(
+            kind("D"),
+            Some(operate(Operator::Delete, Target::Motion(Motion::LineEnd)))
+        );
+        assert_eq!(kind("rx"), Some(CommandKind::Action(Action::Replace('x'))));
+        assert_eq!(parsed("d", false), None);
+        assert_eq!(parsed("di", false), None);
+        assert_eq!(parsed("dy", false), Some(None));
+        assert_eq!(parsed("diz", false), Some(None));
+    }
+
+    #[test]
+    fn parses_visual_commands() {
+        let kind = |keys| parsed(keys, true).flatten().map(|(_, _, kind)| kind);
+        assert_eq!(
+            kind("d"),
+            Some(CommandKind::OperateSelection(Operator::Delete))
+        );
+        assert_eq!(
+            kind("~"),
+            Some(CommandKind::OperateSelection(Operator::ToggleCase))
+        );
+        assert_eq!(
+            kind("iw"),
+            Some(CommandKind::Select(TextObject::Word {
+                inner: true,
+                big: false
+            }))
+        );
+        assert_eq!(kind("o"), Some(CommandKind::Action(Action::SwapAnchor)));
+        assert_eq!(kind("p"), Some(CommandKind::Action(Action::PasteAfter)));
+        assert_eq!(parsed("u", true), Some(None));
+    }
+
+    #[test]
+    fn operators_edit_text() {
+        assert_eq!(run("hello world", 0, "dw").0, "world");
+        assert_eq!(run("hello world", 0, "cwbye\x1b").0, "bye world");
+        assert_eq!(run("a\nb\nc\n", 0, "dd").0, "b\nc\n");
+        assert_eq!(run("a\nb\nc", 4, "dd").0, "a\nb");
+        assert_eq!(run("a\nb\nc\n", 0, "2dd").0, "c\n");
+        assert_eq!(run("foo(bar, baz)", 5, "di(").0, "foo()");
+        assert_eq!(run("x = \"abc\";", 6, "ci\"q\x1b").0, "x = \"q\";");
+        assert_eq!(run("a b c d", 0, "dtc").0, "c d");
+        assert_eq!(run("a b c d", 0, "fcD").0, "a b ");
+        assert_eq!(run("abc", 0, ">>").0, "\tabc");
+        assert_eq!(run("abc def", 0, "vex").0, " def");
+        assert_eq!(run("a\nb\nc\n", 0, "Vjd").0, "c\n");
+    }
+
+    #[test]
+    fn registers_and_repeat() {
+        assert_eq!(run("a\nb\nc\n", 0, "yyp").0, "a\na\nb\nc\n");
+        assert_eq!(run("abc", 0, "\"ayy\"ap").0, "abc\nabc");
+        assert_eq!(run("one two three", 0, "dw.").0, "three");
+        assert_eq!(run("abc", 0, "x..").0, "");
+        assert_eq!(run("abc", 0, "ix\x1bj.").0, "xxabc");
+    }
+
+    #[test]
+    fn motions_move_the_cursor() {
+        assert_eq!(run("abc", 0, "$").1, 2);
+        assert_eq!(run("a\nb\nc", 0, "G").1, 4);
+        assert_eq!(run("a\nb\nc", 4, "gg").1, 0);
+        assert_eq!(run("a\nb\nc", 0, "2G").1, 2);
+        assert_eq!(run("a\n\nb\nc\n\nd", 0, "}").1, 2);
+        assert_eq!(run("abc def", 0, "vey").2, Mode::Normal);
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+pub const EXTENSION: &str = "eta-workspace";
+
+/// Folders opened next to the project path.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Workspace {
+    /// Where the workspace was opened from or saved to, added and removed folders are written
+    /// back to it.
+    pub file: Option<PathBuf>,
+    pub folders: Vec<PathBuf>,
+}
+
+/// The TOML file, folders are relative to it so it can be committed.
+#[derive(Serialize, Deserialize)]
+struct WorkspaceFile {
+    folders: Vec<PathBuf>,
+}
+
+/// Reads the roots of the workspace `file`.
+pub fn load(file: &Path) -> io::Result<Vec<PathBuf>> {
+    let content = fs::read_to_string(file)?;
+    let parsed = toml::from_str::<WorkspaceFile>(&content)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.message().to_string()))?;
+    let dir = canonical(file.parent().unwrap_or(Path::new("")));
+    let roots = parsed
+        .folders
+        .into_iter()
+        .map(|folder| canonical(&dir.join(folder)))
+        .collect::<Vec<_>>();
+    if roots.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "The workspace has no folders",
+        ));
+    }
+    Ok(roots)
+}
+
+/// Writes `roots` to the workspace `file`.
+pub fn save(file: &Path, roots: &[PathBuf]) -> io::Result<()> {
+    // both sides resolved, or symlinks end up as `../` detours
+    let dir = canonical(file.parent().unwrap_or(Path::new("")));
+    let folders = roots
+        .iter()
+        .map(|root| relative(&canonical(root), &dir))
+        .collect();
+    let content = toml::to_string_pretty(&WorkspaceFile { folders }).map_err(io::Error::other)?;
+    fs::write(file, content)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or(path.to_path_buf())
+}
+
+/// `path` relative to the directory `base`, e.g. `../vendor/lib`.
+fn relative(path: &Path, base: &Path) -> PathBuf {
+    let path_components = path.components().collect::<Vec<_>>();
+    let base_components = base.components().collect::<Vec<_>>();
+    let common = path_components
+        .iter()
+        .zip(&base_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+    // different drives or roots
+    if common == 0 && path.has_root() {
+        return path.to_path_buf();
+    }
+    let relative = base_components[common..]
+        .iter()
+        .map(|_| Component::ParentDir)
+        .chain(path_components[common..].iter().copied())
+        .collect::<PathBuf>();
+    if relative.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        relative
+    }
+}